@@ -0,0 +1,73 @@
+use rusqlite::{Connection, params};
+use serde::Serialize;
+use shared::{Action, ApiError};
+
+use crate::AppState;
+
+/// How many entries admins are shown.
+const AUDIT_LOG_LIMIT: u32 = 200;
+
+pub fn init_audit_table(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS audit_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            at INTEGER NOT NULL DEFAULT (unixepoch()),
+            actor TEXT NOT NULL,
+            action TEXT NOT NULL,
+            node_id TEXT NOT NULL,
+            details TEXT NOT NULL
+        );",
+    )
+}
+
+/// One admin change to the shape of the story.
+#[derive(Serialize)]
+pub struct AuditEntry {
+    pub at: u64,
+    pub actor: String,
+    pub action: String,
+    pub node_id: String,
+    pub details: serde_json::Value,
+}
+
+/// Records that `actor` did `action` to `node_id`. Pass the transaction making the change,
+/// so the entry is only kept if the change is.
+pub fn record(
+    conn: &Connection,
+    actor: &str,
+    action: &str,
+    node_id: &str,
+    details: &serde_json::Value,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO audit_log (actor, action, node_id, details) VALUES (?1, ?2, ?3, ?4)",
+        params![actor, action, node_id, details.to_string()],
+    )?;
+    Ok(())
+}
+
+impl AppState {
+    /// The most recent audit log entries, newest first.
+    pub fn audit_log(&self, admin: Option<&str>) -> Result<Vec<AuditEntry>, ApiError> {
+        self.authorize(admin, Action::Restructure)?;
+        self.db
+            .prepare("SELECT at, actor, action, node_id, details FROM audit_log ORDER BY id DESC LIMIT ?1")
+            .and_then(|mut stmt| {
+                stmt.query_map(params![AUDIT_LOG_LIMIT], |row| {
+                    let details: String = row.get(4)?;
+                    Ok(AuditEntry {
+                        at: row.get(0)?,
+                        actor: row.get(1)?,
+                        action: row.get(2)?,
+                        node_id: row.get(3)?,
+                        details: serde_json::from_str(&details).unwrap_or(serde_json::Value::String(details)),
+                    })
+                })?
+                .collect()
+            })
+            .map_err(|e| {
+                tracing::error!("Failed to load audit log: {}", e);
+                ApiError::Internal
+            })
+    }
+}
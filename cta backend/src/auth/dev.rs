@@ -0,0 +1,35 @@
+use async_trait::async_trait;
+
+use super::{AuthError, AuthProvider, Credential, Identity};
+
+/// Believes whatever username the configured header says. Only for local development.
+pub struct DevAuth {
+    header: String,
+}
+
+impl DevAuth {
+    pub fn new(header: String) -> Self {
+        Self { header }
+    }
+}
+
+#[async_trait]
+impl AuthProvider for DevAuth {
+    fn name(&self) -> &'static str {
+        "dev"
+    }
+
+    async fn verify(&self, credential: &Credential) -> Result<Identity, AuthError> {
+        let username = credential
+            .headers
+            .get(self.header.as_str())
+            .and_then(|v| v.to_str().ok())
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .ok_or(AuthError::Missing)?;
+        Ok(Identity {
+            id: format!("dev:{}", username),
+            username: username.to_string(),
+        })
+    }
+}
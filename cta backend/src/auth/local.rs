@@ -0,0 +1,165 @@
+use std::sync::{Arc, Mutex};
+
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use async_trait::async_trait;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use rusqlite::{Connection, OptionalExtension, params};
+use shared::ApiError;
+
+use super::{AuthError, AuthProvider, Credential, Identity};
+
+const USERNAME_MAX_CHARS: usize = 32;
+const PASSWORD_MIN_CHARS: usize = 8;
+
+/// Username/password accounts kept in the server's own SQLite database, for instances
+/// that don't run on Newgrounds.
+pub struct LocalAuth {
+    db: Arc<Mutex<Connection>>,
+}
+
+impl LocalAuth {
+    pub fn open(db_path: &str) -> Self {
+        let conn = Connection::open(db_path).expect("Failed to open SQLite database");
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS local_users (
+                username TEXT PRIMARY KEY COLLATE NOCASE,
+                password_hash TEXT NOT NULL,
+                created_at INTEGER NOT NULL DEFAULT (unixepoch())
+            );",
+        )
+        .expect("Failed to create local_users table");
+        Self {
+            db: Arc::new(Mutex::new(conn)),
+        }
+    }
+}
+
+/// Username and password from `Authorization: Basic`, or from a `username:password` token.
+fn username_and_password(credential: &Credential) -> Option<(String, String)> {
+    let basic = credential
+        .headers
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Basic "))
+        .and_then(|encoded| STANDARD.decode(encoded.trim()).ok())
+        .and_then(|decoded| String::from_utf8(decoded).ok());
+
+    let pair = basic.or_else(|| credential.token.clone())?;
+    let (username, password) = pair.split_once(':')?;
+    Some((username.to_string(), password.to_string()))
+}
+
+impl Identity {
+    fn local(username: String) -> Self {
+        Self {
+            // Usernames are case-insensitive here
+            id: format!("local:{}", username.to_lowercase()),
+            username,
+        }
+    }
+}
+
+fn invalid(field: &str, reason: impl Into<String>) -> ApiError {
+    ApiError::Validation {
+        field: field.into(),
+        reason: reason.into(),
+    }
+}
+
+fn check_new_account(username: &str, password: &str) -> Result<(), ApiError> {
+    let len = username.chars().count();
+    if len == 0 || len > USERNAME_MAX_CHARS {
+        return Err(invalid(
+            "username",
+            format!("Usernames must be 1 to {} characters", USERNAME_MAX_CHARS),
+        ));
+    }
+    if !username.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+        return Err(invalid("username", "Usernames may only use letters, digits, _ and -"));
+    }
+    if password.chars().count() < PASSWORD_MIN_CHARS {
+        return Err(invalid(
+            "password",
+            format!("Passwords must be at least {} characters", PASSWORD_MIN_CHARS),
+        ));
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl AuthProvider for LocalAuth {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    async fn verify(&self, credential: &Credential) -> Result<Identity, AuthError> {
+        let (username, password) = username_and_password(credential).ok_or(AuthError::Missing)?;
+        let db = self.db.clone();
+
+        // Argon2 is deliberately slow, so keep it off the async workers
+        tokio::task::spawn_blocking(move || {
+            let row: Option<(String, String)> = db
+                .lock()
+                .unwrap()
+                .query_row(
+                    "SELECT username, password_hash FROM local_users WHERE username = ?1",
+                    params![username],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()
+                .map_err(|e| AuthError::Unavailable(e.to_string()))?;
+            let (username, hash) = row.ok_or(AuthError::Rejected)?;
+
+            let hash = PasswordHash::new(&hash).map_err(|e| AuthError::Unavailable(e.to_string()))?;
+            Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .map_err(|_| AuthError::Rejected)?;
+            Ok(Identity::local(username))
+        })
+        .await
+        .map_err(|e| AuthError::Unavailable(e.to_string()))?
+    }
+
+    async fn register(&self, username: String, password: String) -> Result<Identity, ApiError> {
+        let username = username.trim().to_string();
+        check_new_account(&username, &password)?;
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let salt = SaltString::generate(&mut OsRng);
+            let hash = Argon2::default()
+                .hash_password(password.as_bytes(), &salt)
+                .map_err(|e| {
+                    tracing::error!("Failed to hash password: {}", e);
+                    ApiError::Internal
+                })?
+                .to_string();
+
+            let inserted = db
+                .lock()
+                .unwrap()
+                .execute(
+                    "INSERT OR IGNORE INTO local_users (username, password_hash) VALUES (?1, ?2)",
+                    params![username, hash],
+                )
+                .map_err(|e| {
+                    tracing::error!("Failed to insert local user: {}", e);
+                    ApiError::Internal
+                })?;
+            if inserted == 0 {
+                return Err(invalid("username", "That username is taken"));
+            }
+
+            tracing::info!("Registered local user {}", username);
+            Ok(Identity::local(username))
+        })
+        .await
+        .map_err(|e| {
+            tracing::error!("Registration task failed: {}", e);
+            ApiError::Internal
+        })?
+    }
+}
@@ -0,0 +1,172 @@
+mod dev;
+mod local;
+mod newgrounds;
+mod session;
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+use actix_web::http::header::HeaderMap;
+use actix_web::{HttpRequest, HttpResponse, Responder, Scope, post, web};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use shared::ApiError;
+
+use crate::{AppState, get_env};
+use crate::rate_limit::{Limited, RateLimiter};
+use crate::rest::error_response;
+
+pub use dev::DevAuth;
+pub use local::LocalAuth;
+pub use newgrounds::NewgroundsAuth;
+pub use session::Sessions;
+
+/// Whatever the client sent to prove who it is.
+pub struct Credential {
+    /// Opaque token: the `session_id` of a bincode message, or an `Authorization: Bearer` header.
+    pub token: Option<String>,
+    /// Headers of the request, for providers that read their own.
+    pub headers: HeaderMap,
+}
+
+impl Credential {
+    /// Credential for a bincode request, which carries its token inside the message.
+    pub fn new(req: &HttpRequest, token: Option<String>) -> Self {
+        Self {
+            token: token.filter(|t| !t.is_empty()),
+            headers: req.headers().clone(),
+        }
+    }
+
+    /// Credential for a REST request, taking the token from `Authorization: Bearer <token>`.
+    pub fn from_bearer(req: &HttpRequest) -> Self {
+        let token = req
+            .headers()
+            .get("Authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .map(|s| s.trim().to_string());
+        Self::new(req, token)
+    }
+}
+
+/// A verified user.
+#[derive(Clone, Debug, Serialize)]
+pub struct Identity {
+    /// Stays the same if the user is renamed, e.g. `ng:12345`.
+    pub id: String,
+    pub username: String,
+}
+
+#[derive(Debug)]
+pub enum AuthError {
+    /// No credential the provider understands was sent.
+    Missing,
+    /// The credential was checked and is wrong or expired.
+    Rejected,
+    /// The provider couldn't check the credential.
+    Unavailable(String),
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing => write!(f, "no credential"),
+            Self::Rejected => write!(f, "credential rejected"),
+            Self::Unavailable(e) => write!(f, "provider unavailable: {}", e),
+        }
+    }
+}
+
+/// A way of telling who sent a request, chosen per deployment with `AUTH_PROVIDER`.
+#[async_trait]
+pub trait AuthProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    async fn verify(&self, credential: &Credential) -> Result<Identity, AuthError>;
+
+    /// Creates an account, for providers that keep their own.
+    async fn register(&self, _username: String, _password: String) -> Result<Identity, ApiError> {
+        Err(ApiError::BadRequest("This server doesn't support creating accounts".into()))
+    }
+}
+
+/// Builds the provider named by `AUTH_PROVIDER` (`newgrounds`, `local` or `dev`), wrapped
+/// so that signed-in clients authenticate with our own session tokens.
+pub fn from_env(client: reqwest::Client, db_path: &str) -> Arc<Sessions> {
+    Arc::new(Sessions::from_env(provider_from_env(client, db_path)))
+}
+
+fn provider_from_env(client: reqwest::Client, db_path: &str) -> Arc<dyn AuthProvider> {
+    match get_env("AUTH_PROVIDER", "newgrounds").as_str() {
+        "local" => Arc::new(LocalAuth::open(db_path)),
+        "dev" => {
+            let header = get_env("DEV_AUTH_HEADER", "X-Dev-User");
+            tracing::warn!("Using dev auth: anyone can act as any user by setting {}", header);
+            Arc::new(DevAuth::new(header))
+        }
+        "newgrounds" => Arc::new(NewgroundsAuth::from_env(client)),
+        other => panic!("Unknown AUTH_PROVIDER {:?}; expected newgrounds, local or dev", other),
+    }
+}
+
+/// Resolves a credential to the stable id of the verified user, which is what gets stored
+/// as authorship, roles and bans. Failures count as signed out.
+pub async fn verified_user(auth: &dyn AuthProvider, credential: &Credential) -> Option<String> {
+    match auth.verify(credential).await {
+        Ok(identity) => Some(identity.id),
+        Err(AuthError::Missing) => None,
+        Err(e) => {
+            tracing::warn!("{} verification failed: {}", auth.name(), e);
+            None
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Registration {
+    username: String,
+    password: String,
+}
+
+/// Exchanges the credential in the request for a session token.
+#[post("/login")]
+async fn login(
+    req: HttpRequest,
+    data: web::Data<Mutex<AppState>>,
+    sessions: web::Data<Sessions>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    if let Err(e) = limits.check(Limited::Auth, &req, None) {
+        return error_response(e);
+    }
+    let result = sessions.login(&Credential::from_bearer(&req)).await.and_then(|(identity, token)| {
+        data.lock().unwrap().remember_user(&identity)?;
+        Ok(token)
+    });
+    match result {
+        Ok(token) => HttpResponse::Ok().json(token),
+        Err(error) => error_response(error),
+    }
+}
+
+#[post("/register")]
+async fn register(
+    req: HttpRequest,
+    body: web::Json<Registration>,
+    auth: web::Data<dyn AuthProvider>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    if let Err(e) = limits.check(Limited::Auth, &req, None) {
+        return error_response(e);
+    }
+    let body = body.into_inner();
+    match auth.register(body.username, body.password).await {
+        Ok(identity) => HttpResponse::Created().json(identity),
+        Err(error) => error_response(error),
+    }
+}
+
+pub fn routes() -> Scope {
+    web::scope("/api/auth").service(login).service(register)
+}
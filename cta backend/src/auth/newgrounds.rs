@@ -0,0 +1,78 @@
+use async_trait::async_trait;
+
+use super::{AuthError, AuthProvider, Credential, Identity};
+
+use crate::get_env;
+
+const DEFAULT_APP_ID: &str = "61527:TKbPOk1F";
+const DEFAULT_GATEWAY_URL: &str = "https://newgrounds.io/gateway_v3.php";
+
+/// Checks Newgrounds session IDs against the NG.io gateway.
+pub struct NewgroundsAuth {
+    client: reqwest::Client,
+    app_id: String,
+    gateway_url: String,
+}
+
+impl NewgroundsAuth {
+    /// Reads `NG_APP_ID` and `NG_GATEWAY_URL`, so tests can point at a stand-in gateway.
+    pub fn from_env(client: reqwest::Client) -> Self {
+        Self {
+            client,
+            app_id: get_env("NG_APP_ID", DEFAULT_APP_ID),
+            gateway_url: get_env("NG_GATEWAY_URL", DEFAULT_GATEWAY_URL),
+        }
+    }
+
+    async fn check_session(&self, session_id: &str) -> Result<Option<Identity>, String> {
+        let payload = serde_json::json!({
+            "app_id": self.app_id,
+            "session_id": session_id,
+            "call": {
+                "component": "App.checkSession",
+                "parameters": {}
+            }
+        });
+
+        let resp = self
+            .client
+            .post(&self.gateway_url)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(format!("input={}", payload))
+            .send()
+            .await
+            .map_err(|e| format!("NG gateway request failed: {}", e))?;
+
+        let body: serde_json::Value = resp
+            .json()
+            .await
+            .map_err(|e| format!("NG gateway response parse failed: {}", e))?;
+
+        let Some(user) = body.pointer("/result/data/session/user") else {
+            return Ok(None);
+        };
+        let username = user.get("name").and_then(|v| v.as_str());
+        let user_id = user.get("id").and_then(|v| v.as_u64());
+
+        Ok(username.zip(user_id).map(|(username, user_id)| Identity {
+            id: format!("ng:{}", user_id),
+            username: username.to_string(),
+        }))
+    }
+}
+
+#[async_trait]
+impl AuthProvider for NewgroundsAuth {
+    fn name(&self) -> &'static str {
+        "Newgrounds"
+    }
+
+    async fn verify(&self, credential: &Credential) -> Result<Identity, AuthError> {
+        let session_id = credential.token.as_deref().ok_or(AuthError::Missing)?;
+        match self.check_session(session_id).await {
+            Ok(Some(identity)) => Ok(identity),
+            Ok(None) => Err(AuthError::Rejected),
+            Err(e) => Err(AuthError::Unavailable(e)),
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use async_trait::async_trait;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use shared::{ApiError, SessionToken};
+
+use super::{AuthError, AuthProvider, Credential, Identity};
+use crate::get_env;
+
+/// Marks tokens issued by us, as opposed to credentials for the underlying provider.
+const TOKEN_PREFIX: &str = "cta1.";
+
+const DEFAULT_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// How long after logging in with the provider a token can still be renewed.
+const DEFAULT_MAX_AGE_SECS: u64 = 30 * 24 * 60 * 60;
+
+#[derive(Serialize, Deserialize)]
+struct Claims {
+    /// Stable identity, e.g. `ng:12345`.
+    sub: String,
+    name: String,
+    /// When the provider last vouched for them. Kept when the token is renewed; tokens from
+    /// before it was recorded count as too old to renew.
+    #[serde(default)]
+    iat: u64,
+    exp: u64,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Wraps the configured provider so it's only asked once, at login. After that, clients
+/// send the HMAC-signed token we hand out, which is checked without any network calls;
+/// nothing else is accepted outside of login.
+pub struct Sessions {
+    inner: Arc<dyn AuthProvider>,
+    key: Vec<u8>,
+    ttl_secs: u64,
+    /// Renewing stops this long after `iat`, so a leaked token can't be kept alive forever.
+    max_age_secs: u64,
+}
+
+impl Sessions {
+    /// Signs with `SESSION_SECRET`; without one, a random key is used and tokens stop
+    /// working when the server restarts.
+    pub fn from_env(inner: Arc<dyn AuthProvider>) -> Self {
+        let key = match std::env::var("SESSION_SECRET") {
+            Ok(secret) if !secret.is_empty() => secret.into_bytes(),
+            _ => {
+                tracing::warn!("SESSION_SECRET not set; sessions won't survive a restart");
+                let mut key = vec![0u8; 32];
+                OsRng.fill_bytes(&mut key);
+                key
+            }
+        };
+        let ttl_secs = get_env("SESSION_TTL_SECS", &DEFAULT_TTL_SECS.to_string())
+            .parse()
+            .unwrap_or(DEFAULT_TTL_SECS);
+        let max_age_secs = get_env("SESSION_MAX_AGE_SECS", &DEFAULT_MAX_AGE_SECS.to_string())
+            .parse()
+            .unwrap_or(DEFAULT_MAX_AGE_SECS);
+        Self { inner, key, ttl_secs, max_age_secs }
+    }
+
+    fn mac(&self) -> Hmac<Sha256> {
+        Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts keys of any length")
+    }
+
+    /// Token for `identity`, whom the provider vouched for at `iat`.
+    fn issue(&self, identity: Identity, iat: u64) -> SessionToken {
+        let claims = Claims {
+            sub: identity.id,
+            name: identity.username,
+            iat,
+            exp: (now() + self.ttl_secs).min(iat + self.max_age_secs),
+        };
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims).expect("claims serialize"));
+        let signed = format!("{}{}", TOKEN_PREFIX, payload);
+
+        let mut mac = self.mac();
+        mac.update(signed.as_bytes());
+        let signature = URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes());
+
+        SessionToken {
+            token: format!("{}.{}", signed, signature),
+            username: claims.name,
+            expires_at: claims.exp,
+        }
+    }
+
+    fn check(&self, token: &str) -> Result<Claims, AuthError> {
+        let (signed, signature) = token.rsplit_once('.').ok_or(AuthError::Rejected)?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| AuthError::Rejected)?;
+
+        let mut mac = self.mac();
+        mac.update(signed.as_bytes());
+        mac.verify_slice(&signature).map_err(|_| AuthError::Rejected)?;
+
+        let payload = signed.strip_prefix(TOKEN_PREFIX).ok_or(AuthError::Rejected)?;
+        let payload = URL_SAFE_NO_PAD.decode(payload).map_err(|_| AuthError::Rejected)?;
+        let claims: Claims = serde_json::from_slice(&payload).map_err(|_| AuthError::Rejected)?;
+        if claims.exp <= now() {
+            return Err(AuthError::Rejected);
+        }
+        Ok(claims)
+    }
+
+    /// Verifies `credential` with the provider and issues a token, or renews a token of ours
+    /// that's still within `max_age_secs` of its original login.
+    pub async fn login(&self, credential: &Credential) -> Result<(Identity, SessionToken), ApiError> {
+        let verified = match credential.token.as_deref() {
+            Some(token) if token.starts_with(TOKEN_PREFIX) => self.check(token).and_then(|claims| {
+                if claims.iat + self.max_age_secs <= now() {
+                    tracing::info!("Refused to renew token of {} issued at {}", claims.sub, claims.iat);
+                    return Err(AuthError::Rejected);
+                }
+                Ok((Identity { id: claims.sub, username: claims.name }, claims.iat))
+            }),
+            _ => self.inner.verify(credential).await.map(|identity| (identity, now())),
+        };
+        match verified {
+            Ok((identity, iat)) => {
+                tracing::info!("{} signed in", identity.id);
+                Ok((identity.clone(), self.issue(identity, iat)))
+            }
+            Err(AuthError::Missing | AuthError::Rejected) => Err(ApiError::Unauthenticated),
+            Err(AuthError::Unavailable(e)) => {
+                tracing::warn!("{} login failed: {}", self.inner.name(), e);
+                Err(ApiError::Internal)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl AuthProvider for Sessions {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    async fn verify(&self, credential: &Credential) -> Result<Identity, AuthError> {
+        match credential.token.as_deref() {
+            Some(token) if token.starts_with(TOKEN_PREFIX) => self.check(token).map(|claims| Identity {
+                id: claims.sub,
+                username: claims.name,
+            }),
+            // Provider credentials are only good for logging in
+            _ => Err(AuthError::Missing),
+        }
+    }
+
+    async fn register(&self, username: String, password: String) -> Result<Identity, ApiError> {
+        self.inner.register(username, password).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::DevAuth;
+
+    fn sessions(key: &str) -> Sessions {
+        Sessions {
+            inner: Arc::new(DevAuth::new("X-Dev-User".into())),
+            key: key.as_bytes().to_vec(),
+            ttl_secs: 60,
+            max_age_secs: 600,
+        }
+    }
+
+    fn identity() -> Identity {
+        Identity { id: "dev:ann".into(), username: "ann".into() }
+    }
+
+    #[test]
+    fn issued_tokens_check_out() {
+        let sessions = sessions("secret");
+        let token = sessions.issue(identity(), now());
+        assert!(token.token.starts_with(TOKEN_PREFIX));
+        assert_eq!(token.username, "ann");
+
+        let claims = sessions.check(&token.token).unwrap();
+        assert_eq!(claims.sub, "dev:ann");
+        assert_eq!(claims.exp, token.expires_at);
+    }
+
+    #[test]
+    fn renewed_tokens_expire_with_the_original_login() {
+        let sessions = sessions("secret");
+        let iat = now() - 590;
+        let token = sessions.issue(identity(), iat);
+        assert_eq!(token.expires_at, iat + 600);
+        assert!(sessions.check(&token.token).is_ok());
+
+        let expired = sessions.issue(identity(), now() - 700);
+        assert!(matches!(sessions.check(&expired.token), Err(AuthError::Rejected)));
+    }
+
+    #[test]
+    fn forged_tokens_are_rejected() {
+        let token = sessions("secret").issue(identity(), now()).token;
+        assert!(sessions("other secret").check(&token).is_err());
+
+        // Swapping the payload for someone else's breaks the signature
+        let (_, signature) = token.rsplit_once('.').unwrap();
+        let admin = Identity { id: "dev:admin".into(), username: "admin".into() };
+        let admin = sessions("other secret").issue(admin, now());
+        let (signed, _) = admin.token.rsplit_once('.').unwrap();
+        assert!(sessions("secret").check(&format!("{}.{}", signed, signature)).is_err());
+
+        assert!(sessions("secret").check("not a token").is_err());
+        assert!(sessions("secret").check("").is_err());
+    }
+}
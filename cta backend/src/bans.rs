@@ -0,0 +1,151 @@
+use std::collections::HashSet;
+
+use rusqlite::{Connection, OptionalExtension, params};
+use shared::{Action, AdventureNode, ApiError, LiveEvent, Role};
+
+use crate::moderation::{PUBLISHED, SHADOW};
+use crate::roles::{clear_role, rename_username_column, set_role};
+use crate::{AppState, load_nodes_from_db, node_from_row};
+
+pub fn init_bans_table(conn: &Connection) -> rusqlite::Result<()> {
+    rename_username_column(conn, "shadowbans")?;
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS shadowbans (
+            user_id TEXT PRIMARY KEY,
+            banned_by TEXT,
+            banned_at INTEGER NOT NULL DEFAULT (unixepoch())
+        );",
+    )
+}
+
+fn internal(context: &str, e: rusqlite::Error) -> ApiError {
+    tracing::error!("{}: {}", context, e);
+    ApiError::Internal
+}
+
+impl AppState {
+    pub fn is_shadowbanned(&self, user_id: &str) -> Result<bool, ApiError> {
+        self.db
+            .query_row("SELECT 1 FROM shadowbans WHERE user_id = ?1", params![user_id], |_| Ok(()))
+            .optional()
+            .map(|row| row.is_some())
+            .map_err(|e| internal("Failed to look up shadowban", e))
+    }
+
+    /// Nodes by `user_id` that only they can see.
+    pub fn unlisted_nodes_of(&self, user_id: Option<&str>) -> Result<Vec<AdventureNode>, ApiError> {
+        let Some(user_id) = user_id else {
+            return Ok(Vec::new());
+        };
+        let mut stmt = self
+            .db
+            .prepare(
+                "SELECT id, parent_id, choice_text, story_text, created_by FROM nodes
+                 WHERE created_by = ?1 AND status = ?2 ORDER BY created_at",
+            )
+            .map_err(|e| internal("Failed to prepare unlisted nodes", e))?;
+        stmt.query_map(params![user_id, SHADOW], node_from_row)
+            .and_then(|rows| rows.collect())
+            .map_err(|e| internal("Failed to load unlisted nodes", e))
+    }
+
+    pub fn ban_user(&mut self, admin: Option<&str>, username: &str, shadow: bool) -> Result<(), ApiError> {
+        self.authorize(admin, Action::Ban)?;
+        let username = username.trim();
+        if username.is_empty() {
+            return Err(ApiError::Validation {
+                field: "username".into(),
+                reason: "Username can't be empty".into(),
+            });
+        }
+        let user_id = self.resolve_user(username)?;
+        if self.role_of(&user_id)? >= Role::Admin {
+            return Err(ApiError::BadRequest("Admins can't be banned; take away their role first".into()));
+        }
+
+        if !shadow {
+            set_role(&self.db, &user_id, Role::Banned, admin).map_err(|e| internal("Failed to ban user", e))?;
+            tracing::info!("{:?} banned {}", admin, user_id);
+            return Ok(());
+        }
+
+        let tx = self
+            .db
+            .unchecked_transaction()
+            .map_err(|e| internal("Failed to start transaction", e))?;
+        tx.execute(
+            "INSERT OR REPLACE INTO shadowbans (user_id, banned_by) VALUES (?1, ?2)",
+            params![user_id, admin],
+        )
+        .and_then(|_| {
+            tx.execute(
+                "UPDATE nodes SET status = ?1 WHERE created_by = ?2 AND status = ?3",
+                params![SHADOW, user_id, PUBLISHED],
+            )
+        })
+        .and_then(|_| {
+            tx.execute(
+                "INSERT OR REPLACE INTO node_tombstones (id, deleted_at)
+                 SELECT id, unixepoch() FROM nodes WHERE created_by = ?1 AND status = ?2",
+                params![user_id, SHADOW],
+            )
+        })
+        .and_then(|_| tx.commit())
+        .map_err(|e| internal("Failed to shadowban user", e))?;
+        tracing::info!("{:?} shadowbanned {}", admin, user_id);
+
+        self.reload_nodes();
+        Ok(())
+    }
+
+    pub fn unban_user(&mut self, admin: Option<&str>, username: &str) -> Result<(), ApiError> {
+        self.authorize(admin, Action::Ban)?;
+        let user_id = self.resolve_user(username)?;
+
+        if self.role_of(&user_id)? == Role::Banned {
+            clear_role(&self.db, &user_id).map_err(|e| internal("Failed to unban user", e))?;
+        }
+
+        let tx = self
+            .db
+            .unchecked_transaction()
+            .map_err(|e| internal("Failed to start transaction", e))?;
+        tx.execute("DELETE FROM shadowbans WHERE user_id = ?1", params![user_id])
+            .and_then(|_| {
+                tx.execute(
+                    "DELETE FROM node_tombstones WHERE id IN
+                     (SELECT id FROM nodes WHERE created_by = ?1 AND status = ?2)",
+                    params![user_id, SHADOW],
+                )
+            })
+            .and_then(|_| {
+                // Bumped so incremental syncs pick them up
+                tx.execute(
+                    "UPDATE nodes SET status = ?1, published_at = unixepoch() WHERE created_by = ?2 AND status = ?3",
+                    params![PUBLISHED, user_id, SHADOW],
+                )
+            })
+            .and_then(|_| tx.commit())
+            .map_err(|e| internal("Failed to lift shadowban", e))?;
+        tracing::info!("{:?} unbanned {}", admin, user_id);
+
+        self.reload_nodes();
+        Ok(())
+    }
+
+    /// Reloads the story from the database after bulk changes, telling readers what
+    /// appeared and disappeared.
+    pub fn reload_nodes(&mut self) {
+        let before: HashSet<String> = self.nodes.iter().map(|n| n.id.clone()).collect();
+        let old = std::mem::replace(&mut self.nodes, load_nodes_from_db(&self.db));
+        let after: HashSet<&str> = self.nodes.iter().map(|n| n.id.as_str()).collect();
+
+        for node in old.iter().filter(|n| !after.contains(n.id.as_str())) {
+            self.publish(LiveEvent::NodeDeleted { node_id: node.id.clone() });
+        }
+        for node in self.nodes.iter().filter(|n| !before.contains(&n.id)) {
+            self.publish(LiveEvent::NodeAdded(node.clone()));
+        }
+        self.publish(LiveEvent::CountsChanged(crate::compute_descendant_counts(&self.nodes)));
+    }
+}
@@ -0,0 +1,196 @@
+use rusqlite::{Connection, OptionalExtension, params, params_from_iter};
+use shared::chance::{ChanceTable, MAX_OUTCOME_VALUE, Roll};
+use shared::{ApiError, LiveEvent};
+
+use crate::AppState;
+use crate::details::{id_list, mark_changed};
+
+pub fn init_chance_tables(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS random_branches (
+            node_id TEXT PRIMARY KEY,
+            roll TEXT NOT NULL,
+            updated_by TEXT,
+            updated_at INTEGER NOT NULL DEFAULT (unixepoch())
+        );
+        CREATE TABLE IF NOT EXISTS outcome_values (
+            node_id TEXT PRIMARY KEY,
+            value INTEGER NOT NULL,
+            updated_by TEXT,
+            updated_at INTEGER NOT NULL DEFAULT (unixepoch())
+        );",
+    )
+}
+
+fn internal(context: &str, e: rusqlite::Error) -> ApiError {
+    tracing::error!("{}: {}", context, e);
+    ApiError::Internal
+}
+
+fn invalid(field: &str, reason: impl Into<String>) -> ApiError {
+    ApiError::Validation {
+        field: field.into(),
+        reason: reason.into(),
+    }
+}
+
+/// Rolls are kept as JSON, so the column reads naturally and survives new kinds of roll.
+fn roll_from_json(json: &str) -> Option<Roll> {
+    serde_json::from_str(json)
+        .inspect_err(|e| tracing::error!("Unreadable roll {:?}: {}", json, e))
+        .ok()
+}
+
+pub fn check_roll(roll: &Roll) -> Result<(), ApiError> {
+    roll.check().map_err(|reason| invalid("roll", reason))
+}
+
+/// Makes `node_id` a random branch rolling `roll`, or with `None` an ordinary node again.
+pub fn save_roll(conn: &Connection, node_id: &str, roll: Option<&Roll>, updated_by: &str) -> rusqlite::Result<()> {
+    match roll {
+        Some(roll) => {
+            let json = serde_json::to_string(roll).expect("rolls serialize");
+            conn.execute(
+                "INSERT OR REPLACE INTO random_branches (node_id, roll, updated_by) VALUES (?1, ?2, ?3)",
+                params![node_id, json, updated_by],
+            )?;
+        }
+        None => {
+            conn.execute("DELETE FROM random_branches WHERE node_id = ?1", params![node_id])?;
+        }
+    }
+    mark_changed(conn, node_id)
+}
+
+pub fn save_outcome_value(conn: &Connection, node_id: &str, value: Option<u32>, updated_by: &str) -> rusqlite::Result<()> {
+    match value {
+        Some(value) => {
+            conn.execute(
+                "INSERT OR REPLACE INTO outcome_values (node_id, value, updated_by) VALUES (?1, ?2, ?3)",
+                params![node_id, value, updated_by],
+            )?;
+        }
+        None => {
+            conn.execute("DELETE FROM outcome_values WHERE node_id = ?1", params![node_id])?;
+        }
+    }
+    mark_changed(conn, node_id)
+}
+
+impl AppState {
+    /// Every random branch and outcome value among the nodes in the story.
+    pub fn chance(&self) -> Result<ChanceTable, ApiError> {
+        let all = self.load_chance(None)?;
+        Ok(ChanceTable {
+            branches: all.branches.into_iter().filter(|(id, _)| self.node(id).is_ok()).collect(),
+            outcomes: all.outcomes.into_iter().filter(|(id, _)| self.node(id).is_ok()).collect(),
+        })
+    }
+
+    /// The random branches and outcome values among `node_ids`.
+    pub fn chance_of(&self, node_ids: &[String]) -> Result<ChanceTable, ApiError> {
+        self.load_chance(Some(node_ids))
+    }
+
+    /// Random branches and outcome values of `node_ids`, or of every node for `None`.
+    fn load_chance(&self, node_ids: Option<&[String]>) -> Result<ChanceTable, ApiError> {
+        let (filter, ids) = match node_ids {
+            Some(node_ids) => ("WHERE node_id IN (SELECT value FROM json_each(?1))", vec![id_list(node_ids)]),
+            None => ("", Vec::new()),
+        };
+        let branches: Vec<(String, String)> = self
+            .db
+            .prepare(&format!("SELECT node_id, roll FROM random_branches {filter}"))
+            .and_then(|mut stmt| stmt.query_map(params_from_iter(&ids), |row| Ok((row.get(0)?, row.get(1)?)))?.collect())
+            .map_err(|e| internal("Failed to load random branches", e))?;
+        let outcomes: Vec<(String, u32)> = self
+            .db
+            .prepare(&format!("SELECT node_id, value FROM outcome_values {filter}"))
+            .and_then(|mut stmt| stmt.query_map(params_from_iter(&ids), |row| Ok((row.get(0)?, row.get(1)?)))?.collect())
+            .map_err(|e| internal("Failed to load outcome values", e))?;
+
+        Ok(ChanceTable {
+            branches: branches
+                .into_iter()
+                .filter_map(|(id, json)| Some((id, roll_from_json(&json)?)))
+                .collect(),
+            outcomes: outcomes.into_iter().collect(),
+        })
+    }
+
+    pub fn is_random_branch(&self, node_id: &str) -> Result<bool, ApiError> {
+        self.db
+            .query_row("SELECT 1 FROM random_branches WHERE node_id = ?1", params![node_id], |_| Ok(()))
+            .optional()
+            .map(|found| found.is_some())
+            .map_err(|e| internal("Failed to look up random branch", e))
+    }
+
+    /// Checks `value` can be the weight or threshold of an outcome continuing from `parent_id`.
+    pub fn check_outcome_value(&self, parent_id: Option<&str>, value: u32) -> Result<(), ApiError> {
+        if value > MAX_OUTCOME_VALUE {
+            return Err(invalid("value", format!("Must be at most {}", MAX_OUTCOME_VALUE)));
+        }
+        if !self.is_random_branch(parent_id.unwrap_or_default())? {
+            return Err(invalid("parent_id", "Only outcomes of a random branch have a weight or threshold"));
+        }
+        Ok(())
+    }
+
+    /// Makes `node_id` a random branch rolling `roll`, or an ordinary node again.
+    pub fn set_random_branch(&mut self, username: Option<&str>, node_id: &str, roll: Option<Roll>) -> Result<(), ApiError> {
+        let username = self.authorize_rules(username, node_id)?;
+        if let Some(roll) = &roll {
+            check_roll(roll)?;
+        }
+        save_roll(&self.db, node_id, roll.as_ref(), username).map_err(|e| internal("Failed to save random branch", e))?;
+        tracing::info!("{} set roll of {} to {:?}", username, node_id, roll);
+
+        if self.node(node_id).is_ok() {
+            self.publish(LiveEvent::RandomBranchChanged { node_id: node_id.to_string(), roll });
+        }
+        Ok(())
+    }
+
+    /// Sets the weight or threshold `node_id` carries as an outcome of its parent's roll.
+    pub fn set_outcome_value(&mut self, username: Option<&str>, node_id: &str, value: Option<u32>) -> Result<(), ApiError> {
+        let username = self.authorize_rules(username, node_id)?;
+        if let Some(value) = value {
+            let parent_id: Option<String> = self
+                .db
+                .query_row("SELECT parent_id FROM nodes WHERE id = ?1", params![node_id], |row| row.get(0))
+                .map_err(|e| internal("Failed to load node", e))?;
+            self.check_outcome_value(parent_id.as_deref(), value)?;
+        }
+        save_outcome_value(&self.db, node_id, value, username).map_err(|e| internal("Failed to save outcome value", e))?;
+        tracing::info!("{} set outcome value of {} to {:?}", username, node_id, value);
+
+        if self.node(node_id).is_ok() {
+            self.publish(LiveEvent::OutcomeValueChanged { node_id: node_id.to_string(), value });
+        }
+        Ok(())
+    }
+
+    /// Tells readers how chance plays out at `node_id`, for a node that's just (re)joined the story.
+    pub fn publish_chance(&self, node_id: &str) {
+        let roll: rusqlite::Result<Option<String>> = self
+            .db
+            .query_row("SELECT roll FROM random_branches WHERE node_id = ?1", params![node_id], |row| row.get(0))
+            .optional();
+        let value: rusqlite::Result<Option<u32>> = self
+            .db
+            .query_row("SELECT value FROM outcome_values WHERE node_id = ?1", params![node_id], |row| row.get(0))
+            .optional();
+        match (roll, value) {
+            (Ok(roll), Ok(value)) => {
+                if let Some(roll) = roll.as_deref().and_then(roll_from_json) {
+                    self.publish(LiveEvent::RandomBranchChanged { node_id: node_id.to_string(), roll: Some(roll) });
+                }
+                if value.is_some() {
+                    self.publish(LiveEvent::OutcomeValueChanged { node_id: node_id.to_string(), value });
+                }
+            }
+            (Err(e), _) | (_, Err(e)) => tracing::error!("Failed to load chance of {}: {}", node_id, e),
+        }
+    }
+}
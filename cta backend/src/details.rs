@@ -0,0 +1,64 @@
+use rusqlite::{Connection, params};
+use shared::{ApiError, NodeDetails};
+
+use crate::AppState;
+use crate::moderation::PUBLISHED;
+
+pub fn init_changes_table(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS detail_changes (
+            node_id TEXT PRIMARY KEY,
+            changed_at INTEGER NOT NULL DEFAULT (unixepoch())
+        );",
+    )
+}
+
+/// Records that the links or rules of `node_id` changed, so incremental syncs fetch them again.
+pub fn mark_changed(conn: &Connection, node_id: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO detail_changes (node_id, changed_at) VALUES (?1, unixepoch())",
+        params![node_id],
+    )?;
+    Ok(())
+}
+
+/// `node_ids` as a JSON array, for `json_each` to match rows against in one query.
+pub fn id_list(node_ids: &[String]) -> String {
+    serde_json::to_string(node_ids).expect("IDs serialize")
+}
+
+fn internal(context: &str, e: rusqlite::Error) -> ApiError {
+    tracing::error!("{}: {}", context, e);
+    ApiError::Internal
+}
+
+impl AppState {
+    /// The links and rules of those of `node_ids` in the story.
+    pub fn node_details(&self, mut node_ids: Vec<String>) -> Result<NodeDetails, ApiError> {
+        node_ids.retain(|id| self.node(id).is_ok());
+        Ok(NodeDetails {
+            links: self.links_after(&node_ids)?,
+            mechanics: self.mechanics_of(&node_ids)?,
+            chance: self.chance_of(&node_ids)?,
+            endings: self.endings_of(&node_ids)?,
+            node_ids,
+        })
+    }
+
+    /// The links and rules of the nodes published, or whose links or rules changed, at or
+    /// after `cursor`. Links to a node that came back are offered after other nodes, which
+    /// are sent again too.
+    pub fn detail_changes_since(&self, cursor: u64) -> Result<NodeDetails, ApiError> {
+        let node_ids: Vec<String> = self
+            .db
+            .prepare(
+                "SELECT node_id FROM detail_changes WHERE changed_at >= ?2
+                 UNION SELECT id FROM nodes WHERE status = ?1 AND published_at >= ?2
+                 UNION SELECT parent_id FROM links WHERE target_id IN
+                     (SELECT id FROM nodes WHERE status = ?1 AND published_at >= ?2)",
+            )
+            .and_then(|mut stmt| stmt.query_map(params![PUBLISHED, cursor], |row| row.get(0))?.collect())
+            .map_err(|e| internal("Failed to load changed details", e))?;
+        self.node_details(node_ids)
+    }
+}
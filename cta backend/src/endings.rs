@@ -0,0 +1,157 @@
+use rusqlite::{Connection, OptionalExtension, params, params_from_iter};
+use shared::{Action, ApiError, Ending, LiveEvent, NodeEnding};
+
+use crate::AppState;
+use crate::details::{id_list, mark_changed};
+
+/// Adds `ending` to the nodes table, moving over endings kept in the `node_endings` table
+/// of databases from before nodes carried their own.
+pub fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM pragma_table_info('nodes') WHERE name = 'ending')",
+        [],
+        |row| row.get(0),
+    )?;
+    if exists {
+        return Ok(());
+    }
+
+    tracing::info!("Adding ending column to nodes table");
+    conn.execute_batch(
+        "BEGIN;
+        ALTER TABLE nodes ADD COLUMN ending TEXT;
+        CREATE TABLE IF NOT EXISTS node_endings (node_id TEXT PRIMARY KEY, ending TEXT NOT NULL);
+        UPDATE nodes SET ending = (SELECT ending FROM node_endings WHERE node_id = nodes.id);
+        DROP TABLE node_endings;
+        COMMIT;",
+    )
+}
+
+/// Makes `node_id` an ending of the given kind, or with `None` an ordinary node again.
+pub fn save_ending(conn: &Connection, node_id: &str, ending: Option<Ending>) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE nodes SET ending = ?1 WHERE id = ?2",
+        params![ending.map(|ending| ending.as_str()), node_id],
+    )?;
+    mark_changed(conn, node_id)
+}
+
+fn internal(context: &str, e: rusqlite::Error) -> ApiError {
+    tracing::error!("{}: {}", context, e);
+    ApiError::Internal
+}
+
+impl AppState {
+    /// Every ending among the nodes in the story.
+    pub fn endings(&self) -> Result<Vec<NodeEnding>, ApiError> {
+        self.load_endings(None)
+    }
+
+    /// The endings among `node_ids`.
+    pub fn endings_of(&self, node_ids: &[String]) -> Result<Vec<NodeEnding>, ApiError> {
+        self.load_endings(Some(node_ids))
+    }
+
+    /// Every ending of the story starting at `root_id`.
+    pub fn story_endings(&self, root_id: &str) -> Result<Vec<NodeEnding>, ApiError> {
+        self.node(root_id)?;
+        let mut endings = self.endings()?;
+        endings.retain(|ending| ending.root_id == root_id);
+        Ok(endings)
+    }
+
+    /// Endings among `node_ids`, or among every node for `None`.
+    fn load_endings(&self, node_ids: Option<&[String]>) -> Result<Vec<NodeEnding>, ApiError> {
+        let (filter, ids) = match node_ids {
+            Some(node_ids) => ("AND id IN (SELECT value FROM json_each(?1))", vec![id_list(node_ids)]),
+            None => ("", Vec::new()),
+        };
+        let rows: Vec<(String, String)> = self
+            .db
+            .prepare(&format!("SELECT id, ending FROM nodes WHERE ending IS NOT NULL {filter}"))
+            .and_then(|mut stmt| stmt.query_map(params_from_iter(&ids), |row| Ok((row.get(0)?, row.get(1)?)))?.collect())
+            .map_err(|e| internal("Failed to load endings", e))?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|(node_id, ending)| {
+                let ending = ending
+                    .parse()
+                    .inspect_err(|e| tracing::error!("Unreadable ending of {}: {}", node_id, e))
+                    .ok()?;
+                self.node_ending(node_id, ending)
+            })
+            .collect())
+    }
+
+    /// `node_id` as an ending, if it's in the story.
+    fn node_ending(&self, node_id: String, ending: Ending) -> Option<NodeEnding> {
+        let root_id = match self.ancestors(&node_id).ok()?.first() {
+            Some(root) => root.id.clone(),
+            None => node_id.clone(),
+        };
+        Some(NodeEnding { node_id, root_id, ending })
+    }
+
+    /// Makes `node_id` an ending of the given kind, or an ordinary node again.
+    pub fn set_ending(&mut self, username: Option<&str>, node_id: &str, ending: Option<Ending>) -> Result<(), ApiError> {
+        let username = self.authorize_rules(username, node_id)?;
+        save_ending(&self.db, node_id, ending).map_err(|e| internal("Failed to save ending", e))?;
+        tracing::info!("{} set ending of {} to {:?}", username, node_id, ending);
+
+        match ending {
+            Some(ending) => {
+                if let Some(node_ending) = self.node_ending(node_id.to_string(), ending) {
+                    self.publish(LiveEvent::EndingSet(node_ending));
+                }
+            }
+            None if self.node(node_id).is_ok() => {
+                self.publish(LiveEvent::EndingCleared { node_id: node_id.to_string() });
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Tells readers `node_id` is an ending, for a node that's just (re)joined the story.
+    pub fn publish_ending(&self, node_id: &str) {
+        let ending: rusqlite::Result<Option<String>> = self
+            .db
+            .query_row("SELECT ending FROM nodes WHERE id = ?1", params![node_id], |row| row.get(0))
+            .optional()
+            .map(Option::flatten);
+        match ending {
+            Ok(ending) => {
+                let node_ending = ending
+                    .and_then(|ending| ending.parse().ok())
+                    .and_then(|ending| self.node_ending(node_id.to_string(), ending));
+                if let Some(node_ending) = node_ending {
+                    self.publish(LiveEvent::EndingSet(node_ending));
+                }
+            }
+            Err(e) => tracing::error!("Failed to load ending of {}: {}", node_id, e),
+        }
+    }
+
+    /// Whether `node_id` is one of the story's endings.
+    pub fn is_ending(&self, node_id: &str) -> Result<bool, ApiError> {
+        self.db
+            .query_row("SELECT ending IS NOT NULL FROM nodes WHERE id = ?1", params![node_id], |row| row.get(0))
+            .optional()
+            .map(|ending| ending.unwrap_or(false))
+            .map_err(|e| internal("Failed to look up ending", e))
+    }
+
+    /// Refuses to continue the story past an ending at `parent_id`, unless `user_id` is a
+    /// moderator writing past it anyway.
+    pub fn check_not_ending(&self, user_id: Option<&str>, parent_id: &str) -> Result<(), ApiError> {
+        let moderator = user_id.map(|u| self.role_of(u)).transpose()?.is_some_and(|r| r.allows(Action::Moderate));
+        if moderator || !self.is_ending(parent_id)? {
+            return Ok(());
+        }
+        Err(ApiError::Validation {
+            field: "parent_id".into(),
+            reason: "This node is an ending, so only moderators can continue the story past it".into(),
+        })
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::HashSet;
+
+use shared::AdventureNode;
+
+use super::{ContentFilter, OnMatch, Verdict};
+use crate::get_env;
+
+/// Matches nodes that repeat an option already offered at the same point.
+pub struct DuplicateFilter {
+    /// Share of overlapping word triples above which two story texts count as the same,
+    /// from `FILTER_DUPLICATE_SIMILARITY`.
+    threshold: f64,
+    on_match: OnMatch,
+}
+
+impl DuplicateFilter {
+    pub fn from_env(on_match: OnMatch) -> Self {
+        Self {
+            threshold: get_env("FILTER_DUPLICATE_SIMILARITY", "0.8").parse().unwrap_or(0.8),
+            on_match,
+        }
+    }
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Runs of three words (or the words themselves, for very short texts).
+fn shingles(text: &str) -> HashSet<Vec<String>> {
+    let words = words(text);
+    if words.len() < 3 {
+        return words.into_iter().map(|w| vec![w]).collect();
+    }
+    words.windows(3).map(<[String]>::to_vec).collect()
+}
+
+/// Jaccard similarity of two sets; 0 when both are empty.
+fn similarity(a: &HashSet<Vec<String>>, b: &HashSet<Vec<String>>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+impl ContentFilter for DuplicateFilter {
+    fn name(&self) -> &'static str {
+        "duplicates"
+    }
+
+    fn check(&self, node: &AdventureNode, siblings: &[&AdventureNode]) -> Verdict {
+        let choice = words(&node.choice_text);
+        if let Some(same) = siblings.iter().find(|s| words(&s.choice_text) == choice) {
+            return self
+                .on_match
+                .verdict("choice_text", format!("There's already an option called \"{}\" here", same.choice_text));
+        }
+
+        let story = shingles(&node.story_text);
+        let duplicate = siblings
+            .iter()
+            .find(|s| similarity(&story, &shingles(&s.story_text)) >= self.threshold);
+        match duplicate {
+            Some(same) => self.on_match.verdict(
+                "story_text",
+                format!("This is almost the same as the \"{}\" option", same.choice_text),
+            ),
+            None => Verdict::Accept,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(choice_text: &str, story_text: &str) -> AdventureNode {
+        AdventureNode {
+            id: choice_text.into(),
+            parent_id: Some("parent".into()),
+            choice_text: choice_text.into(),
+            story_text: story_text.into(),
+            created_by: None,
+        }
+    }
+
+    fn filter() -> DuplicateFilter {
+        DuplicateFilter { threshold: 0.8, on_match: OnMatch::Reject }
+    }
+
+    #[test]
+    fn similarity_of_word_triples() {
+        let a = shingles("You open the door and walk in");
+        assert_eq!(similarity(&a, &a), 1.0);
+        assert_eq!(similarity(&a, &shingles("Nothing in common here at all")), 0.0);
+        assert_eq!(similarity(&HashSet::new(), &HashSet::new()), 0.0);
+        // Too short for triples, so single words are compared
+        assert_eq!(shingles("Go north").len(), 2);
+    }
+
+    #[test]
+    fn same_choice_text_ignoring_case_and_punctuation() {
+        let existing = node("Open the door", "You push it open and step through.");
+        let verdict = filter().check(&node("open the door!", "A completely different story follows."), &[&existing]);
+        assert_eq!(
+            verdict,
+            Verdict::Reject {
+                field: "choice_text",
+                reason: "There's already an option called \"Open the door\" here".into(),
+            }
+        );
+    }
+
+    #[test]
+    fn nearly_the_same_story_text() {
+        let story = "The old man hands you a rusty key and points toward the tower on the hill, \
+                     muttering about ghosts.";
+        let existing = node("Talk to the man", story);
+        let copied = node("Ask the stranger", &story.replace("ghosts.", "ghosts!"));
+        assert!(matches!(filter().check(&copied, &[&existing]), Verdict::Reject { field: "story_text", .. }));
+
+        let fresh = node("Ask the stranger", "The stranger ignores you and walks off into the rain without a word.");
+        assert_eq!(filter().check(&fresh, &[&existing]), Verdict::Accept);
+    }
+}
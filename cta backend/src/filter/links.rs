@@ -0,0 +1,91 @@
+use shared::AdventureNode;
+
+use super::{ContentFilter, OnMatch, Verdict, fields};
+
+/// Top-level domains common enough in spam to treat `word.tld` as a link.
+const TLDS: &[&str] = &[
+    "com", "net", "org", "io", "co", "info", "biz", "xyz", "gg", "ly", "me", "tv", "app", "dev", "link",
+    "site", "online", "top", "ru", "uk", "de",
+];
+
+/// Matches nodes containing URLs or bare domain names.
+pub struct LinkFilter {
+    on_match: OnMatch,
+}
+
+impl LinkFilter {
+    pub fn new(on_match: OnMatch) -> Self {
+        Self { on_match }
+    }
+}
+
+fn is_link(token: &str) -> bool {
+    let token = token
+        .trim_matches(|c: char| !c.is_alphanumeric())
+        .to_lowercase();
+    if token.contains("://") || token.starts_with("www.") {
+        return true;
+    }
+    // "example.com" or "example.com/page"
+    let host = token.split('/').next().unwrap_or_default();
+    match host.rsplit_once('.') {
+        Some((name, tld)) => {
+            !name.is_empty()
+                && name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '.')
+                && TLDS.contains(&tld)
+        }
+        None => false,
+    }
+}
+
+impl ContentFilter for LinkFilter {
+    fn name(&self) -> &'static str {
+        "links"
+    }
+
+    fn check(&self, node: &AdventureNode, _siblings: &[&AdventureNode]) -> Verdict {
+        for (field, text) in fields(node) {
+            if text.split_whitespace().any(is_link) {
+                return self.on_match.verdict(field, "Links aren't allowed in the story");
+            }
+        }
+        Verdict::Accept
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spots_urls_and_bare_domains() {
+        assert!(is_link("https://example.org/page"));
+        assert!(is_link("www.example"));
+        assert!(is_link("(visit-me.xyz/free)"));
+        assert!(is_link("Shop.Example.COM"));
+    }
+
+    #[test]
+    fn leaves_ordinary_punctuation_alone() {
+        assert!(!is_link("end.of"));
+        assert!(!is_link("...com"));
+        assert!(!is_link("3.14"));
+        assert!(!is_link("Mr.Smith"));
+    }
+
+    #[test]
+    fn checks_both_fields() {
+        let filter = LinkFilter::new(OnMatch::Flag);
+        let node = AdventureNode {
+            id: "node".into(),
+            parent_id: None,
+            choice_text: "Go outside".into(),
+            story_text: "A sign reads free-stuff.io in big letters.".into(),
+            created_by: None,
+        };
+        assert_eq!(
+            filter.check(&node, &[]),
+            Verdict::Flag { reason: "Links aren't allowed in the story".into() }
+        );
+    }
+}
@@ -0,0 +1,161 @@
+mod duplicates;
+mod links;
+mod shouting;
+mod wordlist;
+
+use std::collections::HashMap;
+
+use rusqlite::{Connection, params};
+use shared::{Action, AdventureNode, ApiError};
+
+use crate::moderation::PENDING;
+use crate::{AppState, get_env};
+
+pub use duplicates::DuplicateFilter;
+pub use links::LinkFilter;
+pub use shouting::{CapsFilter, RepetitionFilter};
+pub use wordlist::WordlistFilter;
+
+/// What a filter thinks of a submitted node.
+#[derive(Debug, PartialEq)]
+pub enum Verdict {
+    Accept,
+    /// Refused outright; `reason` is shown to the author next to `field`.
+    Reject { field: &'static str, reason: String },
+    /// Let in, but held for a moderator, who sees `reason`.
+    Flag { reason: String },
+}
+
+/// What a filter does when it matches, set per filter in `CONTENT_FILTERS`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OnMatch {
+    Reject,
+    Flag,
+}
+
+impl OnMatch {
+    pub fn verdict(self, field: &'static str, reason: impl Into<String>) -> Verdict {
+        match self {
+            Self::Reject => Verdict::Reject { field, reason: reason.into() },
+            Self::Flag => Verdict::Flag { reason: reason.into() },
+        }
+    }
+}
+
+/// A check run on every submitted node before it's stored.
+pub trait ContentFilter: Send {
+    fn name(&self) -> &'static str;
+
+    /// `siblings` are the published nodes sharing the node's parent (or the other stories,
+    /// for a new story).
+    fn check(&self, node: &AdventureNode, siblings: &[&AdventureNode]) -> Verdict;
+}
+
+/// The node's text fields with their names, for filters that look at both.
+fn fields(node: &AdventureNode) -> [(&'static str, &str); 2] {
+    [("choice_text", &node.choice_text), ("story_text", &node.story_text)]
+}
+
+const DEFAULT_FILTERS: &str = "wordlist:reject,links:flag,caps:flag,repetition:flag,duplicates:reject";
+
+/// The filters a deployment runs, in order, from `CONTENT_FILTERS`: a comma-separated
+/// list of `wordlist`, `links`, `caps`, `repetition` and `duplicates`, each optionally
+/// followed by `:reject` or `:flag`. Set it empty to accept everything.
+pub struct FilterChain {
+    filters: Vec<Box<dyn ContentFilter>>,
+}
+
+impl FilterChain {
+    pub fn from_env() -> Self {
+        let config = get_env("CONTENT_FILTERS", DEFAULT_FILTERS);
+        let mut filters: Vec<Box<dyn ContentFilter>> = Vec::new();
+        for entry in config.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (name, action) = entry.split_once(':').unwrap_or((entry, ""));
+            let on_match = match action.trim() {
+                "" | "reject" => OnMatch::Reject,
+                "flag" => OnMatch::Flag,
+                other => panic!("Unknown action {:?} for content filter {}; expected reject or flag", other, name),
+            };
+            let filter: Box<dyn ContentFilter> = match name.trim() {
+                "wordlist" => match WordlistFilter::from_env(on_match) {
+                    Some(filter) => Box::new(filter),
+                    None => continue,
+                },
+                "links" => Box::new(LinkFilter::new(on_match)),
+                "caps" => Box::new(CapsFilter::from_env(on_match)),
+                "repetition" => Box::new(RepetitionFilter::new(on_match)),
+                "duplicates" => Box::new(DuplicateFilter::from_env(on_match)),
+                other => panic!(
+                    "Unknown content filter {:?}; expected wordlist, links, caps, repetition or duplicates",
+                    other
+                ),
+            };
+            filters.push(filter);
+        }
+
+        let names: Vec<&str> = filters.iter().map(|f| f.name()).collect();
+        tracing::info!("Content filters: {}", if names.is_empty() { "none".into() } else { names.join(", ") });
+        Self { filters }
+    }
+
+    /// Runs every filter over `node`. The first rejection fails the submission; otherwise
+    /// the reasons it was flagged for, if any, are returned.
+    pub fn run(&self, node: &AdventureNode, siblings: &[&AdventureNode]) -> Result<Vec<String>, ApiError> {
+        let mut flags = Vec::new();
+        for filter in &self.filters {
+            match filter.check(node, siblings) {
+                Verdict::Accept => {}
+                Verdict::Reject { field, reason } => {
+                    tracing::info!("{} filter rejected a node by {:?}: {}", filter.name(), node.created_by, reason);
+                    return Err(ApiError::Validation { field: field.into(), reason });
+                }
+                Verdict::Flag { reason } => {
+                    tracing::info!("{} filter flagged a node by {:?}: {}", filter.name(), node.created_by, reason);
+                    flags.push(reason);
+                }
+            }
+        }
+        Ok(flags)
+    }
+}
+
+/// Adds `nodes.flag_reason` to databases created before filters existed.
+pub fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM pragma_table_info('nodes') WHERE name = 'flag_reason')",
+        [],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        conn.execute_batch("ALTER TABLE nodes ADD COLUMN flag_reason TEXT")?;
+    }
+    Ok(())
+}
+
+impl AppState {
+    /// Runs the filter chain over a validated node going into `visible`. Returns why it
+    /// has to be held for review, if a filter flagged it. Moderators' own nodes aren't held.
+    pub fn screen_content(&self, node: &AdventureNode, visible: &[AdventureNode]) -> Result<Option<String>, ApiError> {
+        let siblings: Vec<&AdventureNode> = visible.iter().filter(|n| n.parent_id == node.parent_id).collect();
+        let flags = self.filters.run(node, &siblings)?;
+        if flags.is_empty() || self.role_of(node.created_by.as_deref().unwrap_or_default())?.allows(Action::Moderate) {
+            return Ok(None);
+        }
+        Ok(Some(flags.join("; ")))
+    }
+
+    /// Why each node in the moderation queue was flagged, for the ones that were.
+    pub fn flag_reasons(&self, moderator: Option<&str>) -> Result<HashMap<String, String>, ApiError> {
+        self.authorize(moderator, Action::Moderate)?;
+        self.db
+            .prepare("SELECT id, flag_reason FROM nodes WHERE status = ?1 AND flag_reason IS NOT NULL")
+            .and_then(|mut stmt| {
+                stmt.query_map(params![PENDING], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect()
+            })
+            .map_err(|e| {
+                tracing::error!("Failed to load flag reasons: {}", e);
+                ApiError::Internal
+            })
+    }
+}
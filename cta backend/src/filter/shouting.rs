@@ -0,0 +1,149 @@
+use shared::AdventureNode;
+
+use super::{ContentFilter, OnMatch, Verdict, fields};
+use crate::get_env;
+
+/// Text with fewer letters than this is too short to judge for caps.
+const CAPS_MIN_LETTERS: usize = 16;
+/// A run of the same character this long ("aaaaaaaaaaaa", "!!!!!!!!!!!!").
+const MAX_CHAR_RUN: usize = 12;
+/// The same word this many times in a row.
+const MAX_WORD_RUN: usize = 6;
+/// Texts of at least this many words...
+const VARIETY_MIN_WORDS: usize = 20;
+/// ...need at least this share of distinct words.
+const MIN_VARIETY: f64 = 0.2;
+
+/// Matches text that's mostly capital letters.
+pub struct CapsFilter {
+    /// Share of letters that may be uppercase, from `FILTER_CAPS_RATIO`.
+    max_ratio: f64,
+    on_match: OnMatch,
+}
+
+impl CapsFilter {
+    pub fn from_env(on_match: OnMatch) -> Self {
+        Self {
+            max_ratio: get_env("FILTER_CAPS_RATIO", "0.7").parse().unwrap_or(0.7),
+            on_match,
+        }
+    }
+}
+
+impl ContentFilter for CapsFilter {
+    fn name(&self) -> &'static str {
+        "caps"
+    }
+
+    fn check(&self, node: &AdventureNode, _siblings: &[&AdventureNode]) -> Verdict {
+        for (field, text) in fields(node) {
+            let letters = text.chars().filter(|c| c.is_alphabetic()).count();
+            let upper = text.chars().filter(|c| c.is_uppercase()).count();
+            if letters >= CAPS_MIN_LETTERS && upper as f64 > letters as f64 * self.max_ratio {
+                return self.on_match.verdict(field, "Please don't write in all caps");
+            }
+        }
+        Verdict::Accept
+    }
+}
+
+/// Matches keyboard mashing and copy-pasted filler.
+pub struct RepetitionFilter {
+    on_match: OnMatch,
+}
+
+impl RepetitionFilter {
+    pub fn new(on_match: OnMatch) -> Self {
+        Self { on_match }
+    }
+}
+
+fn longest_run<T: PartialEq>(items: impl Iterator<Item = T>) -> usize {
+    let mut longest = 0;
+    let mut current = 0;
+    let mut previous = None;
+    for item in items {
+        current = if previous.as_ref() == Some(&item) { current + 1 } else { 1 };
+        longest = longest.max(current);
+        previous = Some(item);
+    }
+    longest
+}
+
+fn is_repetitive(text: &str) -> bool {
+    if longest_run(text.chars().filter(|c| !c.is_whitespace())) >= MAX_CHAR_RUN {
+        return true;
+    }
+
+    let words: Vec<String> = text
+        .split_whitespace()
+        .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase())
+        .filter(|w| !w.is_empty())
+        .collect();
+    if longest_run(words.iter()) >= MAX_WORD_RUN {
+        return true;
+    }
+    if words.len() >= VARIETY_MIN_WORDS {
+        let distinct = words.iter().collect::<std::collections::HashSet<_>>().len();
+        return (distinct as f64) < words.len() as f64 * MIN_VARIETY;
+    }
+    false
+}
+
+impl ContentFilter for RepetitionFilter {
+    fn name(&self) -> &'static str {
+        "repetition"
+    }
+
+    fn check(&self, node: &AdventureNode, _siblings: &[&AdventureNode]) -> Verdict {
+        for (field, text) in fields(node) {
+            if is_repetitive(text) {
+                return self.on_match.verdict(field, "This looks like repeated filler");
+            }
+        }
+        Verdict::Accept
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(choice_text: &str, story_text: &str) -> AdventureNode {
+        AdventureNode {
+            id: "node".into(),
+            parent_id: None,
+            choice_text: choice_text.into(),
+            story_text: story_text.into(),
+            created_by: None,
+        }
+    }
+
+    #[test]
+    fn caps_needs_enough_letters_to_judge() {
+        let filter = CapsFilter { max_ratio: 0.7, on_match: OnMatch::Reject };
+        assert_eq!(filter.check(&node("RUN", "NASA calls. You pick up."), &[]), Verdict::Accept);
+        assert_eq!(
+            filter.check(&node("Run", "THE DOOR SLAMS SHUT BEHIND YOU"), &[]),
+            Verdict::Reject { field: "story_text", reason: "Please don't write in all caps".into() }
+        );
+    }
+
+    #[test]
+    fn runs_of_characters_and_words() {
+        assert_eq!(longest_run("aabbbc".chars()), 3);
+        assert_eq!(longest_run(std::iter::empty::<char>()), 0);
+        assert!(is_repetitive("Noooooooooooooo!"));
+        assert!(is_repetitive("go go go, go go go now"));
+        assert!(!is_repetitive("Nooooo! You run, run, run away."));
+    }
+
+    #[test]
+    fn long_texts_need_some_variety() {
+        let filler = "buy cheap gold now ".repeat(10);
+        assert!(is_repetitive(&filler));
+        let story = "The lantern flickers as you step into the cellar. Something scrapes against the far wall, \
+                     and the smell of damp earth fills the air around you.";
+        assert!(!is_repetitive(story));
+    }
+}
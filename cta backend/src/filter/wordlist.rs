@@ -0,0 +1,98 @@
+use std::collections::HashSet;
+
+use shared::AdventureNode;
+
+use super::{ContentFilter, OnMatch, Verdict, fields};
+use crate::get_env;
+
+/// Matches nodes containing any word from a list, ignoring case.
+pub struct WordlistFilter {
+    words: HashSet<String>,
+    on_match: OnMatch,
+}
+
+impl WordlistFilter {
+    pub fn new(words: impl IntoIterator<Item = String>, on_match: OnMatch) -> Self {
+        Self {
+            words: words
+                .into_iter()
+                .map(|w| w.trim().to_lowercase())
+                .filter(|w| !w.is_empty())
+                .collect(),
+            on_match,
+        }
+    }
+
+    /// Words come from `FILTER_WORDLIST` (comma-separated) and `FILTER_WORDLIST_FILE` (one
+    /// per line). With neither set there's nothing to filter and this returns `None`.
+    pub fn from_env(on_match: OnMatch) -> Option<Self> {
+        let mut words: Vec<String> = get_env("FILTER_WORDLIST", "").split(',').map(String::from).collect();
+        let path = get_env("FILTER_WORDLIST_FILE", "");
+        if !path.is_empty() {
+            let contents = std::fs::read_to_string(&path)
+                .unwrap_or_else(|e| panic!("Failed to read FILTER_WORDLIST_FILE {}: {}", path, e));
+            words.extend(contents.lines().filter(|l| !l.starts_with('#')).map(String::from));
+        }
+
+        let filter = Self::new(words, on_match);
+        (!filter.words.is_empty()).then_some(filter)
+    }
+}
+
+impl ContentFilter for WordlistFilter {
+    fn name(&self) -> &'static str {
+        "wordlist"
+    }
+
+    fn check(&self, node: &AdventureNode, _siblings: &[&AdventureNode]) -> Verdict {
+        for (field, text) in fields(node) {
+            let text = text.to_lowercase();
+            let hit = text
+                .split(|c: char| !c.is_alphanumeric() && c != '\'')
+                .any(|word| self.words.contains(word));
+            if hit {
+                return self.on_match.verdict(field, "Contains a word that isn't allowed here");
+            }
+        }
+        Verdict::Accept
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(choice_text: &str, story_text: &str) -> AdventureNode {
+        AdventureNode {
+            id: "node".into(),
+            parent_id: None,
+            choice_text: choice_text.into(),
+            story_text: story_text.into(),
+            created_by: None,
+        }
+    }
+
+    fn filter(words: &[&str]) -> WordlistFilter {
+        WordlistFilter::new(words.iter().map(|w| w.to_string()), OnMatch::Reject)
+    }
+
+    #[test]
+    fn matches_whole_words_ignoring_case() {
+        let filter = filter(&[" Grue ", ""]);
+        assert_eq!(
+            filter.check(&node("Open the door", "A GRUE eats you."), &[]),
+            Verdict::Reject { field: "story_text", reason: "Contains a word that isn't allowed here".into() }
+        );
+        assert_eq!(filter.check(&node("Look at the gruesome statue", "It stares back."), &[]), Verdict::Accept);
+    }
+
+    #[test]
+    fn keeps_apostrophes_inside_words() {
+        let filter = filter(&["don't"]);
+        assert!(matches!(
+            filter.check(&node("Don't look", "You look."), &[]),
+            Verdict::Reject { field: "choice_text", .. }
+        ));
+        assert_eq!(filter.check(&node("Do not look", "You don t look."), &[]), Verdict::Accept);
+    }
+}
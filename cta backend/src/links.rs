@@ -0,0 +1,131 @@
+use std::collections::HashSet;
+
+use rusqlite::{Connection, OptionalExtension, params};
+use shared::{Action, ApiError, LiveEvent, NodeLink};
+use ulid::Ulid;
+
+use crate::AppState;
+use crate::details::{id_list, mark_changed};
+use crate::validation::validate_link;
+
+pub fn init_links_table(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS links (
+            id TEXT PRIMARY KEY,
+            parent_id TEXT NOT NULL,
+            target_id TEXT NOT NULL,
+            choice_text TEXT NOT NULL,
+            created_by TEXT,
+            created_at INTEGER NOT NULL DEFAULT (unixepoch())
+        );",
+    )
+}
+
+fn link_from_row(row: &rusqlite::Row) -> rusqlite::Result<NodeLink> {
+    Ok(NodeLink {
+        id: row.get(0)?,
+        parent_id: row.get(1)?,
+        target_id: row.get(2)?,
+        choice_text: row.get(3)?,
+        created_by: row.get(4)?,
+    })
+}
+
+fn internal(context: &str, e: rusqlite::Error) -> ApiError {
+    tracing::error!("{}: {}", context, e);
+    ApiError::Internal
+}
+
+impl AppState {
+    /// Links whose ends are both in the story. Ones left dangling by a deleted or hidden
+    /// node are kept, and come back with it.
+    pub fn links(&self) -> Result<Vec<NodeLink>, ApiError> {
+        let visible: HashSet<&str> = self.nodes.iter().map(|n| n.id.as_str()).collect();
+        let links: Vec<NodeLink> = self
+            .db
+            .prepare("SELECT id, parent_id, target_id, choice_text, created_by FROM links ORDER BY created_at")
+            .and_then(|mut stmt| stmt.query_map([], link_from_row)?.collect())
+            .map_err(|e| internal("Failed to load links", e))?;
+        Ok(links
+            .into_iter()
+            .filter(|l| visible.contains(l.parent_id.as_str()) && visible.contains(l.target_id.as_str()))
+            .collect())
+    }
+
+    /// Links offered after `node_ids` that lead to a node in the story.
+    pub fn links_after(&self, node_ids: &[String]) -> Result<Vec<NodeLink>, ApiError> {
+        let links: Vec<NodeLink> = self
+            .db
+            .prepare(
+                "SELECT id, parent_id, target_id, choice_text, created_by FROM links
+                 WHERE parent_id IN (SELECT value FROM json_each(?1)) ORDER BY created_at",
+            )
+            .and_then(|mut stmt| stmt.query_map([id_list(node_ids)], link_from_row)?.collect())
+            .map_err(|e| internal("Failed to load links", e))?;
+        Ok(links.into_iter().filter(|l| self.node(&l.target_id).is_ok()).collect())
+    }
+
+    /// Adds a choice leading to a node that already exists. It has no story of its own
+    /// for a moderator to review, so where submissions are reviewed only writers who'd
+    /// skip review may link.
+    pub fn create_link(&mut self, link: NodeLink) -> Result<NodeLink, ApiError> {
+        self.authorize(link.created_by.as_deref(), Action::Submit)?;
+        let author = link.created_by.clone().unwrap_or_default();
+        if self.needs_review(&author)? {
+            return Err(ApiError::Forbidden);
+        }
+        if !link.id.is_empty() {
+            return Err(ApiError::Validation {
+                field: "id".into(),
+                reason: "Link IDs are assigned by the server".into(),
+            });
+        }
+
+        if self.is_random_branch(&link.parent_id)? {
+            return Err(ApiError::Validation {
+                field: "parent_id".into(),
+                reason: "What follows a random branch is left to chance, so it can't have links".into(),
+            });
+        }
+        self.check_not_ending(link.created_by.as_deref(), &link.parent_id)?;
+        let mut link = validate_link(link, &self.nodes, &self.links()?, &self.validation)?;
+        link.id = Ulid::new().to_string();
+        if self.is_shadowbanned(&author)? {
+            // Nobody else would ever see it
+            tracing::info!("Dropped link from shadowbanned {:?}", author);
+            return Ok(link);
+        }
+
+        self.db
+            .execute(
+                "INSERT INTO links (id, parent_id, target_id, choice_text, created_by) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![link.id, link.parent_id, link.target_id, link.choice_text, link.created_by],
+            )
+            .and_then(|_| mark_changed(&self.db, &link.parent_id))
+            .map_err(|e| internal("Failed to insert link", e))?;
+        tracing::info!("{} linked {} to {}", author, link.parent_id, link.target_id);
+        self.publish(LiveEvent::LinkAdded(link.clone()));
+        Ok(link)
+    }
+
+    pub fn delete_link(&mut self, username: Option<&str>, link_id: &str) -> Result<(), ApiError> {
+        let (parent_id, created_by): (String, Option<String>) = self
+            .db
+            .query_row("SELECT parent_id, created_by FROM links WHERE id = ?1", params![link_id], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .optional()
+            .map_err(|e| internal("Failed to load link", e))?
+            .ok_or(ApiError::NotFound)?;
+        let own = username.is_some() && created_by.as_deref() == username;
+        self.authorize(username, if own { Action::DeleteOwn } else { Action::DeleteAny })?;
+
+        self.db
+            .execute("DELETE FROM links WHERE id = ?1", params![link_id])
+            .and_then(|_| mark_changed(&self.db, &parent_id))
+            .map_err(|e| internal("Failed to delete link", e))?;
+        tracing::info!("{:?} removed link {}", username, link_id);
+        self.publish(LiveEvent::LinkRemoved { link_id: link_id.to_string() });
+        Ok(())
+    }
+}
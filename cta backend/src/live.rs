@@ -0,0 +1,52 @@
+use std::time::Duration;
+
+use actix_web::{HttpResponse, Responder, get, web};
+use futures::StreamExt;
+use shared::LiveEvent;
+use tokio::sync::broadcast;
+
+/// How many events a slow subscriber may fall behind before its stream is closed.
+pub const EVENT_BUFFER: usize = 256;
+
+/// Comment line sent when nothing has happened for a while, so proxies keep the stream open.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Server-sent event stream of `LiveEvent`s as JSON.
+#[get("/api/events")]
+async fn live_events(events: web::Data<broadcast::Sender<LiveEvent>>) -> impl Responder {
+    let rx = events.subscribe();
+
+    let updates = futures::stream::unfold(rx, |mut rx| async move {
+        loop {
+            let chunk = match tokio::time::timeout(KEEPALIVE_INTERVAL, rx.recv()).await {
+                Ok(Ok(event)) => match serde_json::to_string(&event) {
+                    Ok(json) => format!("data: {}\n\n", json),
+                    Err(e) => {
+                        tracing::error!("Failed to encode live event: {}", e);
+                        continue;
+                    }
+                },
+                // The browser reconnects to a closed stream, and clients sync whenever it
+                // opens, which picks up whatever this one missed
+                Ok(Err(broadcast::error::RecvError::Lagged(missed))) => {
+                    tracing::warn!("Live event subscriber lagged by {} events, closing its stream", missed);
+                    return None;
+                }
+                Ok(Err(broadcast::error::RecvError::Closed)) => return None,
+                Err(_) => ": keepalive\n\n".to_string(),
+            };
+            return Some((Ok::<_, actix_web::Error>(web::Bytes::from(chunk)), rx));
+        }
+    });
+
+    // Headers only go out with the first chunk, so open with a comment
+    let stream = futures::stream::once(async { Ok(web::Bytes::from_static(b": connected\n\n")) })
+        .chain(updates);
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("content-encoding", "identity"))
+        .insert_header(("cache-control", "no-cache"))
+        .insert_header(("x-accel-buffering", "no"))
+        .streaming(stream)
+}
@@ -1,18 +1,48 @@
-use std::collections::HashMap;
+mod audit;
+mod auth;
+mod bans;
+mod chance;
+mod details;
+mod endings;
+mod filter;
+mod links;
+mod live;
+mod mechanics;
+mod moderation;
+mod openapi;
+mod protocol;
+mod purge;
+mod rate_limit;
+mod reports;
+mod restructure;
+mod rest;
+mod roles;
+mod trash;
+mod users;
+mod validation;
+
+use std::collections::{HashMap, HashSet};
 use std::io;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
 use actix_cors::Cors;
 use actix_web::{
-    App, HttpResponse, HttpServer, Responder, get, post,
-    http::header::ContentType,
+    App, HttpRequest, HttpResponse, HttpServer, Responder, get, post,
+    http::{StatusCode, header, header::ContentType},
     middleware, web,
 };
+use auth::{AuthProvider, Credential, Sessions, verified_user};
 use futures::StreamExt;
+use moderation::Submitted;
+use protocol::{DecodeError, LEGACY_VERSION, OUTDATED_MESSAGE, decode_request, encode_response, legacy_outdated};
+use rate_limit::{Limited, RateLimiter};
 use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
-use shared::{AdventureNode, ServerMessage};
+use shared::{Action, AdventureNode, ApiError, ChangeSet, LiveEvent, NodeRules, ServerMessage, UserInfo};
+use tokio::sync::broadcast;
+use ulid::Ulid;
+use validation::{ValidationConfig, validate_node};
 
 fn get_env(key: &str, default: &str) -> String {
     std::env::var(key).unwrap_or_else(|_| default.to_string())
@@ -29,6 +59,15 @@ struct CounterResponse {
 struct AppState {
     nodes: Vec<AdventureNode>,
     db: Connection,
+    validation: ValidationConfig,
+    events: broadcast::Sender<LiveEvent>,
+    /// Hold submissions from writers for review before publishing them.
+    moderation: bool,
+    /// Open reports after which a node is hidden; 0 never hides.
+    report_hide_threshold: u32,
+    filters: filter::FilterChain,
+    /// Display names of user ids, which is all that's stored.
+    names: users::Names,
 }
 
 fn seed_nodes() -> Vec<AdventureNode> {
@@ -75,9 +114,6 @@ red ground under a black sky, and my shoes were made of pixels.".into(),
 
 const DB_PATH: &str = "./adventure.db";
 
-const NG_APP_ID: &str = "61527:TKbPOk1F";
-const NG_GATEWAY_URL: &str = "https://newgrounds.io/gateway_v3.php";
-
 fn init_db(path: &str) -> Connection {
     let conn = Connection::open(path).expect("Failed to open SQLite database");
     conn.execute_batch(
@@ -87,72 +123,121 @@ fn init_db(path: &str) -> Connection {
             choice_text TEXT NOT NULL,
             story_text TEXT NOT NULL,
             created_by TEXT,
-            created_at INTEGER DEFAULT (unixepoch())
+            created_at INTEGER DEFAULT (unixepoch()),
+            status TEXT NOT NULL DEFAULT 'published',
+            published_at INTEGER,
+            reviewed_by TEXT,
+            review_reason TEXT,
+            flag_reason TEXT,
+            deleted_at INTEGER,
+            deleted_by TEXT
+        );
+        CREATE TABLE IF NOT EXISTS node_tombstones (
+            id TEXT PRIMARY KEY,
+            deleted_at INTEGER NOT NULL DEFAULT (unixepoch())
         )"
     ).expect("Failed to create nodes table");
+    moderation::migrate(&conn).expect("Failed to migrate nodes table");
+    filter::migrate(&conn).expect("Failed to add flag_reason to nodes table");
+    trash::migrate(&conn).expect("Failed to add deletion columns to nodes table");
+    users::init_users_table(&conn).expect("Failed to create users table");
+    roles::init_roles_table(&conn).expect("Failed to create user_roles table");
+    reports::init_reports_table(&conn).expect("Failed to create reports table");
+    bans::init_bans_table(&conn).expect("Failed to create shadowbans table");
+    audit::init_audit_table(&conn).expect("Failed to create audit_log table");
+    restructure::init_archive_table(&conn).expect("Failed to create node_archive table");
+    links::init_links_table(&conn).expect("Failed to create links table");
+    mechanics::init_mechanics_table(&conn).expect("Failed to create mechanics table");
+    chance::init_chance_tables(&conn).expect("Failed to create chance tables");
+    endings::migrate(&conn).expect("Failed to add ending to nodes table");
+    details::init_changes_table(&conn).expect("Failed to create detail_changes table");
     conn
 }
 
+fn node_from_row(row: &rusqlite::Row) -> rusqlite::Result<AdventureNode> {
+    Ok(AdventureNode {
+        id: row.get(0)?,
+        parent_id: row.get(1)?,
+        choice_text: row.get(2)?,
+        story_text: row.get(3)?,
+        created_by: row.get(4)?,
+    })
+}
+
+/// Published nodes, leaving out any below a node that isn't.
 fn load_nodes_from_db(conn: &Connection) -> Vec<AdventureNode> {
     let mut stmt = conn
-        .prepare("SELECT id, parent_id, choice_text, story_text, created_by FROM nodes")
+        .prepare("SELECT id, parent_id, choice_text, story_text, created_by FROM nodes WHERE status = ?1")
         .expect("Failed to prepare SELECT statement");
     let nodes = stmt
-        .query_map([], |row| {
-            Ok(AdventureNode {
-                id: row.get(0)?,
-                parent_id: row.get(1)?,
-                choice_text: row.get(2)?,
-                story_text: row.get(3)?,
-                created_by: row.get(4)?,
-            })
-        })
+        .query_map([moderation::PUBLISHED], node_from_row)
         .expect("Failed to query nodes")
         .filter_map(|r| r.ok())
         .collect();
-    nodes
+    reports::reachable(nodes, |_| true)
 }
 
-fn insert_node(conn: &Connection, node: &AdventureNode) {
-    if let Err(e) = conn.execute(
-        "INSERT OR IGNORE INTO nodes (id, parent_id, choice_text, story_text, created_by) VALUES (?1, ?2, ?3, ?4, ?5)",
-        rusqlite::params![node.id, node.parent_id, node.choice_text, node.story_text, node.created_by],
-    ) {
-        tracing::error!("Failed to insert node {}: {}", node.id, e);
-    }
+fn insert_node(conn: &Connection, node: &AdventureNode, status: &str, flag_reason: Option<&str>) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO nodes (id, parent_id, choice_text, story_text, created_by, status, published_at, flag_reason)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, CASE WHEN ?6 = 'published' THEN unixepoch() END, ?7)",
+        rusqlite::params![
+            node.id,
+            node.parent_id,
+            node.choice_text,
+            node.story_text,
+            node.created_by,
+            status,
+            flag_reason
+        ],
+    )?;
+    Ok(())
 }
 
-async fn verify_ng_session(client: &reqwest::Client, session_id: &str) -> Result<Option<String>, String> {
-    let payload = serde_json::json!({
-        "app_id": NG_APP_ID,
-        "session_id": session_id,
-        "call": {
-            "component": "App.checkSession",
-            "parameters": {}
-        }
-    });
+/// Current time by the database clock, which is what `published_at` cursors compare against.
+fn db_now(conn: &Connection) -> rusqlite::Result<u64> {
+    conn.query_row("SELECT unixepoch()", [], |row| row.get(0))
+}
 
-    let resp = client
-        .post(NG_GATEWAY_URL)
-        .header("Content-Type", "application/x-www-form-urlencoded")
-        .body(format!("input={}", payload))
-        .send()
-        .await
-        .map_err(|e| format!("NG gateway request failed: {}", e))?;
+/// Nodes added and IDs removed at or after `cursor` (unix seconds), plus the cursor to
+/// pass next time. The window overlaps by a second, so clients must apply it idempotently.
+fn load_changes_since(conn: &Connection, cursor: u64) -> rusqlite::Result<ChangeSet> {
+    let now = db_now(conn)?;
 
-    let body: serde_json::Value = resp
-        .json()
-        .await
-        .map_err(|e| format!("NG gateway response parse failed: {}", e))?;
+    let added = conn
+        .prepare(
+            "SELECT id, parent_id, choice_text, story_text, created_by FROM nodes
+             WHERE status = ?1 AND published_at >= ?2 ORDER BY published_at",
+        )?
+        .query_map(rusqlite::params![moderation::PUBLISHED, cursor], node_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    // A full sync (cursor 0) has nothing stale to remove
+    let removed = if cursor == 0 {
+        Vec::new()
+    } else {
+        conn.prepare("SELECT id FROM node_tombstones WHERE deleted_at >= ?1")?
+            .query_map([cursor], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?
+    };
 
-    let username = body
-        .pointer("/result/data/session/user/name")
-        .and_then(|v| v.as_str())
-        .map(|s| s.to_string());
+    Ok(ChangeSet { added, removed, cursor: now })
+}
 
-    Ok(username)
+fn insert_seed_node(conn: &Connection, node: &AdventureNode) {
+    if let Err(e) = conn.execute(
+        "INSERT OR IGNORE INTO nodes (id, parent_id, choice_text, story_text, created_by, published_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, unixepoch())",
+        rusqlite::params![node.id, node.parent_id, node.choice_text, node.story_text, node.created_by],
+    ) {
+        tracing::error!("Failed to insert seed node {}: {}", node.id, e);
+    }
 }
 
+/// How many nodes sit below each node in the tree. Links aren't followed: what they lead
+/// to is counted where it was written, so shared targets aren't counted twice and loops
+/// can't run away. Iterative, so a deep story can't overflow the stack, and a corrupted
+/// `parent_id` cycle is cut where it closes rather than looping.
 fn compute_descendant_counts(nodes: &[AdventureNode]) -> HashMap<String, u64> {
     let mut children_map: HashMap<&str, Vec<&str>> = HashMap::new();
     for node in nodes {
@@ -164,155 +249,568 @@ fn compute_descendant_counts(nodes: &[AdventureNode]) -> HashMap<String, u64> {
         }
     }
 
-    fn count(
-        id: &str,
-        children_map: &HashMap<&str, Vec<&str>>,
-        cache: &mut HashMap<String, u64>,
-    ) -> u64 {
-        if let Some(&cached) = cache.get(id) {
-            return cached;
+    let mut counts: HashMap<String, u64> = HashMap::new();
+    let mut visiting: HashSet<&str> = HashSet::new();
+    for node in nodes {
+        // Post-order walk: a node is counted once all its children have been
+        let mut stack = vec![(node.id.as_str(), false)];
+        while let Some((id, children_done)) = stack.pop() {
+            if counts.contains_key(id) {
+                continue;
+            }
+            let children = children_map.get(id).map(Vec::as_slice).unwrap_or_default();
+            if children_done {
+                let total = children.iter().map(|c| 1 + counts.get(*c).copied().unwrap_or(0)).sum();
+                counts.insert(id.to_string(), total);
+                visiting.remove(id);
+            } else if visiting.insert(id) {
+                stack.push((id, true));
+                stack.extend(children.iter().filter(|c| !visiting.contains(**c)).map(|c| (*c, false)));
+            }
         }
-        let total = match children_map.get(id) {
-            Some(child_ids) => child_ids
-                .iter()
-                .map(|cid| 1 + count(cid, children_map, cache))
-                .sum(),
-            None => 0,
+    }
+    counts
+}
+
+impl AppState {
+    fn node(&self, id: &str) -> Result<&AdventureNode, ApiError> {
+        self.nodes.iter().find(|n| n.id == id).ok_or(ApiError::NotFound)
+    }
+
+    fn children(&self, id: &str) -> Result<Vec<AdventureNode>, ApiError> {
+        self.node(id)?;
+        Ok(self
+            .nodes
+            .iter()
+            .filter(|n| n.parent_id.as_deref() == Some(id))
+            .cloned()
+            .collect())
+    }
+
+    /// The chain of nodes leading to `id`, root first, not including the node itself.
+    fn ancestors(&self, id: &str) -> Result<Vec<AdventureNode>, ApiError> {
+        let mut chain = Vec::new();
+        let mut current = self.node(id)?;
+        while let Some(parent_id) = &current.parent_id {
+            // Guard against a corrupted graph looping forever
+            if chain.len() >= self.nodes.len() {
+                break;
+            }
+            current = self.node(parent_id)?;
+            chain.push(current.clone());
+        }
+        chain.reverse();
+        Ok(chain)
+    }
+
+    /// `root_id` (or every root) and everything up to `depth` levels below it.
+    fn subtree(&self, root_id: Option<&str>, depth: u32) -> Result<Vec<AdventureNode>, ApiError> {
+        let mut children: HashMap<&str, Vec<&AdventureNode>> = HashMap::new();
+        for node in &self.nodes {
+            if let Some(parent_id) = &node.parent_id {
+                children.entry(parent_id.as_str()).or_default().push(node);
+            }
+        }
+
+        let mut level = match root_id {
+            Some(id) => vec![self.node(id)?],
+            None => self.nodes.iter().filter(|n| n.parent_id.is_none()).collect(),
         };
-        cache.insert(id.to_string(), total);
-        total
+        let mut result: Vec<AdventureNode> = level.iter().map(|&n| n.clone()).collect();
+        for _ in 0..depth {
+            level = level
+                .iter()
+                .flat_map(|n| children.get(n.id.as_str()).into_iter().flatten().copied())
+                .collect();
+            if level.is_empty() {
+                break;
+            }
+            result.extend(level.iter().map(|&n| n.clone()));
+        }
+        Ok(result)
     }
 
-    let mut cache = HashMap::new();
-    for node in nodes {
-        count(&node.id, &children_map, &mut cache);
+    /// Validates, filters and stores a new node with its rules, publishing it unless the author's
+    /// submissions need review first or a filter flagged it. `node.created_by` must already hold
+    /// the verified author.
+    fn submit_node(&mut self, node: AdventureNode, rules: NodeRules) -> Result<Submitted, ApiError> {
+        self.authorize(node.created_by.as_deref(), Action::Submit)?;
+        let queue = self.needs_review(node.created_by.as_deref().unwrap_or_default())?;
+        if !node.id.is_empty() {
+            return Err(ApiError::Validation {
+                field: "id".into(),
+                reason: "Node IDs are assigned by the server".into(),
+            });
+        }
+
+        let author = node.created_by.clone().unwrap_or_default();
+        let rules = self.check_rules(rules, node.parent_id.as_deref())?;
+        if let Some(parent_id) = &node.parent_id {
+            self.check_not_ending(node.created_by.as_deref(), parent_id)?;
+        }
+        if self.is_shadowbanned(&author)? {
+            // Looks published to them, but nobody else ever sees it
+            let mut visible = self.nodes.clone();
+            visible.extend(self.unlisted_nodes_of(Some(&author))?);
+            let mut node = validate_node(node, &visible, &self.links()?, &self.validation)?;
+            self.screen_content(&node, &visible)?;
+            node.id = Ulid::new().to_string();
+            tracing::info!("Received node {:?} from shadowbanned {:?}", node.id, author);
+            self.insert_with_rules(&node, moderation::SHADOW, None, &rules)?;
+            return Ok(Submitted::Published(node));
+        }
+
+        let mut node = validate_node(node, &self.nodes, &self.links()?, &self.validation)?;
+        let flag_reason = self.screen_content(&node, &self.nodes)?;
+        let queue = queue || flag_reason.is_some();
+
+        node.id = Ulid::new().to_string();
+        tracing::info!("Received new adventure node: {:?} (by {:?})", node.id, node.created_by);
+
+        let status = if queue { moderation::PENDING } else { moderation::PUBLISHED };
+        self.insert_with_rules(&node, status, flag_reason.as_deref(), &rules)?;
+
+        if queue {
+            self.publish(LiveEvent::SubmissionQueued { node_id: node.id.clone() });
+            return Ok(Submitted::Queued(node));
+        }
+        self.nodes.push(node.clone());
+        self.publish(LiveEvent::NodeAdded(node.clone()));
+        if rules != NodeRules::default() {
+            self.publish_mechanics(&node.id);
+            self.publish_chance(&node.id);
+            self.publish_ending(&node.id);
+        }
+        self.publish_counts(&node.id);
+        Ok(Submitted::Published(node))
+    }
+
+    /// Stores a new node and its rules together, so it never appears with only some of them.
+    fn insert_with_rules(
+        &self,
+        node: &AdventureNode,
+        status: &str,
+        flag_reason: Option<&str>,
+        rules: &NodeRules,
+    ) -> Result<(), ApiError> {
+        let author = node.created_by.as_deref().unwrap_or_default();
+        self.db
+            .unchecked_transaction()
+            .and_then(|tx| {
+                insert_node(&tx, node, status, flag_reason)?;
+                mechanics::save_rules(&tx, &node.id, rules, author)?;
+                tx.commit()
+            })
+            .map_err(|e| {
+                tracing::error!("Failed to insert node {}: {}", node.id, e);
+                ApiError::Internal
+            })
+    }
+
+    fn delete_node(&mut self, node_id: &str, username: Option<&str>) -> Result<(), ApiError> {
+        let node = self.node(node_id)?;
+        let own = username.is_some() && node.created_by.as_deref() == username;
+        self.authorize(username, if own { Action::DeleteOwn } else { Action::DeleteAny })?;
+
+        let has_children = self.nodes.iter().any(|n| n.parent_id.as_deref() == Some(node_id));
+        if has_children {
+            return Err(ApiError::HasChildren);
+        }
+
+        tracing::info!("Deleting adventure node: {:?} (by {:?})", node_id, username);
+
+        if let Err(e) = trash::delete_node_row(&self.db, node_id, username) {
+            tracing::error!("Failed to delete node {} from DB: {}", node_id, e);
+            return Err(ApiError::Internal);
+        }
+
+        let parent_id = node.parent_id.clone();
+        self.nodes.retain(|n| n.id != node_id);
+        self.publish(LiveEvent::NodeDeleted { node_id: node_id.to_string() });
+        if let Some(parent_id) = parent_id {
+            self.publish_counts(&parent_id);
+        }
+        Ok(())
+    }
+
+    /// Sends an event to connected readers. Having nobody listening is fine.
+    fn publish(&self, event: LiveEvent) {
+        let _ = self.events.send(self.present(event));
+    }
+
+    /// Broadcasts the descendant counts of `id` and its ancestors, the only ones a
+    /// change at `id` affects.
+    fn publish_counts(&self, id: &str) {
+        let counts = compute_descendant_counts(&self.nodes);
+        let mut affected = self.ancestors(id).unwrap_or_default();
+        affected.extend(self.node(id).ok().cloned());
+        let changed = affected
+            .into_iter()
+            .filter_map(|n| counts.get(&n.id).map(|&count| (n.id, count)))
+            .collect();
+        self.publish(LiveEvent::CountsChanged(changed));
     }
-    cache
 }
 
 fn handle_message(msg: ServerMessage, state: &mut AppState) -> ServerMessage {
-    match msg {
+    let result = match msg {
         ServerMessage::RequestAdventureNodes => {
-            ServerMessage::ReturnAdventureNodes(state.nodes.clone())
+            Ok(ServerMessage::ReturnAdventureNodes(state.nodes.clone()))
         }
         ServerMessage::RequestDescendantCounts => {
-            ServerMessage::ReturnDescendantCounts(compute_descendant_counts(&state.nodes))
+            Ok(ServerMessage::ReturnDescendantCounts(compute_descendant_counts(&state.nodes)))
         }
-        ServerMessage::SubmitAdventureNode { node, .. } => {
-            if node.created_by.is_none() {
-                return ServerMessage::Error("Authentication required to submit nodes".into());
-            }
-            tracing::info!("Received new adventure node: {:?} (by {:?})", node.id, node.created_by);
-            insert_node(&state.db, &node);
-            state.nodes.push(node);
-            ServerMessage::Ok
-        }
-        ServerMessage::DeleteAdventureNode { node_id, session_id: verified_username } => {
-            let username = match verified_username {
-                Some(u) => u,
-                None => return ServerMessage::Error("Authentication required to delete nodes".into()),
-            };
+        ServerMessage::SubmitAdventureNode { node, .. } => state
+            .submit_node(node, NodeRules::default())
+            .map(Submitted::into_message),
+        ServerMessage::SubmitNodeWithRules { node, rules, .. } => {
+            state.submit_node(node, rules).map(Submitted::into_message)
+        }
+        ServerMessage::DeleteAdventureNode { node_id, session_id: verified_user } => state
+            .delete_node(&node_id, verified_user.as_deref())
+            .map(|()| ServerMessage::Ok),
+        ServerMessage::RequestSubtree { root_id, depth } => {
+            state.subtree(root_id.as_deref(), depth).and_then(|nodes| {
+                let cursor = db_now(&state.db).map_err(|e| {
+                    tracing::error!("Failed to read database clock: {}", e);
+                    ApiError::Internal
+                })?;
+                Ok(ServerMessage::ReturnSubtree { nodes, cursor })
+            })
+        }
+        ServerMessage::RequestAncestors { node_id } => {
+            state.ancestors(&node_id).map(ServerMessage::ReturnAdventureNodes)
+        }
+        ServerMessage::RequestChangesSince { cursor } => load_changes_since(&state.db, cursor)
+            .map(ServerMessage::ReturnChanges)
+            .map_err(|e| {
+                tracing::error!("Failed to load changes since {}: {}", cursor, e);
+                ApiError::Internal
+            }),
+        ServerMessage::RequestIdentity { session_id: verified_user } => match verified_user {
+            Some(username) => state
+                .role_of(&username)
+                .map(|role| ServerMessage::ReturnIdentity(Some(UserInfo { username, role }))),
+            None => Ok(ServerMessage::ReturnIdentity(None)),
+        },
+        ServerMessage::GrantRole { username, role, session_id: verified_user } => state
+            .grant_role(verified_user.as_deref(), &username, role)
+            .map(|()| ServerMessage::Ok),
+        ServerMessage::RevokeRole { username, session_id: verified_user } => state
+            .revoke_role(verified_user.as_deref(), &username)
+            .map(|()| ServerMessage::Ok),
+        ServerMessage::RequestCapabilities { node_ids, session_id: verified_user } => state
+            .capabilities(verified_user.as_deref(), &node_ids)
+            .map(ServerMessage::ReturnCapabilities),
+        ServerMessage::RequestModerationQueue { session_id: verified_user } => state
+            .moderation_queue(verified_user.as_deref())
+            .map(ServerMessage::ReturnModerationQueue),
+        ServerMessage::ReviewNodes { node_ids, decision, session_id: verified_user } => state
+            .review_nodes(verified_user.as_deref(), &node_ids, &decision)
+            .map(|(done, failed)| ServerMessage::Reviewed { done, failed }),
+        ServerMessage::RequestMySubmissions { session_id: verified_user } => state
+            .my_submissions(verified_user.as_deref())
+            .map(ServerMessage::ReturnMySubmissions),
+        ServerMessage::ReportNode { node_id, reason, details, session_id: verified_user } => state
+            .report_node(verified_user.as_deref(), &node_id, reason, &details)
+            .map(|()| ServerMessage::Ok),
+        ServerMessage::RequestReports { session_id: verified_user } => state
+            .reports(verified_user.as_deref())
+            .map(ServerMessage::ReturnReports),
+        ServerMessage::ResolveReports { node_id, resolution, session_id: verified_user } => state
+            .resolve_reports(verified_user.as_deref(), &node_id, resolution)
+            .map(|()| ServerMessage::Ok),
+        ServerMessage::BanUser { username, shadow, session_id: verified_user } => state
+            .ban_user(verified_user.as_deref(), &username, shadow)
+            .map(|()| ServerMessage::Ok),
+        ServerMessage::UnbanUser { username, session_id: verified_user } => state
+            .unban_user(verified_user.as_deref(), &username)
+            .map(|()| ServerMessage::Ok),
+        ServerMessage::PurgeUser { username, mode, dry_run, session_id: verified_user } => state
+            .purge_user(verified_user.as_deref(), &username, mode, dry_run)
+            .map(ServerMessage::ReturnPurgePlan),
+        ServerMessage::RequestOwnUnlistedNodes { session_id: verified_user } => state
+            .unlisted_nodes_of(verified_user.as_deref())
+            .map(ServerMessage::ReturnAdventureNodes),
+        ServerMessage::RequestFlagReasons { session_id: verified_user } => state
+            .flag_reasons(verified_user.as_deref())
+            .map(ServerMessage::ReturnFlagReasons),
+        ServerMessage::RestoreNode { node_id, session_id: verified_user } => state
+            .restore_node(verified_user.as_deref(), &node_id)
+            .map(|()| ServerMessage::Ok),
+        ServerMessage::RequestDeletedNodes { session_id: verified_user } => state
+            .deleted_nodes(verified_user.as_deref())
+            .map(ServerMessage::ReturnDeletedNodes),
+        ServerMessage::DeleteSubtree { node_id, dry_run, session_id: verified_user } => state
+            .delete_subtree(verified_user.as_deref(), &node_id, dry_run)
+            .map(ServerMessage::ReturnSubtreeDeletion),
+        ServerMessage::MoveNode { node_id, new_parent_id, session_id: verified_user } => state
+            .move_node(verified_user.as_deref(), &node_id, new_parent_id.as_deref())
+            .map(|()| ServerMessage::Ok),
+        ServerMessage::CreateLink { link, .. } => state.create_link(link).map(ServerMessage::LinkCreated),
+        ServerMessage::DeleteLink { link_id, session_id: verified_user } => state
+            .delete_link(verified_user.as_deref(), &link_id)
+            .map(|()| ServerMessage::Ok),
+        ServerMessage::RequestLinks => state.links().map(ServerMessage::ReturnLinks),
+        ServerMessage::SetNodeMechanics { mechanics, session_id: verified_user } => state
+            .set_mechanics(verified_user.as_deref(), mechanics)
+            .map(|()| ServerMessage::Ok),
+        ServerMessage::RequestMechanics => state.mechanics().map(ServerMessage::ReturnMechanics),
+        ServerMessage::SetRandomBranch { node_id, roll, session_id: verified_user } => state
+            .set_random_branch(verified_user.as_deref(), &node_id, roll)
+            .map(|()| ServerMessage::Ok),
+        ServerMessage::SetOutcomeValue { node_id, value, session_id: verified_user } => state
+            .set_outcome_value(verified_user.as_deref(), &node_id, value)
+            .map(|()| ServerMessage::Ok),
+        ServerMessage::RequestChance => state.chance().map(ServerMessage::ReturnChance),
+        ServerMessage::SetEnding { node_id, ending, session_id: verified_user } => state
+            .set_ending(verified_user.as_deref(), &node_id, ending)
+            .map(|()| ServerMessage::Ok),
+        ServerMessage::RequestEndings => state.endings().map(ServerMessage::ReturnEndings),
+        ServerMessage::RequestNodeDetails { node_ids } => state.node_details(node_ids).map(ServerMessage::ReturnNodeDetails),
+        ServerMessage::RequestDetailChangesSince { cursor } => state
+            .detail_changes_since(cursor)
+            .map(ServerMessage::ReturnNodeDetails),
+        ServerMessage::RequestStoryEndings { root_id } => state.story_endings(&root_id).map(ServerMessage::ReturnEndings),
+        other => {
+            tracing::warn!("Unhandled message type: {:?}", std::mem::discriminant(&other));
+            Err(ApiError::BadRequest("Unhandled message type".into()))
+        }
+    };
 
-            let node = match state.nodes.iter().find(|n| n.id == node_id) {
-                Some(n) => n,
-                None => return ServerMessage::Error("Node not found".into()),
-            };
+    result.unwrap_or_else(ServerMessage::Error)
+}
 
-            let is_admin = username == "comicstosteal";
-            if !is_admin {
-                match &node.created_by {
-                    Some(creator) if creator == &username => {}
-                    _ => return ServerMessage::Error("You can only delete your own nodes".into()),
-                }
-            }
+fn status_for(error: &ApiError) -> StatusCode {
+    match error {
+        ApiError::Unauthenticated => StatusCode::UNAUTHORIZED,
+        ApiError::Forbidden => StatusCode::FORBIDDEN,
+        ApiError::NotFound => StatusCode::NOT_FOUND,
+        ApiError::HasChildren => StatusCode::CONFLICT,
+        ApiError::Validation { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+        ApiError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+        ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+        ApiError::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
 
-            let has_children = state.nodes.iter().any(|n| n.parent_id.as_deref() == Some(&node_id));
-            if has_children {
-                return ServerMessage::Error("Cannot delete a node that has children".into());
-            }
+fn bincode_response(version: u32, msg: ServerMessage) -> HttpResponse {
+    let status = match &msg {
+        // Bare legacy clients only read the body of successful responses
+        ServerMessage::Error(e) if version != LEGACY_VERSION => status_for(e),
+        _ => StatusCode::OK,
+    };
 
-            tracing::info!("Deleting adventure node: {:?} (by {:?})", node_id, username);
+    let retry_after = match &msg {
+        ServerMessage::Error(ApiError::RateLimited { retry_after }) => Some(*retry_after),
+        _ => None,
+    };
 
-            if let Err(e) = state.db.execute("DELETE FROM nodes WHERE id = ?1", rusqlite::params![node_id]) {
-                tracing::error!("Failed to delete node {} from DB: {}", node_id, e);
-                return ServerMessage::Error("Database error".into());
+    match encode_response(version, msg) {
+        Ok(bytes) => {
+            let mut response = HttpResponse::build(status);
+            if let Some(seconds) = retry_after {
+                response.insert_header((header::RETRY_AFTER, seconds.to_string()));
             }
-
-            state.nodes.retain(|n| n.id != node_id);
-            ServerMessage::Ok
+            response.content_type("application/octet-stream").body(bytes)
         }
-        other => {
-            tracing::warn!("Unhandled message type: {:?}", std::mem::discriminant(&other));
-            ServerMessage::Error("Unhandled message type".into())
+        Err(e) => {
+            tracing::error!("Bincode serialization error: {:?}", e);
+            HttpResponse::InternalServerError()
+                .body(format!("Failed to serialize: {}", e))
         }
     }
 }
 
 #[post("/api")]
 async fn api_bincode(
+    req: HttpRequest,
     body: web::Bytes,
     data: web::Data<Mutex<AppState>>,
-    client: web::Data<reqwest::Client>,
+    auth: web::Data<dyn AuthProvider>,
+    sessions: web::Data<Sessions>,
+    limits: web::Data<RateLimiter>,
 ) -> impl Responder {
-    let msg = match bincode::deserialize::<ServerMessage>(&body) {
-        Ok(msg) => msg,
-        Err(e) => {
+    let request = match decode_request(&body) {
+        Ok(request) => request,
+        Err(DecodeError::UnsupportedVersion(version)) => {
+            tracing::warn!("Rejected request with unsupported protocol version {}", version);
+            return HttpResponse::build(StatusCode::UPGRADE_REQUIRED)
+                .content_type(ContentType::plaintext())
+                .body(OUTDATED_MESSAGE);
+        }
+        Err(DecodeError::LegacyWrite) => {
+            tracing::warn!("Rejected write from a client from before protocol versions");
+            // Bare legacy clients only read the body of successful responses
+            return match legacy_outdated() {
+                Ok(bytes) => HttpResponse::Ok().content_type("application/octet-stream").body(bytes),
+                Err(e) => HttpResponse::InternalServerError().body(format!("Failed to serialize: {}", e)),
+            };
+        }
+        Err(DecodeError::Malformed(e)) => {
             tracing::error!("Bincode deserialization error: {:?}", e);
-            return HttpResponse::BadRequest()
-                .body(format!("Failed to deserialize: {}", e));
+            return bincode_response(
+                shared::PROTOCOL_VERSION,
+                ServerMessage::Error(ApiError::BadRequest(format!("Failed to deserialize: {}", e))),
+            );
         }
     };
+    let version = request.version;
+    let msg = request.message;
 
-    tracing::info!("Received API message: {:?}", std::mem::discriminant(&msg));
+    tracing::info!(
+        "Received API message: {:?} (protocol v{}, build {:?})",
+        std::mem::discriminant(&msg),
+        version,
+        request.client_build
+    );
 
-    // If this is a SubmitAdventureNode with a session_id, verify it before locking state
+    // Verify credentials before locking state, replacing them with the verified user's id
+    let verify = async |session_id| verified_user(auth.get_ref(), &Credential::new(&req, session_id)).await;
     let msg = match msg {
         ServerMessage::SubmitAdventureNode { mut node, session_id } => {
-            if let Some(sid) = session_id {
-                match verify_ng_session(&client, &sid).await {
-                    Ok(username) => {
-                        node.created_by = username;
-                    }
-                    Err(e) => {
-                        tracing::warn!("NG session verification failed: {}", e);
-                        node.created_by = None;
-                    }
-                }
-            }
+            node.created_by = verify(session_id).await;
             ServerMessage::SubmitAdventureNode { node, session_id: None }
         }
+        ServerMessage::SubmitNodeWithRules { mut node, rules, session_id } => {
+            node.created_by = verify(session_id).await;
+            ServerMessage::SubmitNodeWithRules { node, rules, session_id: None }
+        }
         ServerMessage::DeleteAdventureNode { node_id, session_id } => {
-            let verified_username = if let Some(sid) = session_id {
-                match verify_ng_session(&client, &sid).await {
-                    Ok(username) => username,
-                    Err(e) => {
-                        tracing::warn!("NG session verification failed for delete: {}", e);
-                        None
-                    }
-                }
-            } else {
-                None
+            let username = verify(session_id).await;
+            ServerMessage::DeleteAdventureNode { node_id, session_id: username }
+        }
+        ServerMessage::RequestIdentity { session_id } => {
+            let username = verify(session_id).await;
+            ServerMessage::RequestIdentity { session_id: username }
+        }
+        ServerMessage::GrantRole { username, role, session_id } => {
+            let admin = verify(session_id).await;
+            ServerMessage::GrantRole { username, role, session_id: admin }
+        }
+        ServerMessage::RevokeRole { username, session_id } => {
+            let admin = verify(session_id).await;
+            ServerMessage::RevokeRole { username, session_id: admin }
+        }
+        ServerMessage::RequestCapabilities { node_ids, session_id } => {
+            let username = verify(session_id).await;
+            ServerMessage::RequestCapabilities { node_ids, session_id: username }
+        }
+        ServerMessage::RequestModerationQueue { session_id } => {
+            let moderator = verify(session_id).await;
+            ServerMessage::RequestModerationQueue { session_id: moderator }
+        }
+        ServerMessage::ReviewNodes { node_ids, decision, session_id } => {
+            let moderator = verify(session_id).await;
+            ServerMessage::ReviewNodes { node_ids, decision, session_id: moderator }
+        }
+        ServerMessage::RequestMySubmissions { session_id } => {
+            let username = verify(session_id).await;
+            ServerMessage::RequestMySubmissions { session_id: username }
+        }
+        ServerMessage::ReportNode { node_id, reason, details, session_id } => {
+            let reporter = verify(session_id).await;
+            ServerMessage::ReportNode { node_id, reason, details, session_id: reporter }
+        }
+        ServerMessage::RequestReports { session_id } => {
+            let moderator = verify(session_id).await;
+            ServerMessage::RequestReports { session_id: moderator }
+        }
+        ServerMessage::ResolveReports { node_id, resolution, session_id } => {
+            let moderator = verify(session_id).await;
+            ServerMessage::ResolveReports { node_id, resolution, session_id: moderator }
+        }
+        ServerMessage::BanUser { username, shadow, session_id } => {
+            let admin = verify(session_id).await;
+            ServerMessage::BanUser { username, shadow, session_id: admin }
+        }
+        ServerMessage::UnbanUser { username, session_id } => {
+            let admin = verify(session_id).await;
+            ServerMessage::UnbanUser { username, session_id: admin }
+        }
+        ServerMessage::PurgeUser { username, mode, dry_run, session_id } => {
+            let admin = verify(session_id).await;
+            ServerMessage::PurgeUser { username, mode, dry_run, session_id: admin }
+        }
+        ServerMessage::RequestOwnUnlistedNodes { session_id } => {
+            let username = verify(session_id).await;
+            ServerMessage::RequestOwnUnlistedNodes { session_id: username }
+        }
+        ServerMessage::RequestFlagReasons { session_id } => {
+            let moderator = verify(session_id).await;
+            ServerMessage::RequestFlagReasons { session_id: moderator }
+        }
+        ServerMessage::RestoreNode { node_id, session_id } => {
+            let username = verify(session_id).await;
+            ServerMessage::RestoreNode { node_id, session_id: username }
+        }
+        ServerMessage::RequestDeletedNodes { session_id } => {
+            let admin = verify(session_id).await;
+            ServerMessage::RequestDeletedNodes { session_id: admin }
+        }
+        ServerMessage::DeleteSubtree { node_id, dry_run, session_id } => {
+            let admin = verify(session_id).await;
+            ServerMessage::DeleteSubtree { node_id, dry_run, session_id: admin }
+        }
+        ServerMessage::MoveNode { node_id, new_parent_id, session_id } => {
+            let admin = verify(session_id).await;
+            ServerMessage::MoveNode { node_id, new_parent_id, session_id: admin }
+        }
+        ServerMessage::CreateLink { mut link, session_id } => {
+            link.created_by = verify(session_id).await;
+            ServerMessage::CreateLink { link, session_id: None }
+        }
+        ServerMessage::DeleteLink { link_id, session_id } => {
+            let username = verify(session_id).await;
+            ServerMessage::DeleteLink { link_id, session_id: username }
+        }
+        ServerMessage::SetNodeMechanics { mechanics, session_id } => {
+            let username = verify(session_id).await;
+            ServerMessage::SetNodeMechanics { mechanics, session_id: username }
+        }
+        ServerMessage::SetRandomBranch { node_id, roll, session_id } => {
+            let username = verify(session_id).await;
+            ServerMessage::SetRandomBranch { node_id, roll, session_id: username }
+        }
+        ServerMessage::SetOutcomeValue { node_id, value, session_id } => {
+            let username = verify(session_id).await;
+            ServerMessage::SetOutcomeValue { node_id, value, session_id: username }
+        }
+        ServerMessage::SetEnding { node_id, ending, session_id } => {
+            let username = verify(session_id).await;
+            ServerMessage::SetEnding { node_id, ending, session_id: username }
+        }
+        // Logging in doesn't touch the story, so only take the lock to record who it was
+        ServerMessage::Login { session_id } => {
+            if let Err(e) = limits.check(Limited::Auth, &req, None) {
+                return bincode_response(version, ServerMessage::Error(e));
+            }
+            let result = sessions.login(&Credential::new(&req, session_id)).await.and_then(|(identity, token)| {
+                data.lock().unwrap().remember_user(&identity)?;
+                Ok(token)
+            });
+            let response = match result {
+                Ok(token) => ServerMessage::LoggedIn(token),
+                Err(e) => ServerMessage::Error(e),
             };
-            ServerMessage::DeleteAdventureNode { node_id, session_id: verified_username }
+            return bincode_response(version, response);
         }
         other => other,
     };
 
+    if let Some((action, username)) = rate_limit::limit_for(&msg)
+        && let Err(e) = limits.check(action, &req, username)
+    {
+        return bincode_response(version, ServerMessage::Error(e));
+    }
+
     let response = {
         let mut state = data.lock().unwrap();
-        handle_message(msg, &mut state)
+        let response = handle_message(msg, &mut state);
+        state.present(response)
     };
 
-    match bincode::serialize(&response) {
-        Ok(bytes) => HttpResponse::Ok()
-            .content_type("application/octet-stream")
-            .body(bytes),
-        Err(e) => {
-            tracing::error!("Bincode serialization error: {:?}", e);
-            HttpResponse::InternalServerError()
-                .body(format!("Failed to serialize: {}", e))
-        }
-    }
+    bincode_response(version, response)
 }
 
 #[get("/")]
@@ -363,9 +861,21 @@ struct LlmProxyError {
 
 #[post("/api/llm")]
 async fn llm_proxy(
+    http_req: HttpRequest,
     body: web::Json<LlmProxyRequest>,
     client: web::Data<reqwest::Client>,
+    auth: web::Data<dyn AuthProvider>,
+    limits: web::Data<RateLimiter>,
 ) -> HttpResponse {
+    let username = verified_user(auth.get_ref(), &Credential::from_bearer(&http_req)).await;
+    if let Err(e) = limits.check(Limited::Llm, &http_req, username.as_deref()) {
+        let mut response = HttpResponse::TooManyRequests();
+        if let ApiError::RateLimited { retry_after } = e {
+            response.insert_header((header::RETRY_AFTER, retry_after.to_string()));
+        }
+        return response.json(LlmProxyError { error: e.to_string() });
+    }
+
     let req = body.into_inner();
 
     if req.api_key.is_empty() {
@@ -443,15 +953,40 @@ async fn main() -> io::Result<()> {
     let conn = init_db(DB_PATH);
     tracing::info!("SQLite database initialized at {}", DB_PATH);
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(result) = roles::run_command(&conn, &args) {
+        match result {
+            Ok(output) => println!("{}", output),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+        }
+        return Ok(());
+    }
+
     // Insert seed nodes (idempotent)
     for node in seed_nodes() {
-        insert_node(&conn, &node);
+        insert_seed_node(&conn, &node);
     }
 
     let nodes = load_nodes_from_db(&conn);
     tracing::info!("Loaded {} adventure nodes from SQLite", nodes.len());
+    let names = users::load_names(&conn).expect("Failed to load user names");
 
-    let app_state = web::Data::new(Mutex::new(AppState { nodes, db: conn }));
+    let (events, _) = broadcast::channel(live::EVENT_BUFFER);
+    let live_events = web::Data::new(events.clone());
+
+    let app_state = web::Data::new(Mutex::new(AppState {
+        nodes,
+        db: conn,
+        validation: ValidationConfig::from_env(),
+        events,
+        moderation: get_env("MODERATE_SUBMISSIONS", "false").parse().unwrap_or(false),
+        report_hide_threshold: get_env("REPORT_HIDE_THRESHOLD", "3").parse().unwrap_or(3),
+        filters: filter::FilterChain::from_env(),
+        names,
+    }));
 
     let http_client = web::Data::new(
         reqwest::Client::builder()
@@ -460,6 +995,15 @@ async fn main() -> io::Result<()> {
             .expect("Failed to create HTTP client"),
     );
 
+    let sessions = auth::from_env(http_client.get_ref().clone(), DB_PATH);
+    tracing::info!("Authenticating users with the {} provider", sessions.name());
+    let auth: web::Data<dyn AuthProvider> = web::Data::from(sessions.clone() as Arc<dyn AuthProvider>);
+    let sessions = web::Data::from(sessions);
+
+    trash::spawn_purge_job(app_state.clone());
+
+    let rate_limits = web::Data::new(RateLimiter::from_env());
+
     let host = get_env("HOST", "0.0.0.0");
     let port = get_env("PORT", "8080").parse::<u16>().unwrap_or(8080);
 
@@ -469,6 +1013,10 @@ async fn main() -> io::Result<()> {
         App::new()
             .app_data(app_state.clone())
             .app_data(http_client.clone())
+            .app_data(live_events.clone())
+            .app_data(auth.clone())
+            .app_data(sessions.clone())
+            .app_data(rate_limits.clone())
             // Allow all origins
             .wrap(Cors::permissive())
             // enable automatic response compression
@@ -479,6 +1027,9 @@ async fn main() -> io::Result<()> {
             .service(index)
             .service(health)
             .service(api_bincode)
+            .service(rest::routes())
+            .service(auth::routes())
+            .service(live::live_events)
             .service(get_counter)
             .service(increment_counter)
             .service(llm_proxy)
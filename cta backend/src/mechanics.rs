@@ -0,0 +1,176 @@
+use rusqlite::{Connection, OptionalExtension, params};
+use shared::mechanics::NodeMechanics;
+use shared::{Action, ApiError, LiveEvent, NodeRules};
+
+use crate::AppState;
+use crate::chance::{check_roll, save_outcome_value, save_roll};
+use crate::details::{id_list, mark_changed};
+use crate::endings::save_ending;
+use crate::moderation::DELETED;
+use crate::validation::validate_mechanics;
+
+pub fn init_mechanics_table(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS node_mechanics (
+            node_id TEXT PRIMARY KEY,
+            condition TEXT NOT NULL,
+            effects TEXT NOT NULL,
+            updated_by TEXT,
+            updated_at INTEGER NOT NULL DEFAULT (unixepoch())
+        );",
+    )
+}
+
+fn internal(context: &str, e: rusqlite::Error) -> ApiError {
+    tracing::error!("{}: {}", context, e);
+    ApiError::Internal
+}
+
+/// Stores `mechanics`, or clears them when both are empty.
+fn save_mechanics(conn: &Connection, mechanics: &NodeMechanics, updated_by: &str) -> rusqlite::Result<()> {
+    if mechanics.is_empty() {
+        conn.execute("DELETE FROM node_mechanics WHERE node_id = ?1", params![mechanics.node_id])?;
+    } else {
+        conn.execute(
+            "INSERT OR REPLACE INTO node_mechanics (node_id, condition, effects, updated_by)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![mechanics.node_id, mechanics.condition, mechanics.effects, updated_by],
+        )?;
+    }
+    mark_changed(conn, &mechanics.node_id)
+}
+
+fn mechanics_from_row(row: &rusqlite::Row) -> rusqlite::Result<NodeMechanics> {
+    Ok(NodeMechanics {
+        node_id: row.get(0)?,
+        condition: row.get(1)?,
+        effects: row.get(2)?,
+    })
+}
+
+/// Stores the rules a new node was written with. Pass the transaction inserting the node.
+pub fn save_rules(conn: &Connection, node_id: &str, rules: &NodeRules, author: &str) -> rusqlite::Result<()> {
+    let mechanics = NodeMechanics {
+        node_id: node_id.to_string(),
+        condition: rules.condition.clone(),
+        effects: rules.effects.clone(),
+    };
+    if !mechanics.is_empty() {
+        save_mechanics(conn, &mechanics, author)?;
+    }
+    if let Some(roll) = &rules.roll {
+        save_roll(conn, node_id, Some(roll), author)?;
+    }
+    if let Some(value) = rules.outcome_value {
+        save_outcome_value(conn, node_id, Some(value), author)?;
+    }
+    if rules.ending.is_some() {
+        save_ending(conn, node_id, rules.ending)?;
+    }
+    Ok(())
+}
+
+impl AppState {
+    /// The mechanics of every node in the story that has any.
+    pub fn mechanics(&self) -> Result<Vec<NodeMechanics>, ApiError> {
+        let all: Vec<NodeMechanics> = self
+            .db
+            .prepare("SELECT node_id, condition, effects FROM node_mechanics")
+            .and_then(|mut stmt| stmt.query_map([], mechanics_from_row)?.collect())
+            .map_err(|e| internal("Failed to load mechanics", e))?;
+        Ok(all.into_iter().filter(|m| self.node(&m.node_id).is_ok()).collect())
+    }
+
+    /// The mechanics of those of `node_ids` that have any.
+    pub fn mechanics_of(&self, node_ids: &[String]) -> Result<Vec<NodeMechanics>, ApiError> {
+        self.db
+            .prepare(
+                "SELECT node_id, condition, effects FROM node_mechanics
+                 WHERE node_id IN (SELECT value FROM json_each(?1))",
+            )
+            .and_then(|mut stmt| stmt.query_map([id_list(node_ids)], mechanics_from_row)?.collect())
+            .map_err(|e| internal("Failed to load mechanics", e))
+    }
+
+    /// Checks `username` may change the rules of `node_id` (its mechanics, or how chance
+    /// plays out there). Its author may while nothing continues from it yet, so nobody's
+    /// branch gets locked away after the fact; moderators may any time.
+    pub fn authorize_rules<'a>(&self, username: Option<&'a str>, node_id: &str) -> Result<&'a str, ApiError> {
+        let username = username.ok_or(ApiError::Unauthenticated)?;
+        let created_by: Option<String> = self
+            .db
+            .query_row(
+                "SELECT created_by FROM nodes WHERE id = ?1 AND status != ?2",
+                params![node_id, DELETED],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| internal("Failed to load node", e))?
+            .ok_or(ApiError::NotFound)?;
+
+        let continued = self.nodes.iter().any(|n| n.parent_id.as_deref() == Some(node_id))
+            || self.links()?.iter().any(|l| l.parent_id == node_id);
+        if created_by.as_deref() == Some(username) && !continued {
+            self.authorize(Some(username), Action::Submit)?;
+        } else {
+            self.authorize(Some(username), Action::Moderate)?;
+        }
+        Ok(username)
+    }
+
+    /// Checks the rules of a new node continuing from `parent_id`, tidying their text.
+    pub fn check_rules(&self, rules: NodeRules, parent_id: Option<&str>) -> Result<NodeRules, ApiError> {
+        let mechanics = validate_mechanics(NodeMechanics {
+            node_id: String::new(),
+            condition: rules.condition,
+            effects: rules.effects,
+        })?;
+        if let Some(roll) = &rules.roll {
+            check_roll(roll)?;
+        }
+        if let Some(value) = rules.outcome_value {
+            self.check_outcome_value(parent_id, value)?;
+        }
+        Ok(NodeRules {
+            condition: mechanics.condition,
+            effects: mechanics.effects,
+            ..rules
+        })
+    }
+
+    /// Sets what `mechanics.node_id` requires and changes.
+    pub fn set_mechanics(&mut self, username: Option<&str>, mechanics: NodeMechanics) -> Result<(), ApiError> {
+        let username = self.authorize_rules(username, &mechanics.node_id)?;
+        let mechanics = validate_mechanics(mechanics)?;
+        save_mechanics(&self.db, &mechanics, username).map_err(|e| internal("Failed to save mechanics", e))?;
+        tracing::info!("{} set mechanics of {}", username, mechanics.node_id);
+
+        if self.node(&mechanics.node_id).is_ok() {
+            self.publish(LiveEvent::MechanicsChanged(mechanics));
+        }
+        Ok(())
+    }
+
+    /// Tells readers about `node_id`'s mechanics, for a node that's just (re)joined the story.
+    pub fn publish_mechanics(&self, node_id: &str) {
+        let found = self
+            .db
+            .query_row(
+                "SELECT condition, effects FROM node_mechanics WHERE node_id = ?1",
+                params![node_id],
+                |row| {
+                    Ok(NodeMechanics {
+                        node_id: node_id.to_string(),
+                        condition: row.get(0)?,
+                        effects: row.get(1)?,
+                    })
+                },
+            )
+            .optional();
+        match found {
+            Ok(Some(mechanics)) => self.publish(LiveEvent::MechanicsChanged(mechanics)),
+            Ok(None) => {}
+            Err(e) => tracing::error!("Failed to load mechanics of {}: {}", node_id, e),
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use rusqlite::{Connection, OptionalExtension, params};
+use shared::{
+    Action, AdventureNode, ApiError, LiveEvent, PendingNode, ReviewDecision, ServerMessage, Submission,
+    SubmissionStatus,
+};
+
+use crate::validation::validate_node;
+use crate::{AppState, node_from_row};
+
+/// Values of `nodes.status`. Only published nodes are loaded into the story.
+pub const PUBLISHED: &str = "published";
+pub const PENDING: &str = "pending";
+pub const REJECTED: &str = "rejected";
+/// Taken out of the story after too many reports, until a moderator decides.
+pub const HIDDEN: &str = "hidden";
+/// Written by a shadowbanned user; only they see it.
+pub const SHADOW: &str = "shadow";
+/// Deleted, but kept so it can be restored until the purge job removes it.
+pub const DELETED: &str = "deleted";
+
+const REASON_MAX_CHARS: usize = 500;
+
+/// How many of their own pending or rejected submissions an author is shown.
+const MY_SUBMISSIONS_LIMIT: u32 = 50;
+
+/// What became of a submitted node.
+pub enum Submitted {
+    Published(AdventureNode),
+    /// Waiting for a moderator; only the author and moderators can see it.
+    Queued(AdventureNode),
+}
+
+impl Submitted {
+    /// The reply to a bincode submission.
+    pub fn into_message(self) -> ServerMessage {
+        match self {
+            Submitted::Published(node) => ServerMessage::NodeCreated { node },
+            Submitted::Queued(node) => ServerMessage::NodeQueued { node },
+        }
+    }
+}
+
+/// IDs that were reviewed, and the ones that couldn't be with the reason why.
+pub type ReviewResult = (Vec<String>, Vec<(String, ApiError)>);
+
+/// Adds the moderation columns to databases created before they existed.
+pub fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+    let columns: Vec<String> = conn
+        .prepare("SELECT name FROM pragma_table_info('nodes')")?
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    if columns.iter().any(|c| c == "status") {
+        return Ok(());
+    }
+
+    tracing::info!("Adding moderation columns to nodes table");
+    conn.execute_batch(
+        "BEGIN;
+        ALTER TABLE nodes ADD COLUMN status TEXT NOT NULL DEFAULT 'published';
+        ALTER TABLE nodes ADD COLUMN published_at INTEGER;
+        ALTER TABLE nodes ADD COLUMN reviewed_by TEXT;
+        ALTER TABLE nodes ADD COLUMN review_reason TEXT;
+        UPDATE nodes SET published_at = created_at;
+        COMMIT;",
+    )
+}
+
+fn internal(context: &str, e: rusqlite::Error) -> ApiError {
+    tracing::error!("{}: {}", context, e);
+    ApiError::Internal
+}
+
+impl AppState {
+    /// Whether submissions by `username` have to wait for review before they're published.
+    pub fn needs_review(&self, username: &str) -> Result<bool, ApiError> {
+        Ok(self.moderation && !self.role_of(username)?.allows(Action::SkipReview))
+    }
+
+    pub fn moderation_queue(&self, moderator: Option<&str>) -> Result<Vec<PendingNode>, ApiError> {
+        self.authorize(moderator, Action::Moderate)?;
+
+        let mut stmt = self
+            .db
+            .prepare(
+                "SELECT n.id, n.parent_id, n.choice_text, n.story_text, n.created_by, n.created_at, p.choice_text
+                 FROM nodes n LEFT JOIN nodes p ON p.id = n.parent_id
+                 WHERE n.status = ?1 ORDER BY n.created_at",
+            )
+            .map_err(|e| internal("Failed to prepare moderation queue", e))?;
+        stmt.query_map(params![PENDING], |row| {
+            Ok(PendingNode {
+                node: node_from_row(row)?,
+                submitted_at: row.get(5)?,
+                parent_choice_text: row.get(6)?,
+            })
+        })
+        .and_then(|rows| rows.collect())
+        .map_err(|e| internal("Failed to load moderation queue", e))
+    }
+
+    /// Applies `decision` to each pending node in `node_ids`, reporting which ones failed and why.
+    pub fn review_nodes(
+        &mut self,
+        moderator: Option<&str>,
+        node_ids: &[String],
+        decision: &ReviewDecision,
+    ) -> Result<ReviewResult, ApiError> {
+        self.authorize(moderator, Action::Moderate)?;
+        let moderator = moderator.unwrap_or_default();
+
+        let reason = match decision {
+            ReviewDecision::Approve => None,
+            ReviewDecision::Reject { reason } => {
+                let reason = reason.trim();
+                if reason.is_empty() || reason.chars().count() > REASON_MAX_CHARS {
+                    return Err(ApiError::Validation {
+                        field: "reason".into(),
+                        reason: format!("Give a reason of 1 to {} characters", REASON_MAX_CHARS),
+                    });
+                }
+                Some(reason.to_string())
+            }
+        };
+
+        let mut done = Vec::new();
+        let mut failed = Vec::new();
+        for id in node_ids {
+            let result = match &reason {
+                None => self.approve(moderator, id),
+                Some(reason) => self.reject(moderator, id, reason),
+            };
+            match result {
+                Ok(()) => done.push(id.clone()),
+                Err(e) => failed.push((id.clone(), e)),
+            }
+        }
+        Ok((done, failed))
+    }
+
+    fn pending_node(&self, id: &str) -> Result<AdventureNode, ApiError> {
+        self.db
+            .query_row(
+                "SELECT id, parent_id, choice_text, story_text, created_by FROM nodes
+                 WHERE id = ?1 AND status = ?2",
+                params![id, PENDING],
+                node_from_row,
+            )
+            .optional()
+            .map_err(|e| internal("Failed to load pending node", e))?
+            .ok_or(ApiError::NotFound)
+    }
+
+    fn approve(&mut self, moderator: &str, id: &str) -> Result<(), ApiError> {
+        // The story may have moved on while it waited: the parent could be gone or full
+        let node = validate_node(self.pending_node(id)?, &self.nodes, &self.links()?, &self.validation)?;
+
+        self.db
+            .execute(
+                "UPDATE nodes SET status = ?1, published_at = unixepoch(), reviewed_by = ?2 WHERE id = ?3",
+                params![PUBLISHED, moderator, id],
+            )
+            .map_err(|e| internal("Failed to approve node", e))?;
+        tracing::info!("{} approved node {}", moderator, id);
+
+        self.nodes.push(node.clone());
+        self.publish(LiveEvent::NodeAdded(node));
+        // Set by the author while it waited
+        self.publish_mechanics(id);
+        self.publish_chance(id);
+        self.publish_ending(id);
+        self.publish(LiveEvent::SubmissionReviewed {
+            node_id: id.to_string(),
+            approved: true,
+        });
+        self.publish_counts(id);
+        Ok(())
+    }
+
+    fn reject(&mut self, moderator: &str, id: &str, reason: &str) -> Result<(), ApiError> {
+        self.pending_node(id)?;
+
+        self.db
+            .execute(
+                "UPDATE nodes SET status = ?1, reviewed_by = ?2, review_reason = ?3 WHERE id = ?4",
+                params![REJECTED, moderator, reason, id],
+            )
+            .map_err(|e| internal("Failed to reject node", e))?;
+        tracing::info!("{} rejected node {}: {}", moderator, id, reason);
+
+        self.publish(LiveEvent::SubmissionReviewed {
+            node_id: id.to_string(),
+            approved: false,
+        });
+        Ok(())
+    }
+
+    pub fn my_submissions(&self, username: Option<&str>) -> Result<Vec<Submission>, ApiError> {
+        let username = username.ok_or(ApiError::Unauthenticated)?;
+
+        let mut stmt = self
+            .db
+            .prepare(
+                "SELECT id, parent_id, choice_text, story_text, created_by, created_at, status, review_reason
+                 FROM nodes WHERE created_by = ?1 AND status IN (?2, ?3)
+                 ORDER BY created_at DESC LIMIT ?4",
+            )
+            .map_err(|e| internal("Failed to prepare submissions", e))?;
+        stmt.query_map(params![username, PENDING, REJECTED, MY_SUBMISSIONS_LIMIT], |row| {
+            let status: String = row.get(6)?;
+            let status = if status == REJECTED {
+                SubmissionStatus::Rejected {
+                    reason: row.get::<_, Option<String>>(7)?.unwrap_or_default(),
+                }
+            } else {
+                SubmissionStatus::Pending
+            };
+            Ok(Submission {
+                node: node_from_row(row)?,
+                status,
+                submitted_at: row.get(5)?,
+            })
+        })
+        .and_then(|rows| rows.collect())
+        .map_err(|e| internal("Failed to load submissions", e))
+    }
+}
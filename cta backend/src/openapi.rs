@@ -0,0 +1,1086 @@
+use serde_json::{Value, json};
+use shared::{ReportReason, Role};
+
+/// OpenAPI description of the JSON API in `rest.rs`, served at `/api/v1/openapi.json`.
+pub fn document() -> Value {
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Collaborative Text Adventure API",
+            "version": "1",
+            "description": "JSON access to adventure nodes. Write operations need a session token \
+from `POST /api/auth/login`, sent as `Authorization: Bearer <token>`. A credential for the server's \
+AUTH_PROVIDER is also accepted directly: a Newgrounds session ID as `Bearer`, local account details \
+as `Authorization: Basic`, or the dev header. Requests are rate limited per user and per IP; a 429 \
+response carries a `Retry-After` header in seconds."
+        },
+        "paths": paths(),
+        "components": {
+            "securitySchemes": {
+                "ngSession": { "type": "http", "scheme": "bearer" },
+                "localAccount": { "type": "http", "scheme": "basic" }
+            },
+            "schemas": schemas()
+        }
+    })
+}
+
+/// Every endpoint, by path.
+fn paths() -> Value {
+    [
+        ("/api/v1/nodes", nodes()),
+        ("/api/v1/nodes/{id}", node()),
+        ("/api/v1/nodes/{id}/children", node_children()),
+        ("/api/v1/nodes/{id}/ancestors", node_ancestors()),
+        ("/api/v1/links", links()),
+        ("/api/v1/links/{id}", link()),
+        ("/api/v1/mechanics", mechanics()),
+        ("/api/v1/nodes/{id}/mechanics", node_mechanics()),
+        ("/api/v1/chance", chance()),
+        ("/api/v1/nodes/{id}/roll", node_roll()),
+        ("/api/v1/nodes/{id}/outcome", node_outcome()),
+        ("/api/v1/endings", endings()),
+        ("/api/v1/nodes/{id}/ending", node_ending()),
+        ("/api/v1/nodes/{id}/reports", node_reports()),
+        ("/api/v1/nodes/{id}/restore", node_restore()),
+        ("/api/v1/nodes/{id}/delete-subtree", node_delete_subtree()),
+        ("/api/v1/nodes/{id}/parent", node_parent()),
+        ("/api/v1/capabilities", capabilities()),
+        ("/api/v1/me", me()),
+        ("/api/v1/me/submissions", my_submissions()),
+        ("/api/v1/moderation/queue", moderation_queue()),
+        ("/api/v1/moderation/review", moderation_review()),
+        ("/api/v1/moderation/reports", moderation_reports()),
+        ("/api/v1/moderation/deleted", moderation_deleted()),
+        ("/api/v1/moderation/reports/{id}", moderation_node_reports()),
+        ("/api/v1/users/{username}/role", user_role()),
+        ("/api/v1/users/{username}/ban", user_ban()),
+        ("/api/v1/users/{username}/purge", user_purge()),
+        ("/api/v1/audit-log", audit_log()),
+        ("/api/v1/descendant-counts", descendant_counts()),
+    ]
+    .into_iter()
+    .collect()
+}
+
+/// The `{id}` path parameter.
+fn node_id() -> Value {
+    json!({
+        "name": "id",
+        "in": "path",
+        "required": true,
+        "schema": { "type": "string" }
+    })
+}
+
+/// The `{username}` path parameter.
+fn username() -> Value {
+    json!({
+        "name": "username",
+        "in": "path",
+        "required": true,
+        "schema": { "type": "string" }
+    })
+}
+
+fn node_list() -> Value {
+    json!({
+        "type": "array",
+        "items": { "$ref": "#/components/schemas/AdventureNode" }
+    })
+}
+
+/// Security requirement of the endpoints that need a signed-in user.
+fn auth() -> Value {
+    json!([{ "ngSession": [] }, { "localAccount": [] }])
+}
+
+/// Response for any failure.
+fn error() -> Value {
+    json!({
+        "description": "Request failed",
+        "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Error" } } }
+    })
+}
+
+fn nodes() -> Value {
+    json!({
+        "get": {
+            "summary": "List every node",
+            "responses": {
+                "200": { "description": "All nodes", "content": { "application/json": { "schema": node_list() } } }
+            }
+        },
+        "post": {
+            "summary": "Add a node",
+            "security": auth(),
+            "requestBody": {
+                "required": true,
+                "content": { "application/json": { "schema": { "$ref": "#/components/schemas/NewNode" } } }
+            },
+            "responses": {
+                "201": {
+                    "description": "The created node with its server-assigned ID",
+                    "content": { "application/json": { "schema": { "$ref": "#/components/schemas/AdventureNode" } } }
+                },
+                "202": {
+                    "description": "The node was stored but waits for a moderator before it's published",
+                    "content": { "application/json": { "schema": { "$ref": "#/components/schemas/AdventureNode" } } }
+                },
+                "401": error(),
+                "403": error(),
+                "422": error(),
+                "429": error()
+            }
+        }
+    })
+}
+
+fn node() -> Value {
+    json!({
+        "get": {
+            "summary": "Fetch one node",
+            "parameters": [node_id()],
+            "responses": {
+                "200": {
+                    "description": "The node",
+                    "content": { "application/json": { "schema": { "$ref": "#/components/schemas/AdventureNode" } } }
+                },
+                "404": error()
+            }
+        },
+        "delete": {
+            "summary": "Delete a node nothing continues from. Writers may delete their own; moderators any. It's kept until purged, so it can be restored",
+            "security": auth(),
+            "parameters": [node_id()],
+            "responses": {
+                "204": { "description": "Deleted" },
+                "401": error(),
+                "403": error(),
+                "404": error(),
+                "409": error(),
+                "429": error()
+            }
+        }
+    })
+}
+
+fn node_children() -> Value {
+    json!({
+        "get": {
+            "summary": "Options branching from a node",
+            "parameters": [node_id()],
+            "responses": {
+                "200": { "description": "Child nodes", "content": { "application/json": { "schema": node_list() } } },
+                "404": error()
+            }
+        }
+    })
+}
+
+fn node_ancestors() -> Value {
+    json!({
+        "get": {
+            "summary": "Path from the root down to a node, excluding the node itself",
+            "parameters": [node_id()],
+            "responses": {
+                "200": { "description": "Ancestors, root first", "content": { "application/json": { "schema": node_list() } } },
+                "404": error()
+            }
+        }
+    })
+}
+
+fn links() -> Value {
+    json!({
+        "get": {
+            "summary": "Choices leading to nodes written elsewhere, so paths can meet or loop back",
+            "responses": {
+                "200": {
+                    "description": "Links whose ends are both in the story",
+                    "content": { "application/json": { "schema": {
+                        "type": "array",
+                        "items": { "$ref": "#/components/schemas/NodeLink" }
+                    } } }
+                },
+                "429": error()
+            }
+        },
+        "post": {
+            "summary": "Add a choice after one node that leads to another existing node. Writers whose submissions are reviewed can't",
+            "security": auth(),
+            "requestBody": {
+                "required": true,
+                "content": { "application/json": { "schema": {
+                    "type": "object",
+                    "required": ["parent_id", "target_id", "choice_text"],
+                    "properties": {
+                        "parent_id": { "type": "string" },
+                        "target_id": { "type": "string" },
+                        "choice_text": { "type": "string" }
+                    }
+                } } }
+            },
+            "responses": {
+                "201": {
+                    "description": "Created",
+                    "content": { "application/json": { "schema": { "$ref": "#/components/schemas/NodeLink" } } }
+                },
+                "401": error(),
+                "403": error(),
+                "422": error(),
+                "429": error()
+            }
+        }
+    })
+}
+
+fn link() -> Value {
+    json!({
+        "delete": {
+            "summary": "Remove a link. Writers may remove their own; moderators any",
+            "security": auth(),
+            "parameters": [node_id()],
+            "responses": {
+                "204": { "description": "Removed" },
+                "401": error(),
+                "403": error(),
+                "404": error(),
+                "429": error()
+            }
+        }
+    })
+}
+
+fn mechanics() -> Value {
+    json!({
+        "get": {
+            "summary": "What each node requires of readers before they can choose it, and what it changes once they have",
+            "responses": {
+                "200": {
+                    "description": "Mechanics of the nodes in the story that have any",
+                    "content": { "application/json": { "schema": {
+                        "type": "array",
+                        "items": { "$ref": "#/components/schemas/NodeMechanics" }
+                    } } }
+                },
+                "429": error()
+            }
+        }
+    })
+}
+
+fn node_mechanics() -> Value {
+    json!({
+        "put": {
+            "summary": "Set a node's condition and effects; both empty clears them. Authors may until someone continues from the node; moderators any time",
+            "security": auth(),
+            "parameters": [node_id()],
+            "requestBody": {
+                "required": true,
+                "content": { "application/json": { "schema": {
+                    "type": "object",
+                    "properties": {
+                        "condition": { "type": "string", "example": "has_key and gold >= 10" },
+                        "effects": { "type": "string", "example": "remove key; gold -= 10; set door_open" }
+                    }
+                } } }
+            },
+            "responses": {
+                "204": { "description": "Set" },
+                "401": error(),
+                "403": error(),
+                "404": error(),
+                "422": error(),
+                "429": error()
+            }
+        }
+    })
+}
+
+fn chance() -> Value {
+    json!({
+        "get": {
+            "summary": "Random branches, whose outcome is rolled for, and the weights or thresholds of their outcomes",
+            "responses": {
+                "200": {
+                    "description": "Both keyed by node ID",
+                    "content": { "application/json": { "schema": { "$ref": "#/components/schemas/ChanceTable" } } }
+                },
+                "429": error()
+            }
+        }
+    })
+}
+
+fn node_roll() -> Value {
+    json!({
+        "put": {
+            "summary": "Make a node a random branch, or (with a null roll) an ordinary node again. Same rules as setting its mechanics",
+            "security": auth(),
+            "parameters": [node_id()],
+            "requestBody": {
+                "required": true,
+                "content": { "application/json": { "schema": {
+                    "type": "object",
+                    "properties": {
+                        "roll": { "allOf": [{ "$ref": "#/components/schemas/Roll" }], "nullable": true }
+                    }
+                } } }
+            },
+            "responses": {
+                "204": { "description": "Set" },
+                "401": error(),
+                "403": error(),
+                "404": error(),
+                "422": error(),
+                "429": error()
+            }
+        }
+    })
+}
+
+fn node_outcome() -> Value {
+    json!({
+        "put": {
+            "summary": "Set the weight (weighted branches) or the lowest total reaching it (dice checks) of an outcome of a random branch; null clears it",
+            "security": auth(),
+            "parameters": [node_id()],
+            "requestBody": {
+                "required": true,
+                "content": { "application/json": { "schema": {
+                    "type": "object",
+                    "properties": {
+                        "value": { "type": "integer", "minimum": 0, "maximum": 10000, "nullable": true }
+                    }
+                } } }
+            },
+            "responses": {
+                "204": { "description": "Set" },
+                "401": error(),
+                "403": error(),
+                "404": error(),
+                "422": error(),
+                "429": error()
+            }
+        }
+    })
+}
+
+fn endings() -> Value {
+    json!({
+        "get": {
+            "summary": "Nodes that end the story, with the story each belongs to",
+            "responses": {
+                "200": {
+                    "description": "Every ending",
+                    "content": { "application/json": { "schema": { "type": "array", "items": { "$ref": "#/components/schemas/NodeEnding" } } } }
+                },
+                "429": error()
+            }
+        }
+    })
+}
+
+fn node_ending() -> Value {
+    json!({
+        "put": {
+            "summary": "Make a node one of the story's endings, or (with a null ending) an ordinary node again. Same rules as setting its mechanics",
+            "security": auth(),
+            "parameters": [node_id()],
+            "requestBody": {
+                "required": true,
+                "content": { "application/json": { "schema": {
+                    "type": "object",
+                    "properties": {
+                        "ending": { "allOf": [{ "$ref": "#/components/schemas/Ending" }], "nullable": true }
+                    }
+                } } }
+            },
+            "responses": {
+                "204": { "description": "Set" },
+                "401": error(),
+                "403": error(),
+                "404": error(),
+                "422": error(),
+                "429": error()
+            }
+        }
+    })
+}
+
+fn node_reports() -> Value {
+    json!({
+        "post": {
+            "summary": "Report a node to moderators. Reporting the same node again does nothing",
+            "security": auth(),
+            "parameters": [node_id()],
+            "requestBody": {
+                "required": true,
+                "content": { "application/json": { "schema": {
+                    "type": "object",
+                    "required": ["reason"],
+                    "properties": {
+                        "reason": { "$ref": "#/components/schemas/ReportReason" },
+                        "details": { "type": "string" }
+                    }
+                } } }
+            },
+            "responses": {
+                "204": { "description": "Reported" },
+                "400": error(),
+                "401": error(),
+                "403": error(),
+                "404": error(),
+                "422": error()
+            }
+        }
+    })
+}
+
+fn node_restore() -> Value {
+    json!({
+        "post": {
+            "summary": "Put a deleted node back. Whoever deleted it may for a short while afterwards; admins any time until it's purged",
+            "security": auth(),
+            "parameters": [node_id()],
+            "responses": {
+                "204": { "description": "Restored" },
+                "400": error(),
+                "401": error(),
+                "403": error(),
+                "404": error()
+            }
+        }
+    })
+}
+
+fn node_delete_subtree() -> Value {
+    json!({
+        "post": {
+            "summary": "Delete a node and everything below it, whatever their status, archiving them first (admins only)",
+            "security": auth(),
+            "parameters": [node_id()],
+            "requestBody": {
+                "required": true,
+                "content": { "application/json": { "schema": {
+                    "type": "object",
+                    "properties": {
+                        "dry_run": { "type": "boolean", "default": false, "description": "Only report what would be deleted" }
+                    }
+                } } }
+            },
+            "responses": {
+                "200": {
+                    "description": "What was (or would be) deleted",
+                    "content": { "application/json": { "schema": { "$ref": "#/components/schemas/SubtreeDeletion" } } }
+                },
+                "401": error(),
+                "403": error(),
+                "404": error()
+            }
+        }
+    })
+}
+
+fn node_parent() -> Value {
+    json!({
+        "put": {
+            "summary": "Move a node and everything below it to continue from another node, or make it a new story (admins only)",
+            "security": auth(),
+            "parameters": [node_id()],
+            "requestBody": {
+                "required": true,
+                "content": { "application/json": { "schema": {
+                    "type": "object",
+                    "properties": {
+                        "parent_id": { "type": "string", "nullable": true, "description": "The new parent; null starts a new story" }
+                    }
+                } } }
+            },
+            "responses": {
+                "204": { "description": "Moved" },
+                "401": error(),
+                "403": error(),
+                "404": error(),
+                "422": error()
+            }
+        }
+    })
+}
+
+fn capabilities() -> Value {
+    json!({
+        "get": {
+            "summary": "What the caller may do to each node. Anonymous callers get all false",
+            "security": auth(),
+            "parameters": [{
+                "name": "node_ids",
+                "in": "query",
+                "description": "Comma-separated node IDs",
+                "schema": { "type": "string" }
+            }],
+            "responses": {
+                "200": {
+                    "description": "Capabilities per known node",
+                    "content": { "application/json": { "schema": { "$ref": "#/components/schemas/Capabilities" } } }
+                }
+            }
+        }
+    })
+}
+
+fn me() -> Value {
+    json!({
+        "get": {
+            "summary": "Who the credential belongs to, and their role",
+            "security": auth(),
+            "responses": {
+                "200": {
+                    "description": "The signed-in user",
+                    "content": { "application/json": { "schema": { "$ref": "#/components/schemas/UserInfo" } } }
+                },
+                "401": error()
+            }
+        }
+    })
+}
+
+fn my_submissions() -> Value {
+    json!({
+        "get": {
+            "summary": "The caller's submissions that are waiting for review or were rejected, newest first",
+            "security": auth(),
+            "responses": {
+                "200": {
+                    "description": "Unpublished submissions",
+                    "content": { "application/json": { "schema": {
+                        "type": "array",
+                        "items": { "$ref": "#/components/schemas/Submission" }
+                    } } }
+                },
+                "401": error()
+            }
+        }
+    })
+}
+
+fn moderation_queue() -> Value {
+    json!({
+        "get": {
+            "summary": "Submissions waiting for review, oldest first (moderators only)",
+            "security": auth(),
+            "responses": {
+                "200": {
+                    "description": "Pending submissions",
+                    "content": { "application/json": { "schema": {
+                        "type": "array",
+                        "items": { "$ref": "#/components/schemas/PendingNode" }
+                    } } }
+                },
+                "401": error(),
+                "403": error()
+            }
+        }
+    })
+}
+
+fn moderation_review() -> Value {
+    json!({
+        "post": {
+            "summary": "Approve or reject pending submissions (moderators only)",
+            "security": auth(),
+            "requestBody": {
+                "required": true,
+                "content": { "application/json": { "schema": {
+                    "type": "object",
+                    "required": ["node_ids", "decision"],
+                    "properties": {
+                        "node_ids": { "type": "array", "items": { "type": "string" } },
+                        "decision": {
+                            "description": "\"Approve\", or {\"Reject\": {\"reason\": \"...\"}}"
+                        }
+                    }
+                } } }
+            },
+            "responses": {
+                "200": {
+                    "description": "Which submissions were reviewed, and why the others weren't",
+                    "content": { "application/json": { "schema": {
+                        "type": "object",
+                        "properties": {
+                            "done": { "type": "array", "items": { "type": "string" } },
+                            "failed": { "type": "array", "items": {
+                                "type": "object",
+                                "properties": {
+                                    "node_id": { "type": "string" },
+                                    "error": { "$ref": "#/components/schemas/Error/properties/error" }
+                                }
+                            } }
+                        }
+                    } } }
+                },
+                "401": error(),
+                "403": error(),
+                "422": error()
+            }
+        }
+    })
+}
+
+fn moderation_reports() -> Value {
+    json!({
+        "get": {
+            "summary": "Nodes with open reports, most reported first (moderators only)",
+            "security": auth(),
+            "responses": {
+                "200": {
+                    "description": "Reported nodes with the path leading to them",
+                    "content": { "application/json": { "schema": {
+                        "type": "array",
+                        "items": { "$ref": "#/components/schemas/ReportedNode" }
+                    } } }
+                },
+                "401": error(),
+                "403": error()
+            }
+        }
+    })
+}
+
+fn moderation_deleted() -> Value {
+    json!({
+        "get": {
+            "summary": "Deleted nodes not yet purged, newest first (admins only)",
+            "security": auth(),
+            "responses": {
+                "200": {
+                    "description": "Deleted nodes",
+                    "content": { "application/json": { "schema": {
+                        "type": "array",
+                        "items": { "$ref": "#/components/schemas/DeletedNode" }
+                    } } }
+                },
+                "401": error(),
+                "403": error()
+            }
+        }
+    })
+}
+
+fn moderation_node_reports() -> Value {
+    json!({
+        "post": {
+            "summary": "Close the open reports on a node: Resolve leaves it as it is, Dismiss puts a hidden node back, Delete removes it (moderators only)",
+            "security": auth(),
+            "parameters": [node_id()],
+            "requestBody": {
+                "required": true,
+                "content": { "application/json": { "schema": {
+                    "type": "object",
+                    "required": ["resolution"],
+                    "properties": {
+                        "resolution": { "type": "string", "enum": ["Resolve", "Dismiss", "Delete"] }
+                    }
+                } } }
+            },
+            "responses": {
+                "204": { "description": "Reports closed" },
+                "401": error(),
+                "403": error(),
+                "404": error(),
+                "409": error()
+            }
+        }
+    })
+}
+
+fn user_role() -> Value {
+    json!({
+        "put": {
+            "summary": "Give a user a role (admins only)",
+            "security": auth(),
+            "parameters": [username()],
+            "requestBody": {
+                "required": true,
+                "content": { "application/json": { "schema": {
+                    "type": "object",
+                    "required": ["role"],
+                    "properties": { "role": { "$ref": "#/components/schemas/Role" } }
+                } } }
+            },
+            "responses": {
+                "204": { "description": "Granted" },
+                "401": error(),
+                "403": error()
+            }
+        },
+        "delete": {
+            "summary": "Put a user back on the default writer role (admins only)",
+            "security": auth(),
+            "parameters": [username()],
+            "responses": {
+                "204": { "description": "Revoked" },
+                "401": error(),
+                "403": error()
+            }
+        }
+    })
+}
+
+fn user_ban() -> Value {
+    json!({
+        "put": {
+            "summary": "Ban a user, or with shadow set, let them keep writing where only they can see it (admins only)",
+            "security": auth(),
+            "parameters": [username()],
+            "requestBody": {
+                "required": true,
+                "content": { "application/json": { "schema": {
+                    "type": "object",
+                    "properties": { "shadow": { "type": "boolean", "default": false } }
+                } } }
+            },
+            "responses": {
+                "204": { "description": "Banned" },
+                "400": error(),
+                "401": error(),
+                "403": error()
+            }
+        },
+        "delete": {
+            "summary": "Lift a ban or shadowban, putting shadowbanned nodes back in the story (admins only)",
+            "security": auth(),
+            "parameters": [username()],
+            "responses": {
+                "204": { "description": "Unbanned" },
+                "401": error(),
+                "403": error()
+            }
+        }
+    })
+}
+
+fn user_purge() -> Value {
+    json!({
+        "post": {
+            "summary": "Delete every node a user wrote (admins only)",
+            "security": auth(),
+            "parameters": [username()],
+            "requestBody": {
+                "required": true,
+                "content": { "application/json": { "schema": {
+                    "type": "object",
+                    "required": ["mode"],
+                    "properties": {
+                        "mode": {
+                            "type": "string",
+                            "enum": ["Reparent", "Cascade"],
+                            "description": "Reparent moves other authors' continuations up to the nearest surviving node; Cascade deletes them too"
+                        },
+                        "dry_run": { "type": "boolean", "default": false, "description": "Only report what would happen" }
+                    }
+                } } }
+            },
+            "responses": {
+                "200": {
+                    "description": "What was (or would be) deleted and moved",
+                    "content": { "application/json": { "schema": { "$ref": "#/components/schemas/PurgePlan" } } }
+                },
+                "401": error(),
+                "403": error()
+            }
+        }
+    })
+}
+
+fn audit_log() -> Value {
+    json!({
+        "get": {
+            "summary": "Recent admin changes to the shape of the story, newest first (admins only)",
+            "security": auth(),
+            "responses": {
+                "200": {
+                    "description": "Audit log entries",
+                    "content": { "application/json": { "schema": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "at": { "type": "integer", "format": "int64" },
+                                "actor": { "type": "string" },
+                                "action": { "type": "string" },
+                                "node_id": { "type": "string" },
+                                "details": { "type": "object" }
+                            }
+                        }
+                    } } }
+                },
+                "401": error(),
+                "403": error()
+            }
+        }
+    })
+}
+
+fn descendant_counts() -> Value {
+    json!({
+        "get": {
+            "summary": "Number of nodes below each node",
+            "responses": {
+                "200": {
+                    "description": "Map of node ID to descendant count",
+                    "content": { "application/json": { "schema": {
+                        "type": "object",
+                        "additionalProperties": { "type": "integer", "format": "int64" }
+                    } } }
+                }
+            }
+        }
+    })
+}
+
+fn schemas() -> Value {
+    json!({
+        "AdventureNode": {
+            "type": "object",
+            "required": ["id", "choice_text", "story_text"],
+            "properties": {
+                "id": { "type": "string" },
+                "parent_id": { "type": "string", "nullable": true },
+                "choice_text": { "type": "string" },
+                "story_text": { "type": "string" },
+                "created_by": { "type": "string", "nullable": true }
+            }
+        },
+        "NodeLink": {
+            "type": "object",
+            "required": ["id", "parent_id", "target_id", "choice_text"],
+            "properties": {
+                "id": { "type": "string" },
+                "parent_id": { "type": "string" },
+                "target_id": { "type": "string" },
+                "choice_text": { "type": "string" },
+                "created_by": { "type": "string", "nullable": true }
+            }
+        },
+        "NodeMechanics": {
+            "type": "object",
+            "required": ["node_id", "condition", "effects"],
+            "properties": {
+                "node_id": { "type": "string" },
+                "condition": { "type": "string" },
+                "effects": { "type": "string" }
+            }
+        },
+        "Roll": {
+            "oneOf": [
+                { "type": "string", "enum": ["Weighted"] },
+                {
+                    "type": "object",
+                    "required": ["Dice"],
+                    "properties": {
+                        "Dice": {
+                            "type": "object",
+                            "required": ["count", "sides", "bonus"],
+                            "properties": {
+                                "count": { "type": "integer", "minimum": 1, "maximum": 10 },
+                                "sides": { "type": "integer", "minimum": 2, "maximum": 100 },
+                                "bonus": { "type": "string", "description": "Variable added to the roll, or empty" }
+                            }
+                        }
+                    }
+                }
+            ]
+        },
+        "ChanceTable": {
+            "type": "object",
+            "required": ["branches", "outcomes"],
+            "properties": {
+                "branches": { "type": "object", "additionalProperties": { "$ref": "#/components/schemas/Roll" } },
+                "outcomes": { "type": "object", "additionalProperties": { "type": "integer" } }
+            }
+        },
+        "Ending": { "type": "string", "enum": ["good", "bad", "neutral", "true"] },
+        "NodeEnding": {
+            "type": "object",
+            "required": ["node_id", "root_id", "ending"],
+            "properties": {
+                "node_id": { "type": "string" },
+                "root_id": { "type": "string" },
+                "ending": { "$ref": "#/components/schemas/Ending" }
+            }
+        },
+        "PendingNode": {
+            "type": "object",
+            "required": ["node", "submitted_at"],
+            "properties": {
+                "node": { "$ref": "#/components/schemas/AdventureNode" },
+                "submitted_at": { "type": "integer", "format": "int64" },
+                "parent_choice_text": { "type": "string", "nullable": true },
+                "flag_reason": {
+                    "type": "string",
+                    "nullable": true,
+                    "description": "Why a content filter held it for review, if one did"
+                }
+            }
+        },
+        "Submission": {
+            "type": "object",
+            "required": ["node", "status", "submitted_at"],
+            "properties": {
+                "node": { "$ref": "#/components/schemas/AdventureNode" },
+                "status": {
+                    "description": "\"Pending\", or {\"Rejected\": {\"reason\": \"...\"}}"
+                },
+                "submitted_at": { "type": "integer", "format": "int64" }
+            }
+        },
+        "ReportReason": {
+            "type": "string",
+            "enum": ReportReason::ALL.map(ReportReason::as_str)
+        },
+        "ReportedNode": {
+            "type": "object",
+            "required": ["node", "path", "reports", "hidden"],
+            "properties": {
+                "node": { "$ref": "#/components/schemas/AdventureNode" },
+                "path": {
+                    "description": "The nodes leading to it, root first",
+                    "type": "array",
+                    "items": { "$ref": "#/components/schemas/AdventureNode" }
+                },
+                "reports": { "type": "array", "items": {
+                    "type": "object",
+                    "properties": {
+                        "reporter": { "type": "string" },
+                        "reason": { "$ref": "#/components/schemas/ReportReason" },
+                        "details": { "type": "string" },
+                        "reported_at": { "type": "integer", "format": "int64" }
+                    }
+                } },
+                "hidden": { "type": "boolean" }
+            }
+        },
+        "DeletedNode": {
+            "type": "object",
+            "required": ["node", "deleted_at"],
+            "properties": {
+                "node": { "$ref": "#/components/schemas/AdventureNode" },
+                "deleted_at": { "type": "integer", "format": "int64" },
+                "deleted_by": { "type": "string", "nullable": true }
+            }
+        },
+        "SubtreeDeletion": {
+            "type": "object",
+            "required": ["root", "deleted", "authors"],
+            "properties": {
+                "root": { "$ref": "#/components/schemas/AdventureNode" },
+                "deleted": { "type": "array", "items": { "type": "string" } },
+                "authors": {
+                    "description": "Pairs of an author and how many of the nodes they wrote, most first",
+                    "type": "array",
+                    "items": { "type": "array", "items": {} }
+                }
+            }
+        },
+        "PurgePlan": {
+            "type": "object",
+            "required": ["deleted", "reparented", "subtrees"],
+            "properties": {
+                "deleted": { "type": "array", "items": { "type": "string" } },
+                "reparented": {
+                    "description": "Pairs of a node ID and its new parent ID (null makes it a new story)",
+                    "type": "array",
+                    "items": { "type": "array", "items": { "type": "string", "nullable": true } }
+                },
+                "subtrees": { "type": "array", "items": {
+                    "type": "object",
+                    "properties": {
+                        "root": { "$ref": "#/components/schemas/AdventureNode" },
+                        "deleted": { "type": "integer", "format": "int64" },
+                        "other_authors": { "type": "array", "items": { "type": "string" } }
+                    }
+                } }
+            }
+        },
+        "Capabilities": {
+            "type": "object",
+            "required": ["nodes", "new_story", "links"],
+            "properties": {
+                "nodes": {
+                    "type": "object",
+                    "additionalProperties": {
+                        "type": "object",
+                        "properties": {
+                            "delete": { "type": "boolean" },
+                            "edit": { "type": "boolean" },
+                            "branch": { "type": "boolean" },
+                            "report": { "type": "boolean" },
+                            "moderate": { "type": "boolean" }
+                        }
+                    }
+                },
+                "new_story": { "type": "boolean" },
+                "links": {
+                    "type": "object",
+                    "description": "Capabilities per link offered after a known node",
+                    "additionalProperties": {
+                        "type": "object",
+                        "properties": {
+                            "remove": { "type": "boolean" }
+                        }
+                    }
+                }
+            }
+        },
+        "Role": {
+            "type": "string",
+            "enum": Role::ALL.map(Role::as_str)
+        },
+        "UserInfo": {
+            "type": "object",
+            "required": ["username", "role"],
+            "properties": {
+                "username": { "type": "string" },
+                "role": { "$ref": "#/components/schemas/Role" }
+            }
+        },
+        "NewNode": {
+            "type": "object",
+            "required": ["choice_text", "story_text"],
+            "properties": {
+                "parent_id": { "type": "string", "nullable": true },
+                "choice_text": { "type": "string" },
+                "story_text": { "type": "string" },
+                "condition": { "type": "string", "description": "What the node requires; saved with it" },
+                "effects": { "type": "string", "description": "What entering the node changes; saved with it" },
+                "roll": {
+                    "allOf": [{ "$ref": "#/components/schemas/Roll" }],
+                    "nullable": true,
+                    "description": "Makes the node a random branch"
+                },
+                "outcome_value": {
+                    "type": "integer",
+                    "nullable": true,
+                    "description": "Weight or threshold, when the parent is a random branch"
+                },
+                "ending": {
+                    "allOf": [{ "$ref": "#/components/schemas/Ending" }],
+                    "nullable": true,
+                    "description": "Makes the node one of the story's endings, which only moderators can continue"
+                }
+            }
+        },
+        "Error": {
+            "type": "object",
+            "required": ["error", "message"],
+            "properties": {
+                "error": {
+                    "description": "Error code: a string such as \"NotFound\", or an object such as {\"Validation\": {\"field\", \"reason\"}}"
+                },
+                "message": { "type": "string" }
+            }
+        }
+    })
+}
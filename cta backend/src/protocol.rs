@@ -0,0 +1,251 @@
+use shared::{ENVELOPE_MAGIC, EnvelopeHeader, PROTOCOL_VERSION, RequestEnvelope, ServerMessage};
+
+/// Bare, unenveloped `ServerMessage`s sent by builds from before versioning.
+pub const LEGACY_VERSION: u32 = 1;
+
+pub const OUTDATED_MESSAGE: &str = "Client outdated, please refresh the page";
+
+pub struct DecodedRequest {
+    pub version: u32,
+    pub client_build: Option<String>,
+    pub message: ServerMessage,
+}
+
+pub enum DecodeError {
+    /// The client speaks a protocol version we no longer (or don't yet) understand.
+    UnsupportedVersion(u32),
+    /// A write from a build from before versioning. Those sign it with a Newgrounds session
+    /// id, which sessions no longer accept, so all it can do is tell the reader to reload.
+    LegacyWrite,
+    Malformed(bincode::Error),
+}
+
+pub fn decode_request(body: &[u8]) -> Result<DecodedRequest, DecodeError> {
+    let is_enveloped = body
+        .get(..4)
+        .is_some_and(|magic| magic == ENVELOPE_MAGIC.to_le_bytes());
+
+    if !is_enveloped {
+        let message = bincode::deserialize::<v1::ServerMessage>(body).map_err(DecodeError::Malformed)?;
+        if matches!(
+            message,
+            v1::ServerMessage::SubmitAdventureNode { .. } | v1::ServerMessage::DeleteAdventureNode { .. }
+        ) {
+            return Err(DecodeError::LegacyWrite);
+        }
+        return Ok(DecodedRequest {
+            version: LEGACY_VERSION,
+            client_build: None,
+            message: message.into(),
+        });
+    }
+
+    let header = bincode::deserialize::<EnvelopeHeader>(body).map_err(DecodeError::Malformed)?;
+    match header.protocol_version {
+        PROTOCOL_VERSION => {
+            let envelope = bincode::deserialize::<RequestEnvelope>(body).map_err(DecodeError::Malformed)?;
+            Ok(DecodedRequest {
+                version: PROTOCOL_VERSION,
+                client_build: Some(envelope.header.client_build),
+                message: envelope.message,
+            })
+        }
+        other => Err(DecodeError::UnsupportedVersion(other)),
+    }
+}
+
+/// Encodes `msg` in the format the client that sent a `version` request expects.
+pub fn encode_response(version: u32, msg: ServerMessage) -> bincode::Result<Vec<u8>> {
+    if version == LEGACY_VERSION {
+        bincode::serialize(&v1::ServerMessage::from(msg))
+    } else {
+        bincode::serialize(&msg)
+    }
+}
+
+/// The "please refresh" reply to a build from before versioning, which it shows as an error.
+pub fn legacy_outdated() -> bincode::Result<Vec<u8>> {
+    bincode::serialize(&v1::ServerMessage::Error(OUTDATED_MESSAGE.into()))
+}
+
+/// Frozen copy of the wire types as they were before the envelope existed.
+/// Don't change these; they have to match what old cached builds send.
+mod v1 {
+    use std::collections::HashMap;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::OUTDATED_MESSAGE;
+
+    #[derive(Serialize, Deserialize)]
+    pub struct AdventureNode {
+        pub id: String,
+        pub parent_id: Option<String>,
+        pub choice_text: String,
+        pub story_text: String,
+        pub created_by: Option<String>,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    pub enum ServerMessage {
+        RequestAdventureNodes,
+        ReturnAdventureNodes(Vec<AdventureNode>),
+        RequestDescendantCounts,
+        ReturnDescendantCounts(HashMap<String, u64>),
+        SubmitAdventureNode {
+            node: AdventureNode,
+            session_id: Option<String>,
+        },
+        DeleteAdventureNode {
+            node_id: String,
+            session_id: Option<String>,
+        },
+        Ok,
+        Error(String),
+    }
+
+    impl From<AdventureNode> for shared::AdventureNode {
+        fn from(n: AdventureNode) -> Self {
+            Self {
+                id: n.id,
+                parent_id: n.parent_id,
+                choice_text: n.choice_text,
+                story_text: n.story_text,
+                created_by: n.created_by,
+            }
+        }
+    }
+
+    impl From<shared::AdventureNode> for AdventureNode {
+        fn from(n: shared::AdventureNode) -> Self {
+            Self {
+                id: n.id,
+                parent_id: n.parent_id,
+                choice_text: n.choice_text,
+                story_text: n.story_text,
+                created_by: n.created_by,
+            }
+        }
+    }
+
+    impl From<ServerMessage> for shared::ServerMessage {
+        fn from(msg: ServerMessage) -> Self {
+            match msg {
+                ServerMessage::RequestAdventureNodes => Self::RequestAdventureNodes,
+                ServerMessage::ReturnAdventureNodes(nodes) => {
+                    Self::ReturnAdventureNodes(nodes.into_iter().map(Into::into).collect())
+                }
+                ServerMessage::RequestDescendantCounts => Self::RequestDescendantCounts,
+                ServerMessage::ReturnDescendantCounts(counts) => Self::ReturnDescendantCounts(counts),
+                ServerMessage::SubmitAdventureNode { node, session_id } => {
+                    // These builds made up their own IDs; the server assigns them now
+                    let node = shared::AdventureNode {
+                        id: String::new(),
+                        ..node.into()
+                    };
+                    Self::SubmitAdventureNode { node, session_id }
+                }
+                ServerMessage::DeleteAdventureNode { node_id, session_id } => {
+                    Self::DeleteAdventureNode { node_id, session_id }
+                }
+                ServerMessage::Ok => Self::Ok,
+                ServerMessage::Error(e) => Self::Error(shared::ApiError::BadRequest(e)),
+            }
+        }
+    }
+
+    impl From<shared::ServerMessage> for ServerMessage {
+        fn from(msg: shared::ServerMessage) -> Self {
+            match msg {
+                shared::ServerMessage::ReturnAdventureNodes(nodes) => {
+                    Self::ReturnAdventureNodes(nodes.into_iter().map(Into::into).collect())
+                }
+                shared::ServerMessage::ReturnDescendantCounts(counts) => {
+                    Self::ReturnDescendantCounts(counts)
+                }
+                shared::ServerMessage::Ok
+                | shared::ServerMessage::NodeCreated { .. }
+                | shared::ServerMessage::NodeQueued { .. } => Self::Ok,
+                shared::ServerMessage::Error(e) => Self::Error(e.to_string()),
+                _ => Self::Error(OUTDATED_MESSAGE.into()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decoded(body: &[u8]) -> DecodedRequest {
+        match decode_request(body) {
+            Ok(request) => request,
+            Err(_) => panic!("Failed to decode {:?}", body),
+        }
+    }
+
+    #[test]
+    fn current_envelopes_carry_the_build() {
+        let body = bincode::serialize(&RequestEnvelope {
+            header: EnvelopeHeader::current("abc123"),
+            message: ServerMessage::RequestSubtree { root_id: Some("root".into()), depth: 2 },
+        })
+        .unwrap();
+        let request = decoded(&body);
+        assert_eq!(request.version, PROTOCOL_VERSION);
+        assert_eq!(request.client_build.as_deref(), Some("abc123"));
+        assert!(matches!(request.message, ServerMessage::RequestSubtree { depth: 2, .. }));
+    }
+
+    #[test]
+    fn bare_reads_are_legacy_requests() {
+        let body = bincode::serialize(&v1::ServerMessage::RequestDescendantCounts).unwrap();
+        let request = decoded(&body);
+        assert_eq!(request.version, LEGACY_VERSION);
+        assert_eq!(request.client_build, None);
+        assert!(matches!(request.message, ServerMessage::RequestDescendantCounts));
+    }
+
+    #[test]
+    fn bare_writes_are_turned_away() {
+        let body = bincode::serialize(&v1::ServerMessage::DeleteAdventureNode {
+            node_id: "node".into(),
+            session_id: Some("ng-session".into()),
+        })
+        .unwrap();
+        assert!(matches!(decode_request(&body), Err(DecodeError::LegacyWrite)));
+    }
+
+    #[test]
+    fn unknown_versions_and_garbage() {
+        let mut header = EnvelopeHeader::current("abc123");
+        header.protocol_version = PROTOCOL_VERSION + 1;
+        let body = bincode::serialize(&header).unwrap();
+        assert!(matches!(
+            decode_request(&body),
+            Err(DecodeError::UnsupportedVersion(v)) if v == PROTOCOL_VERSION + 1
+        ));
+
+        assert!(matches!(decode_request(&[0xff, 0xff]), Err(DecodeError::Malformed(_))));
+        assert!(matches!(decode_request(&[]), Err(DecodeError::Malformed(_))));
+    }
+
+    #[test]
+    fn legacy_clients_get_replies_they_understand() {
+        let node = shared::AdventureNode {
+            id: "node".into(),
+            parent_id: None,
+            choice_text: "Go north".into(),
+            story_text: "You go north.".into(),
+            created_by: None,
+        };
+        let reply = encode_response(LEGACY_VERSION, ServerMessage::NodeCreated { node }).unwrap();
+        assert!(matches!(bincode::deserialize(&reply).unwrap(), v1::ServerMessage::Ok));
+
+        let reply = encode_response(LEGACY_VERSION, ServerMessage::ReturnCapabilities(Default::default())).unwrap();
+        assert!(matches!(
+            bincode::deserialize(&reply).unwrap(),
+            v1::ServerMessage::Error(e) if e == OUTDATED_MESSAGE
+        ));
+    }
+}
@@ -0,0 +1,267 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use rusqlite::params;
+use shared::{Action, AdventureNode, ApiError, PurgeMode, PurgePlan, PurgedSubtree};
+
+use crate::{AppState, audit, node_from_row, trash};
+
+/// Works out what purging `user_id` does to `nodes` (every stored node, whatever its status).
+fn plan(nodes: &[AdventureNode], user_id: &str, mode: PurgeMode) -> PurgePlan {
+    let by_id: HashMap<&str, &AdventureNode> = nodes.iter().map(|n| (n.id.as_str(), n)).collect();
+    let mut children: HashMap<&str, Vec<&AdventureNode>> = HashMap::new();
+    for node in nodes {
+        if let Some(parent_id) = &node.parent_id {
+            children.entry(parent_id.as_str()).or_default().push(node);
+        }
+    }
+    let is_theirs = |node: &AdventureNode| node.created_by.as_deref() == Some(user_id);
+
+    let mut plan = PurgePlan::default();
+    for root in nodes.iter().filter(|n| is_theirs(n)) {
+        // Only start from their topmost nodes; the ones below are covered by the walk
+        let mut ancestor = root.parent_id.as_deref().and_then(|id| by_id.get(id));
+        let mut topmost = true;
+        let mut steps = 0;
+        while let Some(node) = ancestor {
+            if is_theirs(node) {
+                topmost = false;
+                break;
+            }
+            // Guard against a corrupted graph looping forever
+            steps += 1;
+            if steps > nodes.len() {
+                break;
+            }
+            ancestor = node.parent_id.as_deref().and_then(|id| by_id.get(id));
+        }
+        if !topmost {
+            continue;
+        }
+
+        let mut deleted = 0;
+        let mut other_authors = BTreeSet::new();
+        let mut seen = HashSet::new();
+        // Pairs of a node and the nearest surviving ancestor it would move under
+        let mut stack = vec![(root, root.parent_id.clone())];
+        while let Some((node, surviving_parent)) = stack.pop() {
+            if !seen.insert(node.id.as_str()) {
+                continue;
+            }
+            let theirs = is_theirs(node);
+            if !theirs {
+                other_authors.insert(node.created_by.clone().unwrap_or_else(|| "anonymous".into()));
+            }
+
+            if theirs || mode == PurgeMode::Cascade {
+                deleted += 1;
+                plan.deleted.push(node.id.clone());
+                for child in children.get(node.id.as_str()).into_iter().flatten() {
+                    stack.push((child, surviving_parent.clone()));
+                }
+            } else {
+                // The first survivor on this branch moves up and keeps everything below it,
+                // apart from more of the purged user's nodes further down
+                plan.reparented.push((node.id.clone(), surviving_parent));
+                let mut below: Vec<&AdventureNode> = children.get(node.id.as_str()).cloned().unwrap_or_default();
+                while let Some(child) = below.pop() {
+                    if is_theirs(child) {
+                        stack.push((child, Some(child.parent_id.clone().unwrap_or_default())));
+                    } else {
+                        below.extend(children.get(child.id.as_str()).into_iter().flatten());
+                    }
+                }
+            }
+        }
+
+        plan.subtrees.push(PurgedSubtree {
+            root: root.clone(),
+            deleted,
+            other_authors: other_authors.into_iter().collect(),
+        });
+    }
+    plan
+}
+
+/// An audit log entry for each subtree of `plan`, by its root: what was deleted, and where
+/// each moved node was before, so the purge can be undone.
+fn audit_details(
+    nodes: &[AdventureNode],
+    plan: &PurgePlan,
+    user_id: &str,
+    mode: PurgeMode,
+) -> Vec<(String, serde_json::Value)> {
+    let by_id: HashMap<&str, &AdventureNode> = nodes.iter().map(|n| (n.id.as_str(), n)).collect();
+    let roots: HashSet<&str> = plan.subtrees.iter().map(|s| s.root.id.as_str()).collect();
+    // Everything the purge touched sits at or below one of the subtree roots
+    let root_of = |id: &str| {
+        let mut node = by_id.get(id);
+        for _ in 0..=nodes.len() {
+            let current = node?;
+            if roots.contains(current.id.as_str()) {
+                return Some(current.id.as_str());
+            }
+            node = current.parent_id.as_deref().and_then(|id| by_id.get(id));
+        }
+        None
+    };
+
+    let mut deleted: HashMap<&str, Vec<&str>> = HashMap::new();
+    for id in &plan.deleted {
+        if let Some(root) = root_of(id) {
+            deleted.entry(root).or_default().push(id);
+        }
+    }
+    let mut moved: HashMap<&str, Vec<serde_json::Value>> = HashMap::new();
+    for (id, new_parent_id) in &plan.reparented {
+        if let Some(root) = root_of(id) {
+            moved.entry(root).or_default().push(serde_json::json!({
+                "node_id": id,
+                "old_parent_id": by_id.get(id.as_str()).and_then(|n| n.parent_id.as_deref()),
+                "new_parent_id": new_parent_id,
+            }));
+        }
+    }
+
+    plan.subtrees
+        .iter()
+        .map(|subtree| {
+            let root = subtree.root.id.as_str();
+            let details = serde_json::json!({
+                "user_id": user_id,
+                "mode": mode,
+                "deleted": deleted.remove(root).unwrap_or_default(),
+                "moved": moved.remove(root).unwrap_or_default(),
+                "other_authors": subtree.other_authors,
+            });
+            (root.to_string(), details)
+        })
+        .collect()
+}
+
+impl AppState {
+    /// Deletes every node by `username`, or with `dry_run` just says what would happen.
+    pub fn purge_user(
+        &mut self,
+        admin: Option<&str>,
+        username: &str,
+        mode: PurgeMode,
+        dry_run: bool,
+    ) -> Result<PurgePlan, ApiError> {
+        self.authorize(admin, Action::Ban)?;
+
+        let internal = |context: &str, e: rusqlite::Error| {
+            tracing::error!("{}: {}", context, e);
+            ApiError::Internal
+        };
+        let all: Vec<AdventureNode> = self
+            .db
+            .prepare("SELECT id, parent_id, choice_text, story_text, created_by FROM nodes")
+            .and_then(|mut stmt| stmt.query_map([], node_from_row)?.collect())
+            .map_err(|e| internal("Failed to load nodes for purge", e))?;
+
+        let user_id = self.resolve_user(username)?;
+        let plan = plan(&all, &user_id, mode);
+        if dry_run || plan.deleted.is_empty() {
+            return Ok(plan);
+        }
+
+        let admin = admin.unwrap_or_default();
+        let tx = self
+            .db
+            .unchecked_transaction()
+            .map_err(|e| internal("Failed to start transaction", e))?;
+        for (id, parent_id) in &plan.reparented {
+            tx.execute(
+                "UPDATE nodes SET parent_id = ?1, published_at = unixepoch() WHERE id = ?2",
+                params![parent_id, id],
+            )
+            .map_err(|e| internal("Failed to reparent node", e))?;
+        }
+        // Deleted like any other node, so admins can restore them until the purge job runs
+        for id in &plan.deleted {
+            trash::mark_deleted(&tx, id, Some(admin)).map_err(|e| internal("Failed to delete node", e))?;
+        }
+        for (root_id, details) in audit_details(&all, &plan, &user_id, mode) {
+            audit::record(&tx, admin, "purge_user", &root_id, &details)
+                .map_err(|e| internal("Failed to record purge", e))?;
+        }
+        tx.commit().map_err(|e| internal("Failed to commit purge", e))?;
+        tracing::info!(
+            "{} purged {}: {} deleted, {} reparented ({:?})",
+            admin,
+            user_id,
+            plan.deleted.len(),
+            plan.reparented.len(),
+            mode
+        );
+
+        // Moved nodes are resent so readers place them under their new parent
+        let moved: HashSet<&str> = plan.reparented.iter().map(|(id, _)| id.as_str()).collect();
+        self.nodes.retain(|n| !moved.contains(n.id.as_str()));
+        self.reload_nodes();
+        Ok(plan)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: &str, parent_id: Option<&str>, author: &str) -> AdventureNode {
+        AdventureNode {
+            id: id.into(),
+            parent_id: parent_id.map(String::from),
+            choice_text: id.into(),
+            story_text: id.into(),
+            created_by: Some(author.into()),
+        }
+    }
+
+    /// `root` by ann, then alternating spam and other authors down one branch.
+    fn story() -> Vec<AdventureNode> {
+        vec![
+            node("root", None, "ann"),
+            node("a", Some("root"), "spam"),
+            node("b", Some("a"), "ann"),
+            node("c", Some("b"), "spam"),
+            node("d", Some("c"), "bob"),
+        ]
+    }
+
+    #[test]
+    fn reparent_moves_others_to_the_nearest_survivor() {
+        let plan = plan(&story(), "spam", PurgeMode::Reparent);
+        assert_eq!(plan.deleted, vec!["a", "c"]);
+        assert_eq!(
+            plan.reparented,
+            vec![("b".to_string(), Some("root".to_string())), ("d".to_string(), Some("b".to_string()))]
+        );
+        // Their nodes further down are part of the subtree of their topmost one
+        assert_eq!(plan.subtrees.len(), 1);
+        assert_eq!(plan.subtrees[0].root.id, "a");
+        assert_eq!(plan.subtrees[0].deleted, 2);
+        assert_eq!(plan.subtrees[0].other_authors, vec!["ann", "bob"]);
+    }
+
+    #[test]
+    fn cascade_deletes_everything_below() {
+        let plan = plan(&story(), "spam", PurgeMode::Cascade);
+        assert_eq!(plan.deleted, vec!["a", "b", "c", "d"]);
+        assert!(plan.reparented.is_empty());
+        assert_eq!(plan.subtrees[0].deleted, 4);
+        assert_eq!(plan.subtrees[0].other_authors, vec!["ann", "bob"]);
+    }
+
+    #[test]
+    fn continuations_of_a_purged_story_become_stories() {
+        let nodes = vec![node("s", None, "spam"), node("t", Some("s"), "ann"), node("u", Some("t"), "ann")];
+        let plan = plan(&nodes, "spam", PurgeMode::Reparent);
+        assert_eq!(plan.deleted, vec!["s"]);
+        assert_eq!(plan.reparented, vec![("t".to_string(), None)]);
+    }
+
+    #[test]
+    fn nothing_to_do_for_someone_without_nodes() {
+        assert_eq!(plan(&story(), "nobody", PurgeMode::Cascade), PurgePlan::default());
+    }
+}
@@ -0,0 +1,339 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use actix_web::HttpRequest;
+use shared::{ApiError, ServerMessage};
+
+use crate::get_env;
+
+/// Buckets kept before full ones (clients that have gone quiet) are dropped.
+const MAX_BUCKETS: usize = 10_000;
+
+/// Kinds of request with their own limits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Limited {
+    Submit,
+    /// Changing the rules of a node that's already there.
+    Edit,
+    Delete,
+    Llm,
+    Read,
+    /// Logging in and registering, which hash passwords.
+    Auth,
+    Report,
+    /// Reviewing, banning, purging and handing out roles.
+    Moderate,
+}
+
+impl Limited {
+    const ALL: [Self; 8] = [
+        Self::Submit,
+        Self::Edit,
+        Self::Delete,
+        Self::Llm,
+        Self::Read,
+        Self::Auth,
+        Self::Report,
+        Self::Moderate,
+    ];
+
+    fn env_name(self) -> &'static str {
+        match self {
+            Self::Submit => "SUBMIT",
+            Self::Edit => "EDIT",
+            Self::Delete => "DELETE",
+            Self::Llm => "LLM",
+            Self::Read => "READ",
+            Self::Auth => "AUTH",
+            Self::Report => "REPORT",
+            Self::Moderate => "MODERATE",
+        }
+    }
+
+    /// Defaults per signed-in user and per IP, as `<requests>/<seconds>`.
+    fn defaults(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Submit => ("10/600", "30/600"),
+            Self::Edit => ("30/600", "90/600"),
+            Self::Delete => ("20/600", "60/600"),
+            Self::Llm => ("30/3600", "60/3600"),
+            // Reads don't carry a session, so only the IP counts
+            Self::Read => ("off", "1200/60"),
+            // Nobody is signed in yet
+            Self::Auth => ("off", "30/600"),
+            Self::Report => ("10/600", "30/600"),
+            Self::Moderate => ("300/600", "600/600"),
+        }
+    }
+}
+
+/// A token bucket holding up to `burst` requests, refilled at `per_second`.
+#[derive(Clone, Copy, Debug)]
+struct Rate {
+    burst: f64,
+    per_second: f64,
+}
+
+impl Rate {
+    /// Parses `<requests>/<seconds>`.
+    fn parse(value: &str) -> Option<Self> {
+        let (requests, seconds) = value.trim().split_once('/')?;
+        let requests: f64 = requests.trim().parse().ok()?;
+        let seconds: f64 = seconds.trim().parse().ok()?;
+        (requests >= 1.0 && seconds > 0.0).then_some(Self {
+            burst: requests,
+            per_second: requests / seconds,
+        })
+    }
+
+    /// Reads `key`, where `off` means no limit.
+    fn from_env(key: &str, default: &str) -> Option<Self> {
+        let value = get_env(key, default);
+        if value.trim() == "off" {
+            return None;
+        }
+        Self::parse(&value).or_else(|| {
+            tracing::warn!("Ignoring {}={:?}; expected <requests>/<seconds> or off, using {}", key, value, default);
+            Self::parse(default)
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Key {
+    User(String),
+    Ip(String),
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, rate: Rate, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate.per_second).min(rate.burst);
+        self.updated = now;
+    }
+
+    /// Seconds until a request would be let through, or 0 if one would be now.
+    fn wait(&self, rate: Rate) -> f64 {
+        if self.tokens >= 1.0 { 0.0 } else { (1.0 - self.tokens) / rate.per_second }
+    }
+}
+
+/// Per-action request limits for each signed-in user and each client IP, read from
+/// `RATE_LIMIT_<ACTION>` and `RATE_LIMIT_<ACTION>_IP` at startup.
+pub struct RateLimiter {
+    per_user: HashMap<Limited, Rate>,
+    per_ip: HashMap<Limited, Rate>,
+    /// Take the client IP from `X-Forwarded-For` / `Forwarded`, for when we sit behind a proxy.
+    trust_proxy: bool,
+    buckets: Mutex<HashMap<(Limited, Key), Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn from_env() -> Self {
+        let mut per_user = HashMap::new();
+        let mut per_ip = HashMap::new();
+        for action in Limited::ALL {
+            let (user_default, ip_default) = action.defaults();
+            let key = format!("RATE_LIMIT_{}", action.env_name());
+            if let Some(rate) = Rate::from_env(&key, user_default) {
+                per_user.insert(action, rate);
+            }
+            if let Some(rate) = Rate::from_env(&format!("{}_IP", key), ip_default) {
+                per_ip.insert(action, rate);
+            }
+        }
+
+        Self {
+            per_user,
+            per_ip,
+            trust_proxy: get_env("TRUST_PROXY_HEADERS", "false").parse().unwrap_or(false),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn client_ip(&self, req: &HttpRequest) -> Option<String> {
+        let info = req.connection_info();
+        let ip = if self.trust_proxy { info.realip_remote_addr() } else { info.peer_addr() };
+        ip.map(String::from)
+    }
+
+    /// Takes one request of `action` from the buckets of `username` (if signed in) and the
+    /// client's IP, or says how long to wait if either is empty.
+    pub fn check(&self, action: Limited, req: &HttpRequest, username: Option<&str>) -> Result<(), ApiError> {
+        let mut keys = Vec::new();
+        if let (Some(username), Some(&rate)) = (username, self.per_user.get(&action)) {
+            keys.push((Key::User(username.to_string()), rate));
+        }
+        if let (Some(ip), Some(&rate)) = (self.client_ip(req), self.per_ip.get(&action)) {
+            keys.push((Key::Ip(ip), rate));
+        }
+        if keys.is_empty() {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() > MAX_BUCKETS {
+            let (per_user, per_ip) = (&self.per_user, &self.per_ip);
+            buckets.retain(|(action, key), bucket| {
+                let limits = if matches!(key, Key::User(_)) { per_user } else { per_ip };
+                limits.get(action).is_some_and(|&rate| {
+                    bucket.refill(rate, now);
+                    bucket.tokens < rate.burst
+                })
+            });
+        }
+
+        let mut wait: f64 = 0.0;
+        for (key, rate) in &keys {
+            let bucket = buckets.entry((action, key.clone())).or_insert(Bucket {
+                tokens: rate.burst,
+                updated: now,
+            });
+            bucket.refill(*rate, now);
+            wait = wait.max(bucket.wait(*rate));
+        }
+        if wait > 0.0 {
+            tracing::info!("Rate limited {:?} for {:?}", action, keys.iter().map(|(k, _)| k).collect::<Vec<_>>());
+            return Err(ApiError::RateLimited { retry_after: wait.ceil() as u64 });
+        }
+
+        for (key, _) in keys {
+            if let Some(bucket) = buckets.get_mut(&(action, key)) {
+                bucket.tokens -= 1.0;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Which limit a (verified) bincode message counts against, and who sent it.
+pub fn limit_for(msg: &ServerMessage) -> Option<(Limited, Option<&str>)> {
+    match msg {
+        ServerMessage::SubmitAdventureNode { node, .. } | ServerMessage::SubmitNodeWithRules { node, .. } => {
+            Some((Limited::Submit, node.created_by.as_deref()))
+        }
+        ServerMessage::CreateLink { link, .. } => Some((Limited::Submit, link.created_by.as_deref())),
+        ServerMessage::MoveNode { session_id, .. } => Some((Limited::Submit, session_id.as_deref())),
+        ServerMessage::SetNodeMechanics { session_id, .. }
+        | ServerMessage::SetRandomBranch { session_id, .. }
+        | ServerMessage::SetOutcomeValue { session_id, .. }
+        | ServerMessage::SetEnding { session_id, .. } => Some((Limited::Edit, session_id.as_deref())),
+        ServerMessage::DeleteAdventureNode { session_id, .. }
+        | ServerMessage::RestoreNode { session_id, .. }
+        | ServerMessage::DeleteSubtree { session_id, .. }
+        | ServerMessage::DeleteLink { session_id, .. } => {
+            Some((Limited::Delete, session_id.as_deref()))
+        }
+        ServerMessage::ReportNode { session_id, .. } => Some((Limited::Report, session_id.as_deref())),
+        ServerMessage::GrantRole { session_id, .. }
+        | ServerMessage::RevokeRole { session_id, .. }
+        | ServerMessage::ReviewNodes { session_id, .. }
+        | ServerMessage::ResolveReports { session_id, .. }
+        | ServerMessage::BanUser { session_id, .. }
+        | ServerMessage::UnbanUser { session_id, .. }
+        | ServerMessage::PurgeUser { session_id, .. } => Some((Limited::Moderate, session_id.as_deref())),
+        // Checked against `Auth` before the credential is verified
+        ServerMessage::Login { .. } => None,
+        ServerMessage::RequestAdventureNodes
+        | ServerMessage::RequestDescendantCounts
+        | ServerMessage::RequestSubtree { .. }
+        | ServerMessage::RequestAncestors { .. }
+        | ServerMessage::RequestChangesSince { .. }
+        | ServerMessage::RequestLinks
+        | ServerMessage::RequestMechanics
+        | ServerMessage::RequestChance
+        | ServerMessage::RequestEndings
+        | ServerMessage::RequestNodeDetails { .. }
+        | ServerMessage::RequestDetailChangesSince { .. }
+        | ServerMessage::RequestStoryEndings { .. }
+        | ServerMessage::RequestIdentity { .. }
+        | ServerMessage::RequestCapabilities { .. }
+        | ServerMessage::RequestMySubmissions { .. }
+        | ServerMessage::RequestOwnUnlistedNodes { .. }
+        | ServerMessage::RequestModerationQueue { .. }
+        | ServerMessage::RequestReports { .. }
+        | ServerMessage::RequestFlagReasons { .. }
+        | ServerMessage::RequestDeletedNodes { .. } => Some((Limited::Read, None)),
+        // Only the server sends these; a client that does gets an error, counted like a read
+        ServerMessage::ReturnAdventureNodes(_)
+        | ServerMessage::ReturnDescendantCounts(_)
+        | ServerMessage::Ok
+        | ServerMessage::Error(_)
+        | ServerMessage::NodeCreated { .. }
+        | ServerMessage::ReturnChanges(_)
+        | ServerMessage::ReturnSubtree { .. }
+        | ServerMessage::LoggedIn(_)
+        | ServerMessage::ReturnIdentity(_)
+        | ServerMessage::ReturnCapabilities(_)
+        | ServerMessage::NodeQueued { .. }
+        | ServerMessage::ReturnModerationQueue(_)
+        | ServerMessage::Reviewed { .. }
+        | ServerMessage::ReturnMySubmissions(_)
+        | ServerMessage::ReturnReports(_)
+        | ServerMessage::ReturnPurgePlan(_)
+        | ServerMessage::ReturnFlagReasons(_)
+        | ServerMessage::ReturnDeletedNodes(_)
+        | ServerMessage::ReturnSubtreeDeletion(_)
+        | ServerMessage::LinkCreated(_)
+        | ServerMessage::ReturnLinks(_)
+        | ServerMessage::ReturnMechanics(_)
+        | ServerMessage::ReturnChance(_)
+        | ServerMessage::ReturnEndings(_)
+        | ServerMessage::ReturnNodeDetails(_) => Some((Limited::Read, None)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn parses_requests_per_seconds() {
+        let rate = Rate::parse(" 30 / 600 ").unwrap();
+        assert_eq!(rate.burst, 30.0);
+        assert_eq!(rate.per_second, 0.05);
+        assert!(Rate::parse("0/60").is_none());
+        assert!(Rate::parse("10/0").is_none());
+        assert!(Rate::parse("10").is_none());
+        assert!(Rate::parse("ten/60").is_none());
+    }
+
+    #[test]
+    fn bucket_refills_evenly_up_to_the_burst() {
+        let rate = Rate::parse("10/10").unwrap();
+        let start = Instant::now();
+        let mut bucket = Bucket { tokens: 0.0, updated: start };
+        assert_eq!(bucket.wait(rate), 1.0);
+
+        bucket.refill(rate, start + Duration::from_millis(2500));
+        assert_eq!(bucket.tokens, 2.5);
+        assert_eq!(bucket.wait(rate), 0.0);
+
+        bucket.refill(rate, start + Duration::from_secs(60));
+        assert_eq!(bucket.tokens, 10.0);
+    }
+
+    #[test]
+    fn waits_for_the_rest_of_a_token() {
+        let rate = Rate::parse("2/60").unwrap();
+        let bucket = Bucket { tokens: 0.25, updated: Instant::now() };
+        assert_eq!(bucket.wait(rate), 22.5);
+    }
+
+    #[test]
+    fn writes_count_against_their_verified_author() {
+        let msg = ServerMessage::DeleteLink { link_id: "link".into(), session_id: Some("ann".into()) };
+        assert_eq!(limit_for(&msg), Some((Limited::Delete, Some("ann"))));
+        let msg = ServerMessage::RequestCapabilities { node_ids: vec![], session_id: Some("ann".into()) };
+        assert_eq!(limit_for(&msg), Some((Limited::Read, None)));
+    }
+}
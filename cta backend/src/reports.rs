@@ -0,0 +1,309 @@
+use std::collections::{HashMap, HashSet};
+
+use rusqlite::{Connection, OptionalExtension, params};
+use shared::{Action, AdventureNode, ApiError, LiveEvent, Report, ReportReason, ReportResolution, ReportedNode};
+
+use crate::moderation::{DELETED, HIDDEN, PUBLISHED};
+use crate::trash::delete_node_row;
+use crate::{AppState, load_nodes_from_db, node_from_row};
+
+const DETAILS_MAX_CHARS: usize = 1000;
+
+pub fn init_reports_table(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS reports (
+            node_id TEXT NOT NULL,
+            reporter TEXT NOT NULL,
+            reason TEXT NOT NULL,
+            details TEXT NOT NULL DEFAULT '',
+            reported_at INTEGER NOT NULL DEFAULT (unixepoch()),
+            resolution TEXT,
+            resolved_by TEXT,
+            resolved_at INTEGER,
+            PRIMARY KEY (node_id, reporter)
+        );",
+    )
+}
+
+fn internal(context: &str, e: rusqlite::Error) -> ApiError {
+    tracing::error!("{}: {}", context, e);
+    ApiError::Internal
+}
+
+fn open_report_count(conn: &Connection, node_id: &str) -> rusqlite::Result<u32> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM reports WHERE node_id = ?1 AND resolution IS NULL",
+        params![node_id],
+        |row| row.get(0),
+    )
+}
+
+impl AppState {
+    /// Records a report by `reporter`, hiding the node once it has `report_hide_threshold`
+    /// open reports.
+    pub fn report_node(
+        &mut self,
+        reporter: Option<&str>,
+        node_id: &str,
+        reason: ReportReason,
+        details: &str,
+    ) -> Result<(), ApiError> {
+        let node = self.node(node_id)?;
+        let own = reporter.is_some() && node.created_by.as_deref() == reporter;
+        self.authorize(reporter, Action::Report)?;
+        if own {
+            return Err(ApiError::BadRequest("You can't report your own writing".into()));
+        }
+        let reporter = reporter.unwrap_or_default();
+
+        let details = details.trim();
+        if details.chars().count() > DETAILS_MAX_CHARS {
+            return Err(ApiError::Validation {
+                field: "details".into(),
+                reason: format!("Keep it under {} characters", DETAILS_MAX_CHARS),
+            });
+        }
+
+        let inserted = self
+            .db
+            .execute(
+                "INSERT OR IGNORE INTO reports (node_id, reporter, reason, details) VALUES (?1, ?2, ?3, ?4)",
+                params![node_id, reporter, reason.as_str(), details],
+            )
+            .map_err(|e| internal("Failed to store report", e))?;
+        if inserted == 0 {
+            return Ok(());
+        }
+        tracing::info!("{} reported node {} as {}", reporter, node_id, reason.as_str());
+        self.publish(LiveEvent::NodeReported { node_id: node_id.to_string() });
+
+        let count = open_report_count(&self.db, node_id).map_err(|e| internal("Failed to count reports", e))?;
+        if self.report_hide_threshold > 0 && count >= self.report_hide_threshold {
+            tracing::info!("Hiding node {} after {} reports", node_id, count);
+            self.hide_node(node_id)?;
+        }
+        Ok(())
+    }
+
+    /// Takes a node and everything below it out of the story, keeping them in the database.
+    fn hide_node(&mut self, node_id: &str) -> Result<(), ApiError> {
+        let parent_id = self.node(node_id).ok().and_then(|n| n.parent_id.clone());
+        let visible = reachable(self.nodes.clone(), |id| id != node_id);
+        let kept: HashSet<&str> = visible.iter().map(|n| n.id.as_str()).collect();
+        let dropped: Vec<String> = self
+            .nodes
+            .iter()
+            .filter(|n| !kept.contains(n.id.as_str()))
+            .map(|n| n.id.clone())
+            .collect();
+
+        let tx = self
+            .db
+            .unchecked_transaction()
+            .map_err(|e| internal("Failed to start transaction", e))?;
+        tx.execute(
+            "UPDATE nodes SET status = ?1 WHERE id = ?2 AND status = ?3",
+            params![HIDDEN, node_id, PUBLISHED],
+        )
+        .map_err(|e| internal("Failed to hide node", e))?;
+        // Readers syncing incrementally learn they're gone the same way as for deletes
+        for id in &dropped {
+            tx.execute(
+                "INSERT OR REPLACE INTO node_tombstones (id, deleted_at) VALUES (?1, unixepoch())",
+                params![id],
+            )
+            .map_err(|e| internal("Failed to hide node", e))?;
+        }
+        tx.commit().map_err(|e| internal("Failed to hide node", e))?;
+
+        self.nodes = visible;
+        for id in dropped {
+            self.publish(LiveEvent::NodeDeleted { node_id: id });
+        }
+        if let Some(parent_id) = parent_id {
+            self.publish_counts(&parent_id);
+        }
+        Ok(())
+    }
+
+    /// Puts a hidden node back, along with whatever was hidden below it.
+    fn unhide_node(&mut self, node_id: &str) -> Result<(), ApiError> {
+        self.db
+            .execute(
+                "UPDATE nodes SET status = ?1 WHERE id = ?2 AND status = ?3",
+                params![PUBLISHED, node_id, HIDDEN],
+            )
+            .map_err(|e| internal("Failed to unhide node", e))?;
+
+        let visible: HashSet<String> = self.nodes.iter().map(|n| n.id.clone()).collect();
+        self.nodes = load_nodes_from_db(&self.db);
+        let restored: Vec<AdventureNode> = self
+            .nodes
+            .iter()
+            .filter(|n| !visible.contains(&n.id))
+            .cloned()
+            .collect();
+
+        // Bump them so incremental syncs pick them up again
+        for node in &restored {
+            let result = self
+                .db
+                .execute("UPDATE nodes SET published_at = unixepoch() WHERE id = ?1", params![node.id])
+                .and_then(|_| self.db.execute("DELETE FROM node_tombstones WHERE id = ?1", params![node.id]));
+            if let Err(e) = result {
+                tracing::error!("Failed to republish node {}: {}", node.id, e);
+            }
+        }
+        for node in restored {
+            self.publish(LiveEvent::NodeAdded(node));
+        }
+        self.publish_counts(node_id);
+        Ok(())
+    }
+
+    pub fn reports(&self, moderator: Option<&str>) -> Result<Vec<ReportedNode>, ApiError> {
+        self.authorize(moderator, Action::Moderate)?;
+
+        let mut by_node: HashMap<String, Vec<Report>> = HashMap::new();
+        self.db
+            .prepare(
+                "SELECT node_id, reporter, reason, details, reported_at FROM reports
+                 WHERE resolution IS NULL ORDER BY reported_at",
+            )
+            .and_then(|mut stmt| {
+                stmt.query_map([], |row| {
+                    let reason: String = row.get(2)?;
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        Report {
+                            reporter: row.get(1)?,
+                            reason: reason.parse().unwrap_or(ReportReason::Other),
+                            details: row.get(3)?,
+                            reported_at: row.get(4)?,
+                        },
+                    ))
+                })?
+                .try_for_each(|row| {
+                    let (node_id, report) = row?;
+                    by_node.entry(node_id).or_default().push(report);
+                    Ok(())
+                })
+            })
+            .map_err(|e| internal("Failed to load reports", e))?;
+
+        let mut reported = Vec::new();
+        for (node_id, reports) in by_node {
+            let row = self
+                .db
+                .query_row(
+                    "SELECT id, parent_id, choice_text, story_text, created_by, status FROM nodes WHERE id = ?1",
+                    params![node_id],
+                    |row| Ok((node_from_row(row)?, row.get::<_, String>(5)?)),
+                )
+                .optional()
+                .map_err(|e| internal("Failed to load reported node", e))?;
+            // Deleted since it was reported
+            let Some((node, status)) = row.filter(|(_, status)| status != DELETED) else {
+                continue;
+            };
+
+            let path = match node.parent_id.as_deref().map(|id| self.node(id)) {
+                Some(Ok(parent)) => {
+                    let mut path = self.ancestors(&parent.id)?;
+                    path.push(parent.clone());
+                    path
+                }
+                _ => Vec::new(),
+            };
+            reported.push(ReportedNode {
+                node,
+                path,
+                reports,
+                hidden: status == HIDDEN,
+            });
+        }
+        reported.sort_by_key(|r| std::cmp::Reverse(r.reports.len()));
+        Ok(reported)
+    }
+
+    pub fn resolve_reports(
+        &mut self,
+        moderator: Option<&str>,
+        node_id: &str,
+        resolution: ReportResolution,
+    ) -> Result<(), ApiError> {
+        self.authorize(moderator, Action::Moderate)?;
+        let moderator = moderator.unwrap_or_default();
+
+        let count = open_report_count(&self.db, node_id).map_err(|e| internal("Failed to count reports", e))?;
+        if count == 0 {
+            return Err(ApiError::NotFound);
+        }
+
+        let hidden = self.node(node_id).is_err();
+        let label = match resolution {
+            ReportResolution::Resolve => "resolved",
+            ReportResolution::Dismiss => {
+                if hidden {
+                    self.unhide_node(node_id)?;
+                }
+                "dismissed"
+            }
+            ReportResolution::Delete => {
+                if hidden {
+                    self.delete_hidden_node(node_id, moderator)?;
+                } else {
+                    self.delete_node(node_id, Some(moderator))?;
+                }
+                "deleted"
+            }
+        };
+
+        self.db
+            .execute(
+                "UPDATE reports SET resolution = ?1, resolved_by = ?2, resolved_at = unixepoch()
+                 WHERE node_id = ?3 AND resolution IS NULL",
+                params![label, moderator, node_id],
+            )
+            .map_err(|e| internal("Failed to close reports", e))?;
+        tracing::info!("{} {} {} report(s) on node {}", moderator, label, count, node_id);
+        Ok(())
+    }
+
+    /// Deletes a node that's already out of the story, as long as nothing continues from it.
+    fn delete_hidden_node(&mut self, node_id: &str, moderator: &str) -> Result<(), ApiError> {
+        let children: u32 = self
+            .db
+            .query_row(
+                "SELECT COUNT(*) FROM nodes WHERE parent_id = ?1 AND status != ?2",
+                params![node_id, DELETED],
+                |row| row.get(0),
+            )
+            .map_err(|e| internal("Failed to count children", e))?;
+        if children > 0 {
+            return Err(ApiError::HasChildren);
+        }
+        delete_node_row(&self.db, node_id, Some(moderator)).map_err(|e| internal("Failed to delete node", e))
+    }
+}
+
+/// The nodes that can be reached from a root through nodes passing `keep`, in their
+/// original order. Anything below a hidden node is left out with it.
+pub fn reachable(nodes: Vec<AdventureNode>, keep: impl Fn(&str) -> bool) -> Vec<AdventureNode> {
+    let mut children: HashMap<&str, Vec<&str>> = HashMap::new();
+    let mut stack = Vec::new();
+    for node in &nodes {
+        match &node.parent_id {
+            Some(parent_id) => children.entry(parent_id.as_str()).or_default().push(&node.id),
+            None => stack.push(node.id.as_str()),
+        }
+    }
+
+    let mut seen = HashSet::new();
+    while let Some(id) = stack.pop() {
+        if keep(id) && seen.insert(id.to_string()) {
+            stack.extend(children.get(id).into_iter().flatten());
+        }
+    }
+    nodes.into_iter().filter(|n| seen.contains(&n.id)).collect()
+}
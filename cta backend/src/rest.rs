@@ -0,0 +1,898 @@
+use std::sync::Mutex;
+
+use actix_web::{HttpRequest, HttpResponse, Responder, Scope, delete, get, http::header, post, put, web};
+use serde::{Deserialize, Serialize};
+use shared::chance::Roll;
+use shared::mechanics::NodeMechanics;
+use shared::{
+    AdventureNode, ApiError, Ending, NodeLink, NodeRules, PendingNode, PurgeMode, ReportReason, ReportResolution,
+    ReviewDecision, Role, UserInfo,
+};
+
+use crate::auth::{AuthProvider, Credential, verified_user};
+use crate::moderation::Submitted;
+use crate::rate_limit::{Limited, RateLimiter};
+use crate::users::{Names, Present};
+use crate::{AppState, compute_descendant_counts, status_for};
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: ApiError,
+    message: String,
+}
+
+/// Body of `POST /api/v1/nodes`. IDs and authorship are decided by the server.
+#[derive(Deserialize)]
+struct NewNode {
+    parent_id: Option<String>,
+    choice_text: String,
+    story_text: String,
+    /// Rules the node starts out with, saved along with it.
+    #[serde(default)]
+    condition: String,
+    #[serde(default)]
+    effects: String,
+    #[serde(default)]
+    roll: Option<Roll>,
+    #[serde(default)]
+    outcome_value: Option<u32>,
+    #[serde(default)]
+    ending: Option<Ending>,
+}
+
+/// Body of `PUT /api/v1/nodes/{id}/mechanics`.
+#[derive(Deserialize)]
+struct Mechanics {
+    #[serde(default)]
+    condition: String,
+    #[serde(default)]
+    effects: String,
+}
+
+/// Body of `PUT /api/v1/nodes/{id}/roll`.
+#[derive(Deserialize)]
+struct RandomBranch {
+    roll: Option<Roll>,
+}
+
+/// Body of `PUT /api/v1/nodes/{id}/outcome`.
+#[derive(Deserialize)]
+struct OutcomeValue {
+    value: Option<u32>,
+}
+
+/// Body of `PUT /api/v1/nodes/{id}/ending`.
+#[derive(Deserialize)]
+struct NodeEndingBody {
+    ending: Option<Ending>,
+}
+
+/// Body of `POST /api/v1/links`.
+#[derive(Deserialize)]
+struct NewLink {
+    parent_id: String,
+    target_id: String,
+    choice_text: String,
+}
+
+pub fn error_response(error: ApiError) -> HttpResponse {
+    let mut response = HttpResponse::build(status_for(&error));
+    if let ApiError::RateLimited { retry_after } = error {
+        response.insert_header((header::RETRY_AFTER, retry_after.to_string()));
+    }
+    response.json(ErrorBody {
+        message: error.to_string(),
+        error,
+    })
+}
+
+fn json_response<T: Serialize>(result: Result<T, ApiError>) -> HttpResponse {
+    match result {
+        Ok(body) => HttpResponse::Ok().json(body),
+        Err(e) => error_response(e),
+    }
+}
+
+/// `json_response` for bodies naming users, showing their display names rather than ids.
+fn presented_response<T: Serialize + Present>(state: &AppState, result: Result<T, ApiError>) -> HttpResponse {
+    json_response(result.map(|body| state.present(body)))
+}
+
+#[get("/nodes")]
+async fn list_nodes(
+    req: HttpRequest,
+    data: web::Data<Mutex<AppState>>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    if let Err(e) = limits.check(Limited::Read, &req, None) {
+        return error_response(e);
+    }
+    let state = data.lock().unwrap();
+    HttpResponse::Ok().json(state.present(state.nodes.clone()))
+}
+
+#[get("/nodes/{id}")]
+async fn get_node(
+    req: HttpRequest,
+    path: web::Path<String>,
+    data: web::Data<Mutex<AppState>>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    if let Err(e) = limits.check(Limited::Read, &req, None) {
+        return error_response(e);
+    }
+    let state = data.lock().unwrap();
+    presented_response(&state, state.node(&path).cloned())
+}
+
+#[get("/nodes/{id}/children")]
+async fn get_children(
+    req: HttpRequest,
+    path: web::Path<String>,
+    data: web::Data<Mutex<AppState>>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    if let Err(e) = limits.check(Limited::Read, &req, None) {
+        return error_response(e);
+    }
+    let state = data.lock().unwrap();
+    presented_response(&state, state.children(&path))
+}
+
+#[get("/nodes/{id}/ancestors")]
+async fn get_ancestors(
+    req: HttpRequest,
+    path: web::Path<String>,
+    data: web::Data<Mutex<AppState>>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    if let Err(e) = limits.check(Limited::Read, &req, None) {
+        return error_response(e);
+    }
+    let state = data.lock().unwrap();
+    presented_response(&state, state.ancestors(&path))
+}
+
+#[post("/nodes")]
+async fn create_node(
+    req: HttpRequest,
+    body: web::Json<NewNode>,
+    data: web::Data<Mutex<AppState>>,
+    auth: web::Data<dyn AuthProvider>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    let body = body.into_inner();
+    let created_by = verified_user(auth.get_ref(), &Credential::from_bearer(&req)).await;
+    if let Err(e) = limits.check(Limited::Submit, &req, created_by.as_deref()) {
+        return error_response(e);
+    }
+
+    let node = AdventureNode {
+        id: String::new(),
+        parent_id: body.parent_id,
+        choice_text: body.choice_text,
+        story_text: body.story_text,
+        created_by,
+    };
+    let rules = NodeRules {
+        condition: body.condition,
+        effects: body.effects,
+        roll: body.roll,
+        outcome_value: body.outcome_value,
+        ending: body.ending,
+    };
+
+    let state = &mut *data.lock().unwrap();
+    match state.submit_node(node, rules) {
+        Ok(Submitted::Published(node)) => HttpResponse::Created().json(state.present(node)),
+        Ok(Submitted::Queued(node)) => HttpResponse::Accepted().json(state.present(node)),
+        Err(e) => error_response(e),
+    }
+}
+
+#[get("/links")]
+async fn list_links(req: HttpRequest, data: web::Data<Mutex<AppState>>, limits: web::Data<RateLimiter>) -> impl Responder {
+    if let Err(e) = limits.check(Limited::Read, &req, None) {
+        return error_response(e);
+    }
+    let state = data.lock().unwrap();
+    presented_response(&state, state.links())
+}
+
+#[post("/links")]
+async fn create_link(
+    req: HttpRequest,
+    body: web::Json<NewLink>,
+    data: web::Data<Mutex<AppState>>,
+    auth: web::Data<dyn AuthProvider>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    let body = body.into_inner();
+    let created_by = verified_user(auth.get_ref(), &Credential::from_bearer(&req)).await;
+    if let Err(e) = limits.check(Limited::Submit, &req, created_by.as_deref()) {
+        return error_response(e);
+    }
+
+    let link = NodeLink {
+        id: String::new(),
+        parent_id: body.parent_id,
+        target_id: body.target_id,
+        choice_text: body.choice_text,
+        created_by,
+    };
+    let state = &mut *data.lock().unwrap();
+    match state.create_link(link) {
+        Ok(link) => HttpResponse::Created().json(state.present(link)),
+        Err(e) => error_response(e),
+    }
+}
+
+#[delete("/links/{id}")]
+async fn remove_link(
+    req: HttpRequest,
+    path: web::Path<String>,
+    data: web::Data<Mutex<AppState>>,
+    auth: web::Data<dyn AuthProvider>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    let user = verified_user(auth.get_ref(), &Credential::from_bearer(&req)).await;
+    if let Err(e) = limits.check(Limited::Delete, &req, user.as_deref()) {
+        return error_response(e);
+    }
+
+    let result = data.lock().unwrap().delete_link(user.as_deref(), &path);
+    match result {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => error_response(e),
+    }
+}
+
+#[delete("/nodes/{id}")]
+async fn remove_node(
+    req: HttpRequest,
+    path: web::Path<String>,
+    data: web::Data<Mutex<AppState>>,
+    auth: web::Data<dyn AuthProvider>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    let user = verified_user(auth.get_ref(), &Credential::from_bearer(&req)).await;
+    if let Err(e) = limits.check(Limited::Delete, &req, user.as_deref()) {
+        return error_response(e);
+    }
+
+    let result = data.lock().unwrap().delete_node(&path, user.as_deref());
+    match result {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => error_response(e),
+    }
+}
+
+#[post("/nodes/{id}/restore")]
+async fn restore_node(
+    req: HttpRequest,
+    path: web::Path<String>,
+    data: web::Data<Mutex<AppState>>,
+    auth: web::Data<dyn AuthProvider>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    let user = verified_user(auth.get_ref(), &Credential::from_bearer(&req)).await;
+    if let Err(e) = limits.check(Limited::Delete, &req, user.as_deref()) {
+        return error_response(e);
+    }
+
+    let result = data.lock().unwrap().restore_node(user.as_deref(), &path);
+    match result {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => error_response(e),
+    }
+}
+
+/// Body of `POST /api/v1/nodes/{id}/delete-subtree`.
+#[derive(Deserialize)]
+struct SubtreeDelete {
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[post("/nodes/{id}/delete-subtree")]
+async fn delete_subtree(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<SubtreeDelete>,
+    data: web::Data<Mutex<AppState>>,
+    auth: web::Data<dyn AuthProvider>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    let admin = verified_user(auth.get_ref(), &Credential::from_bearer(&req)).await;
+    if let Err(e) = limits.check(Limited::Delete, &req, admin.as_deref()) {
+        return error_response(e);
+    }
+
+    let mut state = data.lock().unwrap();
+    let result = state.delete_subtree(admin.as_deref(), &path, body.dry_run);
+    presented_response(&state, result)
+}
+
+/// Body of `PUT /api/v1/nodes/{id}/parent`.
+#[derive(Deserialize)]
+struct Move {
+    parent_id: Option<String>,
+}
+
+#[put("/nodes/{id}/parent")]
+async fn move_node(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<Move>,
+    data: web::Data<Mutex<AppState>>,
+    auth: web::Data<dyn AuthProvider>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    let admin = verified_user(auth.get_ref(), &Credential::from_bearer(&req)).await;
+    if let Err(e) = limits.check(Limited::Submit, &req, admin.as_deref()) {
+        return error_response(e);
+    }
+
+    let result = data.lock().unwrap().move_node(admin.as_deref(), &path, body.parent_id.as_deref());
+    match result {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => error_response(e),
+    }
+}
+
+#[get("/mechanics")]
+async fn list_mechanics(req: HttpRequest, data: web::Data<Mutex<AppState>>, limits: web::Data<RateLimiter>) -> impl Responder {
+    if let Err(e) = limits.check(Limited::Read, &req, None) {
+        return error_response(e);
+    }
+    let state = data.lock().unwrap();
+    json_response(state.mechanics())
+}
+
+#[put("/nodes/{id}/mechanics")]
+async fn set_mechanics(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<Mechanics>,
+    data: web::Data<Mutex<AppState>>,
+    auth: web::Data<dyn AuthProvider>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    let body = body.into_inner();
+    let user = verified_user(auth.get_ref(), &Credential::from_bearer(&req)).await;
+    if let Err(e) = limits.check(Limited::Edit, &req, user.as_deref()) {
+        return error_response(e);
+    }
+
+    let mechanics = NodeMechanics {
+        node_id: path.into_inner(),
+        condition: body.condition,
+        effects: body.effects,
+    };
+    let result = data.lock().unwrap().set_mechanics(user.as_deref(), mechanics);
+    match result {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => error_response(e),
+    }
+}
+
+#[get("/chance")]
+async fn chance_table(req: HttpRequest, data: web::Data<Mutex<AppState>>, limits: web::Data<RateLimiter>) -> impl Responder {
+    if let Err(e) = limits.check(Limited::Read, &req, None) {
+        return error_response(e);
+    }
+    let state = data.lock().unwrap();
+    json_response(state.chance())
+}
+
+#[put("/nodes/{id}/roll")]
+async fn set_random_branch(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<RandomBranch>,
+    data: web::Data<Mutex<AppState>>,
+    auth: web::Data<dyn AuthProvider>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    let user = verified_user(auth.get_ref(), &Credential::from_bearer(&req)).await;
+    if let Err(e) = limits.check(Limited::Edit, &req, user.as_deref()) {
+        return error_response(e);
+    }
+
+    let result = data.lock().unwrap().set_random_branch(user.as_deref(), &path, body.into_inner().roll);
+    match result {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => error_response(e),
+    }
+}
+
+#[put("/nodes/{id}/outcome")]
+async fn set_outcome_value(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<OutcomeValue>,
+    data: web::Data<Mutex<AppState>>,
+    auth: web::Data<dyn AuthProvider>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    let user = verified_user(auth.get_ref(), &Credential::from_bearer(&req)).await;
+    if let Err(e) = limits.check(Limited::Edit, &req, user.as_deref()) {
+        return error_response(e);
+    }
+
+    let result = data.lock().unwrap().set_outcome_value(user.as_deref(), &path, body.value);
+    match result {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => error_response(e),
+    }
+}
+
+#[get("/endings")]
+async fn list_endings(req: HttpRequest, data: web::Data<Mutex<AppState>>, limits: web::Data<RateLimiter>) -> impl Responder {
+    if let Err(e) = limits.check(Limited::Read, &req, None) {
+        return error_response(e);
+    }
+    let state = data.lock().unwrap();
+    json_response(state.endings())
+}
+
+#[put("/nodes/{id}/ending")]
+async fn set_ending(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<NodeEndingBody>,
+    data: web::Data<Mutex<AppState>>,
+    auth: web::Data<dyn AuthProvider>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    let user = verified_user(auth.get_ref(), &Credential::from_bearer(&req)).await;
+    if let Err(e) = limits.check(Limited::Edit, &req, user.as_deref()) {
+        return error_response(e);
+    }
+
+    let result = data.lock().unwrap().set_ending(user.as_deref(), &path, body.ending);
+    match result {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => error_response(e),
+    }
+}
+
+#[get("/audit-log")]
+async fn audit_log(
+    req: HttpRequest,
+    data: web::Data<Mutex<AppState>>,
+    auth: web::Data<dyn AuthProvider>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    if let Err(e) = limits.check(Limited::Read, &req, None) {
+        return error_response(e);
+    }
+    let admin = verified_user(auth.get_ref(), &Credential::from_bearer(&req)).await;
+    let state = data.lock().unwrap();
+    presented_response(&state, state.audit_log(admin.as_deref()))
+}
+
+/// Body of `PUT /api/v1/users/{username}/role`.
+#[derive(Deserialize)]
+struct RoleAssignment {
+    role: Role,
+}
+
+/// Query of `GET /api/v1/capabilities`.
+#[derive(Deserialize)]
+struct CapabilitiesQuery {
+    /// Comma-separated node IDs.
+    #[serde(default)]
+    node_ids: String,
+}
+
+#[get("/capabilities")]
+async fn capabilities(
+    req: HttpRequest,
+    query: web::Query<CapabilitiesQuery>,
+    data: web::Data<Mutex<AppState>>,
+    auth: web::Data<dyn AuthProvider>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    if let Err(e) = limits.check(Limited::Read, &req, None) {
+        return error_response(e);
+    }
+    let user = verified_user(auth.get_ref(), &Credential::from_bearer(&req)).await;
+    let node_ids: Vec<String> = query
+        .node_ids
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(String::from)
+        .collect();
+
+    let state = data.lock().unwrap();
+    json_response(state.capabilities(user.as_deref(), &node_ids))
+}
+
+#[get("/me")]
+async fn me(
+    req: HttpRequest,
+    data: web::Data<Mutex<AppState>>,
+    auth: web::Data<dyn AuthProvider>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    if let Err(e) = limits.check(Limited::Read, &req, None) {
+        return error_response(e);
+    }
+    let Some(user) = verified_user(auth.get_ref(), &Credential::from_bearer(&req)).await else {
+        return error_response(ApiError::Unauthenticated);
+    };
+    let state = data.lock().unwrap();
+    let role = state.role_of(&user);
+    presented_response(&state, role.map(|role| UserInfo { username: user, role }))
+}
+
+#[put("/users/{username}/role")]
+async fn grant_role(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<RoleAssignment>,
+    data: web::Data<Mutex<AppState>>,
+    auth: web::Data<dyn AuthProvider>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    let admin = verified_user(auth.get_ref(), &Credential::from_bearer(&req)).await;
+    if let Err(e) = limits.check(Limited::Moderate, &req, admin.as_deref()) {
+        return error_response(e);
+    }
+
+    let result = data.lock().unwrap().grant_role(admin.as_deref(), &path, body.role);
+    match result {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => error_response(e),
+    }
+}
+
+#[delete("/users/{username}/role")]
+async fn revoke_role(
+    req: HttpRequest,
+    path: web::Path<String>,
+    data: web::Data<Mutex<AppState>>,
+    auth: web::Data<dyn AuthProvider>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    let admin = verified_user(auth.get_ref(), &Credential::from_bearer(&req)).await;
+    if let Err(e) = limits.check(Limited::Moderate, &req, admin.as_deref()) {
+        return error_response(e);
+    }
+
+    let result = data.lock().unwrap().revoke_role(admin.as_deref(), &path);
+    match result {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => error_response(e),
+    }
+}
+
+/// Body of `PUT /api/v1/users/{username}/ban`.
+#[derive(Deserialize)]
+struct Ban {
+    #[serde(default)]
+    shadow: bool,
+}
+
+/// Body of `POST /api/v1/users/{username}/purge`.
+#[derive(Deserialize)]
+struct Purge {
+    mode: PurgeMode,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[put("/users/{username}/ban")]
+async fn ban_user(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<Ban>,
+    data: web::Data<Mutex<AppState>>,
+    auth: web::Data<dyn AuthProvider>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    let admin = verified_user(auth.get_ref(), &Credential::from_bearer(&req)).await;
+    if let Err(e) = limits.check(Limited::Moderate, &req, admin.as_deref()) {
+        return error_response(e);
+    }
+
+    let result = data.lock().unwrap().ban_user(admin.as_deref(), &path, body.shadow);
+    match result {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => error_response(e),
+    }
+}
+
+#[delete("/users/{username}/ban")]
+async fn unban_user(
+    req: HttpRequest,
+    path: web::Path<String>,
+    data: web::Data<Mutex<AppState>>,
+    auth: web::Data<dyn AuthProvider>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    let admin = verified_user(auth.get_ref(), &Credential::from_bearer(&req)).await;
+    if let Err(e) = limits.check(Limited::Moderate, &req, admin.as_deref()) {
+        return error_response(e);
+    }
+
+    let result = data.lock().unwrap().unban_user(admin.as_deref(), &path);
+    match result {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => error_response(e),
+    }
+}
+
+#[post("/users/{username}/purge")]
+async fn purge_user(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<Purge>,
+    data: web::Data<Mutex<AppState>>,
+    auth: web::Data<dyn AuthProvider>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    let admin = verified_user(auth.get_ref(), &Credential::from_bearer(&req)).await;
+    if let Err(e) = limits.check(Limited::Moderate, &req, admin.as_deref()) {
+        return error_response(e);
+    }
+
+    let mut state = data.lock().unwrap();
+    let result = state.purge_user(admin.as_deref(), &path, body.mode, body.dry_run);
+    presented_response(&state, result)
+}
+
+/// Body of `POST /api/v1/moderation/review`.
+#[derive(Deserialize)]
+struct Review {
+    node_ids: Vec<String>,
+    decision: ReviewDecision,
+}
+
+#[derive(Serialize)]
+struct ReviewFailure {
+    node_id: String,
+    error: ApiError,
+}
+
+#[derive(Serialize)]
+struct ReviewOutcome {
+    done: Vec<String>,
+    failed: Vec<ReviewFailure>,
+}
+
+/// An entry of `GET /api/v1/moderation/queue`.
+#[derive(Serialize)]
+struct QueuedNode {
+    #[serde(flatten)]
+    pending: PendingNode,
+    flag_reason: Option<String>,
+}
+
+impl Present for QueuedNode {
+    fn present(&mut self, names: &Names) {
+        self.pending.present(names);
+    }
+}
+
+#[get("/moderation/queue")]
+async fn moderation_queue(
+    req: HttpRequest,
+    data: web::Data<Mutex<AppState>>,
+    auth: web::Data<dyn AuthProvider>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    if let Err(e) = limits.check(Limited::Read, &req, None) {
+        return error_response(e);
+    }
+    let moderator = verified_user(auth.get_ref(), &Credential::from_bearer(&req)).await;
+    let state = data.lock().unwrap();
+    let queue = state.moderation_queue(moderator.as_deref()).and_then(|queue| {
+        let mut reasons = state.flag_reasons(moderator.as_deref())?;
+        Ok(queue
+            .into_iter()
+            .map(|pending| QueuedNode {
+                flag_reason: reasons.remove(&pending.node.id),
+                pending,
+            })
+            .collect::<Vec<_>>())
+    });
+    presented_response(&state, queue)
+}
+
+#[post("/moderation/review")]
+async fn review(
+    req: HttpRequest,
+    body: web::Json<Review>,
+    data: web::Data<Mutex<AppState>>,
+    auth: web::Data<dyn AuthProvider>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    let moderator = verified_user(auth.get_ref(), &Credential::from_bearer(&req)).await;
+    if let Err(e) = limits.check(Limited::Moderate, &req, moderator.as_deref()) {
+        return error_response(e);
+    }
+
+    let result = data
+        .lock()
+        .unwrap()
+        .review_nodes(moderator.as_deref(), &body.node_ids, &body.decision);
+    json_response(result.map(|(done, failed)| ReviewOutcome {
+        done,
+        failed: failed
+            .into_iter()
+            .map(|(node_id, error)| ReviewFailure { node_id, error })
+            .collect(),
+    }))
+}
+
+/// Body of `POST /api/v1/nodes/{id}/reports`.
+#[derive(Deserialize)]
+struct NewReport {
+    reason: ReportReason,
+    #[serde(default)]
+    details: String,
+}
+
+/// Body of `POST /api/v1/moderation/reports/{id}`.
+#[derive(Deserialize)]
+struct Resolution {
+    resolution: ReportResolution,
+}
+
+#[post("/nodes/{id}/reports")]
+async fn report_node(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<NewReport>,
+    data: web::Data<Mutex<AppState>>,
+    auth: web::Data<dyn AuthProvider>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    let reporter = verified_user(auth.get_ref(), &Credential::from_bearer(&req)).await;
+    if let Err(e) = limits.check(Limited::Report, &req, reporter.as_deref()) {
+        return error_response(e);
+    }
+
+    let result = data
+        .lock()
+        .unwrap()
+        .report_node(reporter.as_deref(), &path, body.reason, &body.details);
+    match result {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => error_response(e),
+    }
+}
+
+#[get("/moderation/reports")]
+async fn reports(
+    req: HttpRequest,
+    data: web::Data<Mutex<AppState>>,
+    auth: web::Data<dyn AuthProvider>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    if let Err(e) = limits.check(Limited::Read, &req, None) {
+        return error_response(e);
+    }
+    let moderator = verified_user(auth.get_ref(), &Credential::from_bearer(&req)).await;
+    let state = data.lock().unwrap();
+    presented_response(&state, state.reports(moderator.as_deref()))
+}
+
+#[get("/moderation/deleted")]
+async fn deleted_nodes(
+    req: HttpRequest,
+    data: web::Data<Mutex<AppState>>,
+    auth: web::Data<dyn AuthProvider>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    if let Err(e) = limits.check(Limited::Read, &req, None) {
+        return error_response(e);
+    }
+    let admin = verified_user(auth.get_ref(), &Credential::from_bearer(&req)).await;
+    let state = data.lock().unwrap();
+    presented_response(&state, state.deleted_nodes(admin.as_deref()))
+}
+
+#[post("/moderation/reports/{id}")]
+async fn resolve_reports(
+    req: HttpRequest,
+    path: web::Path<String>,
+    body: web::Json<Resolution>,
+    data: web::Data<Mutex<AppState>>,
+    auth: web::Data<dyn AuthProvider>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    let moderator = verified_user(auth.get_ref(), &Credential::from_bearer(&req)).await;
+    if let Err(e) = limits.check(Limited::Moderate, &req, moderator.as_deref()) {
+        return error_response(e);
+    }
+
+    let result = data
+        .lock()
+        .unwrap()
+        .resolve_reports(moderator.as_deref(), &path, body.resolution);
+    match result {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => error_response(e),
+    }
+}
+
+#[get("/me/submissions")]
+async fn my_submissions(
+    req: HttpRequest,
+    data: web::Data<Mutex<AppState>>,
+    auth: web::Data<dyn AuthProvider>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    if let Err(e) = limits.check(Limited::Read, &req, None) {
+        return error_response(e);
+    }
+    let user = verified_user(auth.get_ref(), &Credential::from_bearer(&req)).await;
+    let state = data.lock().unwrap();
+    presented_response(&state, state.my_submissions(user.as_deref()))
+}
+
+#[get("/descendant-counts")]
+async fn descendant_counts(
+    req: HttpRequest,
+    data: web::Data<Mutex<AppState>>,
+    limits: web::Data<RateLimiter>,
+) -> impl Responder {
+    if let Err(e) = limits.check(Limited::Read, &req, None) {
+        return error_response(e);
+    }
+    let state = data.lock().unwrap();
+    HttpResponse::Ok().json(compute_descendant_counts(&state.nodes))
+}
+
+#[get("/openapi.json")]
+async fn openapi(req: HttpRequest, limits: web::Data<RateLimiter>) -> impl Responder {
+    if let Err(e) = limits.check(Limited::Read, &req, None) {
+        return error_response(e);
+    }
+    HttpResponse::Ok().json(crate::openapi::document())
+}
+
+/// JSON mirror of the bincode `/api` endpoint, for scripts and tooling.
+pub fn routes() -> Scope {
+    web::scope("/api/v1")
+        .service(list_nodes)
+        .service(create_node)
+        .service(get_node)
+        .service(get_children)
+        .service(get_ancestors)
+        .service(remove_node)
+        .service(list_links)
+        .service(create_link)
+        .service(remove_link)
+        .service(list_mechanics)
+        .service(set_mechanics)
+        .service(chance_table)
+        .service(set_random_branch)
+        .service(set_outcome_value)
+        .service(list_endings)
+        .service(set_ending)
+        .service(report_node)
+        .service(restore_node)
+        .service(delete_subtree)
+        .service(move_node)
+        .service(descendant_counts)
+        .service(capabilities)
+        .service(me)
+        .service(my_submissions)
+        .service(moderation_queue)
+        .service(review)
+        .service(reports)
+        .service(resolve_reports)
+        .service(deleted_nodes)
+        .service(grant_role)
+        .service(revoke_role)
+        .service(ban_user)
+        .service(unban_user)
+        .service(purge_user)
+        .service(audit_log)
+        .service(openapi)
+}
@@ -0,0 +1,224 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use rusqlite::{Connection, params};
+use shared::{Action, AdventureNode, ApiError, LiveEvent, SubtreeDeletion};
+
+use crate::validation::check_siblings;
+use crate::{AppState, audit, node_from_row, trash};
+
+pub fn init_archive_table(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS node_archive (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            root_id TEXT NOT NULL,
+            archived_by TEXT NOT NULL,
+            archived_at INTEGER NOT NULL DEFAULT (unixepoch()),
+            nodes TEXT NOT NULL
+        );",
+    )
+}
+
+/// A stored node with the bookkeeping an archive keeps alongside it.
+struct StoredNode {
+    node: AdventureNode,
+    status: String,
+    created_at: Option<u64>,
+}
+
+fn internal(context: &str, e: rusqlite::Error) -> ApiError {
+    tracing::error!("{}: {}", context, e);
+    ApiError::Internal
+}
+
+/// Every stored node, whatever its status.
+fn load_stored_nodes(conn: &Connection) -> rusqlite::Result<Vec<StoredNode>> {
+    conn.prepare("SELECT id, parent_id, choice_text, story_text, created_by, status, created_at FROM nodes")?
+        .query_map([], |row| {
+            Ok(StoredNode {
+                node: node_from_row(row)?,
+                status: row.get(5)?,
+                created_at: row.get(6)?,
+            })
+        })?
+        .collect()
+}
+
+/// `root_id` and everything below it, parents before children.
+fn subtree_of<'a>(nodes: &'a [StoredNode], root_id: &str) -> Vec<&'a StoredNode> {
+    let mut children: HashMap<&str, Vec<&StoredNode>> = HashMap::new();
+    for stored in nodes {
+        if let Some(parent_id) = &stored.node.parent_id {
+            children.entry(parent_id.as_str()).or_default().push(stored);
+        }
+    }
+
+    let mut found = Vec::new();
+    let mut seen = HashSet::new();
+    let mut stack: Vec<&StoredNode> = nodes.iter().filter(|s| s.node.id == root_id).collect();
+    while let Some(stored) = stack.pop() {
+        // Guard against a corrupted graph looping forever
+        if !seen.insert(stored.node.id.as_str()) {
+            continue;
+        }
+        found.push(stored);
+        stack.extend(children.get(stored.node.id.as_str()).into_iter().flatten());
+    }
+    found
+}
+
+impl AppState {
+    /// Deletes `node_id` and everything below it, whatever their status, after copying
+    /// them to `node_archive`. With `dry_run` only says what would go.
+    pub fn delete_subtree(
+        &mut self,
+        admin: Option<&str>,
+        node_id: &str,
+        dry_run: bool,
+    ) -> Result<SubtreeDeletion, ApiError> {
+        self.authorize(admin, Action::Restructure)?;
+        let admin = admin.unwrap_or_default();
+
+        let stored = load_stored_nodes(&self.db).map_err(|e| internal("Failed to load nodes", e))?;
+        let subtree = subtree_of(&stored, node_id);
+        let Some(root) = subtree.first() else {
+            return Err(ApiError::NotFound);
+        };
+
+        let mut authors: BTreeMap<String, u64> = BTreeMap::new();
+        for s in &subtree {
+            *authors.entry(s.node.created_by.clone().unwrap_or_else(|| "anonymous".into())).or_default() += 1;
+        }
+        let mut authors: Vec<(String, u64)> = authors.into_iter().collect();
+        authors.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        let deletion = SubtreeDeletion {
+            root: root.node.clone(),
+            deleted: subtree.iter().map(|s| s.node.id.clone()).collect(),
+            authors,
+        };
+        if dry_run {
+            return Ok(deletion);
+        }
+
+        let archived: Vec<serde_json::Value> = subtree
+            .iter()
+            .map(|s| {
+                serde_json::json!({
+                    "node": s.node,
+                    "status": s.status,
+                    "created_at": s.created_at,
+                })
+            })
+            .collect();
+
+        let tx = self
+            .db
+            .unchecked_transaction()
+            .map_err(|e| internal("Failed to start transaction", e))?;
+        tx.execute(
+            "INSERT INTO node_archive (root_id, archived_by, nodes) VALUES (?1, ?2, ?3)",
+            params![node_id, admin, serde_json::Value::from(archived).to_string()],
+        )
+        .map_err(|e| internal("Failed to archive subtree", e))?;
+        let archive_id = tx.last_insert_rowid();
+        for id in &deletion.deleted {
+            trash::forget_node(&tx, id)
+                .and_then(|_| {
+                    tx.execute(
+                        "INSERT OR REPLACE INTO node_tombstones (id, deleted_at) VALUES (?1, unixepoch())",
+                        params![id],
+                    )
+                })
+                .map_err(|e| internal("Failed to delete node", e))?;
+        }
+        audit::record(
+            &tx,
+            admin,
+            "delete_subtree",
+            node_id,
+            &serde_json::json!({
+                "archive_id": archive_id,
+                "parent_id": deletion.root.parent_id,
+                "deleted": deletion.deleted.len(),
+                "authors": deletion.authors,
+            }),
+        )
+        .map_err(|e| internal("Failed to record subtree deletion", e))?;
+        tx.commit().map_err(|e| internal("Failed to commit subtree deletion", e))?;
+        tracing::info!(
+            "{} deleted subtree {} ({} nodes, archive {})",
+            admin,
+            node_id,
+            deletion.deleted.len(),
+            archive_id
+        );
+
+        self.reload_nodes();
+        Ok(deletion)
+    }
+
+    /// Moves `node_id` and everything below it under `new_parent_id`, or makes it a new
+    /// story. Both have to be in the story, and the new parent can't be below the node.
+    pub fn move_node(
+        &mut self,
+        admin: Option<&str>,
+        node_id: &str,
+        new_parent_id: Option<&str>,
+    ) -> Result<(), ApiError> {
+        self.authorize(admin, Action::Restructure)?;
+        let admin = admin.unwrap_or_default();
+
+        let old_parent_id = self.node(node_id)?.parent_id.clone();
+        if old_parent_id.as_deref() == new_parent_id {
+            return Ok(());
+        }
+        if let Some(new_parent_id) = new_parent_id {
+            let invalid = |reason: &str| ApiError::Validation {
+                field: "parent_id".into(),
+                reason: reason.into(),
+            };
+            self.node(new_parent_id).map_err(|_| invalid("That node isn't in the story"))?;
+            let mut chain = self.ancestors(new_parent_id)?;
+            chain.extend(self.node(new_parent_id).ok().cloned());
+            if chain.iter().any(|n| n.id == node_id) {
+                return Err(invalid("A node can't be moved below itself"));
+            }
+            check_siblings(new_parent_id, &self.nodes, &self.links()?, &self.validation)?;
+        }
+
+        let tx = self
+            .db
+            .unchecked_transaction()
+            .map_err(|e| internal("Failed to start transaction", e))?;
+        // Bumped so incremental syncs resend it under its new parent
+        tx.execute(
+            "UPDATE nodes SET parent_id = ?1, published_at = unixepoch() WHERE id = ?2",
+            params![new_parent_id, node_id],
+        )
+        .map_err(|e| internal("Failed to move node", e))?;
+        audit::record(
+            &tx,
+            admin,
+            "move_node",
+            node_id,
+            &serde_json::json!({
+                "old_parent_id": old_parent_id,
+                "new_parent_id": new_parent_id,
+            }),
+        )
+        .map_err(|e| internal("Failed to record move", e))?;
+        tx.commit().map_err(|e| internal("Failed to commit move", e))?;
+        tracing::info!("{} moved node {} from {:?} to {:?}", admin, node_id, old_parent_id, new_parent_id);
+
+        let Some(node) = self.nodes.iter_mut().find(|n| n.id == node_id) else {
+            return Ok(());
+        };
+        node.parent_id = new_parent_id.map(String::from);
+        let node = node.clone();
+        self.publish(LiveEvent::NodeAdded(node));
+        if let Some(old_parent_id) = old_parent_id {
+            self.publish_counts(&old_parent_id);
+        }
+        self.publish_counts(node_id);
+        Ok(())
+    }
+}
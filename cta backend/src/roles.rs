@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+
+use rusqlite::{Connection, OptionalExtension, params};
+use shared::{Action, ApiError, Capabilities, LinkCapabilities, NodeCapabilities, Role};
+
+use crate::{AppState, users};
+
+/// Newgrounds username of the admin from before roles were stored.
+const FORMER_ADMIN: &str = "comicstosteal";
+
+pub fn init_roles_table(conn: &Connection) -> rusqlite::Result<()> {
+    rename_username_column(conn, "user_roles")?;
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'user_roles')",
+        [],
+        |row| row.get(0),
+    )?;
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS user_roles (
+            user_id TEXT PRIMARY KEY,
+            role TEXT NOT NULL,
+            granted_by TEXT,
+            granted_at INTEGER NOT NULL DEFAULT (unixepoch())
+        );",
+    )?;
+
+    if !exists {
+        // Their Newgrounds id isn't known yet; the row moves over to it when they next log in
+        tracing::info!("Keeping {} on as admin", FORMER_ADMIN);
+        set_role(conn, FORMER_ADMIN, Role::Admin, None)?;
+    }
+    Ok(())
+}
+
+/// Renames `table.username` to `user_id` in databases from before users had stable ids.
+/// The values are carried over as they are; `users` moves them to ids at each user's next login.
+pub fn rename_username_column(conn: &Connection, table: &str) -> rusqlite::Result<()> {
+    let old: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM pragma_table_info(?1) WHERE name = 'username')",
+        params![table],
+        |row| row.get(0),
+    )?;
+    if old {
+        tracing::info!("Renaming {}.username to user_id", table);
+        conn.execute_batch(&format!("ALTER TABLE {} RENAME COLUMN username TO user_id", table))?;
+    }
+    Ok(())
+}
+
+/// Role assigned to `user_id`, or `Role::DEFAULT` if nobody has assigned one.
+pub fn role_of(conn: &Connection, user_id: &str) -> rusqlite::Result<Role> {
+    let role: Option<String> = conn
+        .query_row(
+            "SELECT role FROM user_roles WHERE user_id = ?1",
+            params![user_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    Ok(match role {
+        Some(role) => role.parse().unwrap_or_else(|e| {
+            tracing::warn!("Ignoring role of {}: {}", user_id, e);
+            Role::DEFAULT
+        }),
+        None => Role::DEFAULT,
+    })
+}
+
+pub fn set_role(conn: &Connection, user_id: &str, role: Role, granted_by: Option<&str>) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO user_roles (user_id, role, granted_by) VALUES (?1, ?2, ?3)",
+        params![user_id, role.as_str(), granted_by],
+    )?;
+    Ok(())
+}
+
+pub fn clear_role(conn: &Connection, user_id: &str) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM user_roles WHERE user_id = ?1", params![user_id])?;
+    Ok(())
+}
+
+/// Everyone with an explicitly assigned role, sorted by name, with their id.
+pub fn list_roles(conn: &Connection) -> rusqlite::Result<Vec<(String, String, String)>> {
+    let mut stmt = conn.prepare(
+        "SELECT coalesce(users.username, user_roles.user_id) AS name, user_roles.user_id, role
+         FROM user_roles LEFT JOIN users ON users.id = user_roles.user_id ORDER BY name",
+    )?;
+    stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect()
+}
+
+impl AppState {
+    pub fn role_of(&self, user_id: &str) -> Result<Role, ApiError> {
+        role_of(&self.db, user_id).map_err(|e| {
+            tracing::error!("Failed to look up role of {}: {}", user_id, e);
+            ApiError::Internal
+        })
+    }
+
+    /// Checks that `user_id` may perform `action`, returning their role if so.
+    pub fn authorize(&self, user_id: Option<&str>, action: Action) -> Result<Role, ApiError> {
+        let user_id = user_id.ok_or(ApiError::Unauthenticated)?;
+        let role = self.role_of(user_id)?;
+        if role.allows(action) {
+            Ok(role)
+        } else {
+            tracing::info!("{} ({}) may not {:?}", user_id, role, action);
+            Err(ApiError::Forbidden)
+        }
+    }
+
+    /// What `user_id` may do to each of `node_ids`, by the same rules the actions enforce.
+    pub fn capabilities(&self, user_id: Option<&str>, node_ids: &[String]) -> Result<Capabilities, ApiError> {
+        let role = user_id.map(|u| self.role_of(u)).transpose()?;
+        let allows = |action| role.is_some_and(|role: Role| role.allows(action));
+
+        // A shadowbanned user keeps seeing, and branching from, their own unlisted nodes
+        let unlisted = self.unlisted_nodes_of(user_id)?;
+        let links = self.links_after(node_ids)?;
+        let mut nodes = HashMap::new();
+        for id in node_ids {
+            let listed = self.node(id).ok();
+            let Some(node) = listed.or_else(|| unlisted.iter().find(|n| n.id == *id)) else {
+                continue;
+            };
+            let own = user_id.is_some() && node.created_by.as_deref() == user_id;
+            let children = self
+                .nodes
+                .iter()
+                .chain(&unlisted)
+                .filter(|n| n.parent_id.as_deref() == Some(id.as_str()))
+                .count();
+
+            nodes.insert(
+                id.clone(),
+                NodeCapabilities {
+                    delete: listed.is_some()
+                        && children == 0
+                        && allows(if own { Action::DeleteOwn } else { Action::DeleteAny }),
+                    edit: (own && allows(Action::Edit)) || allows(Action::Moderate),
+                    branch: allows(Action::Submit)
+                        && children + links.iter().filter(|l| l.parent_id == *id).count()
+                            < self.validation.max_siblings
+                        && (allows(Action::Moderate) || !self.is_ending(id)?),
+                    report: !own && allows(Action::Report),
+                    moderate: allows(Action::Moderate),
+                },
+            );
+        }
+
+        let links = links
+            .into_iter()
+            .map(|link| {
+                let own = user_id.is_some() && link.created_by.as_deref() == user_id;
+                let remove = allows(if own { Action::DeleteOwn } else { Action::DeleteAny });
+                (link.id, LinkCapabilities { remove })
+            })
+            .collect();
+
+        Ok(Capabilities {
+            nodes,
+            new_story: allows(Action::Submit),
+            links,
+        })
+    }
+
+    pub fn grant_role(&self, admin: Option<&str>, username: &str, role: Role) -> Result<(), ApiError> {
+        self.authorize(admin, Action::ManageRoles)?;
+        let username = username.trim();
+        if username.is_empty() {
+            return Err(ApiError::Validation {
+                field: "username".into(),
+                reason: "Username can't be empty".into(),
+            });
+        }
+
+        let user_id = self.resolve_user(username)?;
+        set_role(&self.db, &user_id, role, admin).map_err(|e| {
+            tracing::error!("Failed to grant {} to {}: {}", role, user_id, e);
+            ApiError::Internal
+        })?;
+        tracing::info!("{:?} made {} a {}", admin, user_id, role);
+        Ok(())
+    }
+
+    pub fn revoke_role(&self, admin: Option<&str>, username: &str) -> Result<(), ApiError> {
+        self.authorize(admin, Action::ManageRoles)?;
+        let user_id = self.resolve_user(username)?;
+        clear_role(&self.db, &user_id).map_err(|e| {
+            tracing::error!("Failed to revoke role of {}: {}", user_id, e);
+            ApiError::Internal
+        })?;
+        tracing::info!("{:?} reset {} to {}", admin, user_id, Role::DEFAULT);
+        Ok(())
+    }
+}
+
+/// Handles `cta-backend <command> ...` run from a shell on the server, for managing roles
+/// before any admin exists. Users are named by username (whoever last logged in with it)
+/// or by id, e.g. `ng:12345`. Returns `None` if `args` isn't a command.
+pub fn run_command(conn: &Connection, args: &[String]) -> Option<Result<String, String>> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let result = match args.as_slice() {
+        ["grant-role", username, role] => role
+            .parse::<Role>()
+            .and_then(|role| {
+                let user_id = users::resolve(conn, username).map_err(|e| e.to_string())?;
+                set_role(conn, &user_id, role, None).map_err(|e| e.to_string())?;
+                Ok(format!("{} ({}) is now {}", username, user_id, role))
+            }),
+        ["revoke-role", username] => users::resolve(conn, username)
+            .and_then(|user_id| clear_role(conn, &user_id))
+            .map(|()| format!("{} is back to {}", username, Role::DEFAULT))
+            .map_err(|e| e.to_string()),
+        ["list-roles"] => list_roles(conn)
+            .map(|rows| {
+                rows.into_iter()
+                    .map(|(username, user_id, role)| format!("{}\t{}\t{}", username, user_id, role))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            })
+            .map_err(|e| e.to_string()),
+        [] => return None,
+        _ => Err(format!(
+            "Usage: cta-backend [grant-role <username> <{}> | revoke-role <username> | list-roles]",
+            Role::ALL.map(Role::as_str).join("|")
+        )),
+    };
+    Some(result)
+}
@@ -0,0 +1,182 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use actix_web::web;
+use rusqlite::{Connection, OptionalExtension, params};
+use shared::{Action, ApiError, DeletedNode, LiveEvent, UNDO_WINDOW_SECS};
+
+use crate::moderation::{DELETED, PUBLISHED};
+use crate::{AppState, get_env, node_from_row};
+
+/// How often deleted nodes past the retention period are looked for.
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How many deleted nodes admins are shown.
+const DELETED_LIMIT: u32 = 200;
+
+/// Adds `deleted_at`/`deleted_by` to databases created before nodes were kept on delete.
+pub fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM pragma_table_info('nodes') WHERE name = 'deleted_at')",
+        [],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        conn.execute_batch(
+            "BEGIN;
+            ALTER TABLE nodes ADD COLUMN deleted_at INTEGER;
+            ALTER TABLE nodes ADD COLUMN deleted_by TEXT;
+            COMMIT;",
+        )?;
+    }
+    Ok(())
+}
+
+/// Takes a node out of the story, keeping it until the purge job removes it, and leaves
+/// a tombstone so incremental syncs learn it's gone.
+pub fn delete_node_row(conn: &Connection, id: &str, deleted_by: Option<&str>) -> rusqlite::Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    mark_deleted(&tx, id, deleted_by)?;
+    tx.commit()
+}
+
+/// `delete_node_row` within a transaction the caller already has open. A node that's
+/// already deleted keeps who deleted it and when.
+pub fn mark_deleted(conn: &Connection, id: &str, deleted_by: Option<&str>) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE nodes SET status = ?1, deleted_at = unixepoch(), deleted_by = ?2 WHERE id = ?3 AND status != ?1",
+        params![DELETED, deleted_by, id],
+    )?;
+    conn.execute(
+        "INSERT OR REPLACE INTO node_tombstones (id, deleted_at) VALUES (?1, unixepoch())",
+        params![id],
+    )?;
+    Ok(())
+}
+
+/// Removes a node for good, with its reports, the links to and from it and its rules.
+pub fn forget_node(conn: &Connection, id: &str) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM reports WHERE node_id = ?1", params![id])?;
+    conn.execute("DELETE FROM links WHERE parent_id = ?1 OR target_id = ?1", params![id])?;
+    conn.execute("DELETE FROM node_mechanics WHERE node_id = ?1", params![id])?;
+    conn.execute("DELETE FROM random_branches WHERE node_id = ?1", params![id])?;
+    conn.execute("DELETE FROM outcome_values WHERE node_id = ?1", params![id])?;
+    conn.execute("DELETE FROM detail_changes WHERE node_id = ?1", params![id])?;
+    conn.execute("DELETE FROM nodes WHERE id = ?1", params![id])?;
+    Ok(())
+}
+
+fn internal(context: &str, e: rusqlite::Error) -> ApiError {
+    tracing::error!("{}: {}", context, e);
+    ApiError::Internal
+}
+
+impl AppState {
+    /// Puts a deleted node back where it was. Whoever deleted it can undo that for
+    /// `UNDO_WINDOW_SECS`; after that, or for anyone else, it takes an admin.
+    pub fn restore_node(&mut self, username: Option<&str>, node_id: &str) -> Result<(), ApiError> {
+        let username = username.ok_or(ApiError::Unauthenticated)?;
+        let row = self
+            .db
+            .query_row(
+                "SELECT id, parent_id, choice_text, story_text, created_by, deleted_by, unixepoch() - deleted_at
+                 FROM nodes WHERE id = ?1 AND status = ?2",
+                params![node_id, DELETED],
+                |row| Ok((node_from_row(row)?, row.get::<_, Option<String>>(5)?, row.get::<_, u64>(6)?)),
+            )
+            .optional()
+            .map_err(|e| internal("Failed to load deleted node", e))?;
+        let Some((node, deleted_by, age)) = row else {
+            return Err(ApiError::NotFound);
+        };
+
+        let undo = deleted_by.as_deref() == Some(username) && age <= UNDO_WINDOW_SECS;
+        if !undo {
+            self.authorize(Some(username), Action::Restore)?;
+        }
+        if let Some(parent_id) = &node.parent_id
+            && self.node(parent_id).is_err()
+        {
+            return Err(ApiError::BadRequest("The node it continued from is no longer in the story".into()));
+        }
+
+        let tx = self
+            .db
+            .unchecked_transaction()
+            .map_err(|e| internal("Failed to start transaction", e))?;
+        // Bumped so incremental syncs pick it up again
+        tx.execute(
+            "UPDATE nodes SET status = ?1, deleted_at = NULL, deleted_by = NULL, published_at = unixepoch()
+             WHERE id = ?2",
+            params![PUBLISHED, node_id],
+        )
+        .and_then(|_| tx.execute("DELETE FROM node_tombstones WHERE id = ?1", params![node_id]))
+        .and_then(|_| tx.commit())
+        .map_err(|e| internal("Failed to restore node", e))?;
+        tracing::info!("{} restored node {} (deleted by {:?})", username, node_id, deleted_by);
+
+        self.nodes.push(node.clone());
+        self.publish(LiveEvent::NodeAdded(node));
+        self.publish_counts(node_id);
+        Ok(())
+    }
+
+    /// Deleted nodes still waiting to be purged, newest first.
+    pub fn deleted_nodes(&self, admin: Option<&str>) -> Result<Vec<DeletedNode>, ApiError> {
+        self.authorize(admin, Action::Restore)?;
+        self.db
+            .prepare(
+                "SELECT id, parent_id, choice_text, story_text, created_by, deleted_at, deleted_by FROM nodes
+                 WHERE status = ?1 ORDER BY deleted_at DESC LIMIT ?2",
+            )
+            .and_then(|mut stmt| {
+                stmt.query_map(params![DELETED, DELETED_LIMIT], |row| {
+                    Ok(DeletedNode {
+                        node: node_from_row(row)?,
+                        deleted_at: row.get(5)?,
+                        deleted_by: row.get(6)?,
+                    })
+                })?
+                .collect()
+            })
+            .map_err(|e| internal("Failed to load deleted nodes", e))
+    }
+
+    /// Removes deleted nodes for good once they're older than `retention`, along with
+    /// everything kept about them. Their tombstones stay, for readers that haven't synced since.
+    fn purge_deleted(&self, retention: Duration) -> rusqlite::Result<usize> {
+        let tx = self.db.unchecked_transaction()?;
+        let expired: Vec<String> = tx
+            .prepare("SELECT id FROM nodes WHERE status = ?1 AND deleted_at <= unixepoch() - ?2")?
+            .query_map(params![DELETED, retention.as_secs()], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        for id in &expired {
+            forget_node(&tx, id)?;
+        }
+        tx.commit()?;
+        Ok(expired.len())
+    }
+}
+
+/// Purges deleted nodes older than `DELETED_RETENTION_DAYS` every hour, starting now.
+/// `0` keeps them forever.
+pub fn spawn_purge_job(state: web::Data<Mutex<AppState>>) {
+    let days: u64 = get_env("DELETED_RETENTION_DAYS", "30").parse().unwrap_or(30);
+    if days == 0 {
+        tracing::info!("Keeping deleted nodes forever");
+        return;
+    }
+    let retention = Duration::from_secs(days * 24 * 60 * 60);
+
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            match state.lock().unwrap().purge_deleted(retention) {
+                Ok(0) => {}
+                Ok(purged) => tracing::info!("Purged {} node(s) deleted over {} days ago", purged, days),
+                Err(e) => tracing::error!("Failed to purge deleted nodes: {}", e),
+            }
+        }
+    });
+}
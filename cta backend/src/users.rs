@@ -0,0 +1,297 @@
+use std::collections::HashMap;
+
+use rusqlite::{Connection, OptionalExtension, params};
+use shared::{
+    AdventureNode, ApiError, ChangeSet, DeletedNode, LiveEvent, NodeLink, PendingNode, PurgePlan, Report,
+    ReportedNode, ServerMessage, Submission, SubtreeDeletion, UserInfo,
+};
+
+use crate::AppState;
+use crate::audit::AuditEntry;
+use crate::auth::Identity;
+
+/// Columns naming a user. They hold stable ids (`ng:12345`), but rows written before there
+/// were ids hold the bare username until that user next logs in.
+const USER_COLUMNS: [(&str, &str); 15] = [
+    ("nodes", "created_by"),
+    ("nodes", "reviewed_by"),
+    ("nodes", "deleted_by"),
+    ("links", "created_by"),
+    ("user_roles", "user_id"),
+    ("user_roles", "granted_by"),
+    ("shadowbans", "user_id"),
+    ("shadowbans", "banned_by"),
+    ("reports", "reporter"),
+    ("reports", "resolved_by"),
+    ("node_mechanics", "updated_by"),
+    ("random_branches", "updated_by"),
+    ("outcome_values", "updated_by"),
+    ("audit_log", "actor"),
+    ("node_archive", "archived_by"),
+];
+
+/// Provider of the users named in rows from before ids. Only they may take those rows over,
+/// so nobody can claim a Newgrounds user's nodes or role by signing up under their name.
+const LEGACY_PROVIDER_PREFIX: &str = "ng:";
+
+/// Display name of each user id seen at login.
+pub type Names = HashMap<String, String>;
+
+pub fn init_users_table(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS users (
+            id TEXT PRIMARY KEY,
+            username TEXT NOT NULL,
+            first_seen INTEGER NOT NULL DEFAULT (unixepoch()),
+            last_seen INTEGER NOT NULL DEFAULT (unixepoch())
+        );
+        CREATE INDEX IF NOT EXISTS users_by_username ON users (username);",
+    )
+}
+
+pub fn load_names(conn: &Connection) -> rusqlite::Result<Names> {
+    let mut stmt = conn.prepare("SELECT id, username FROM users")?;
+    stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect()
+}
+
+/// The user an admin means by `name`: an id as it is, otherwise whoever logged in with that
+/// username most recently, otherwise `name` itself, which is how rows from before ids name them.
+pub fn resolve(conn: &Connection, name: &str) -> rusqlite::Result<String> {
+    if name.contains(':') {
+        return Ok(name.to_string());
+    }
+    let id = conn
+        .query_row(
+            "SELECT id FROM users WHERE username = ?1 COLLATE NOCASE ORDER BY last_seen DESC LIMIT 1",
+            params![name],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(id.unwrap_or_else(|| name.to_string()))
+}
+
+/// How `id` is shown: the username it last logged in with, or failing that the id without
+/// its provider prefix.
+pub fn display_name<'a>(names: &'a Names, id: &'a str) -> &'a str {
+    match names.get(id) {
+        Some(name) => name,
+        None => id.split_once(':').map_or(id, |(_, rest)| rest),
+    }
+}
+
+fn show(names: &Names, id: &mut String) {
+    let name = display_name(names, id);
+    if name != id {
+        *id = name.to_string();
+    }
+}
+
+/// Something sent to clients that names users, whose ids are swapped for display names
+/// on the way out.
+pub trait Present {
+    fn present(&mut self, names: &Names);
+}
+
+impl<T: Present> Present for Vec<T> {
+    fn present(&mut self, names: &Names) {
+        self.iter_mut().for_each(|item| item.present(names));
+    }
+}
+
+impl<T: Present> Present for Option<T> {
+    fn present(&mut self, names: &Names) {
+        if let Some(item) = self {
+            item.present(names);
+        }
+    }
+}
+
+impl Present for String {
+    fn present(&mut self, names: &Names) {
+        show(names, self);
+    }
+}
+
+impl Present for AdventureNode {
+    fn present(&mut self, names: &Names) {
+        self.created_by.present(names);
+    }
+}
+
+impl Present for NodeLink {
+    fn present(&mut self, names: &Names) {
+        self.created_by.present(names);
+    }
+}
+
+impl Present for ChangeSet {
+    fn present(&mut self, names: &Names) {
+        self.added.present(names);
+    }
+}
+
+impl Present for UserInfo {
+    fn present(&mut self, names: &Names) {
+        self.username.present(names);
+    }
+}
+
+impl Present for PendingNode {
+    fn present(&mut self, names: &Names) {
+        self.node.present(names);
+    }
+}
+
+impl Present for Submission {
+    fn present(&mut self, names: &Names) {
+        self.node.present(names);
+    }
+}
+
+impl Present for DeletedNode {
+    fn present(&mut self, names: &Names) {
+        self.node.present(names);
+        self.deleted_by.present(names);
+    }
+}
+
+impl Present for ReportedNode {
+    fn present(&mut self, names: &Names) {
+        self.node.present(names);
+        self.path.present(names);
+        self.reports.present(names);
+    }
+}
+
+impl Present for Report {
+    fn present(&mut self, names: &Names) {
+        self.reporter.present(names);
+    }
+}
+
+impl Present for PurgePlan {
+    fn present(&mut self, names: &Names) {
+        for subtree in &mut self.subtrees {
+            subtree.root.present(names);
+            subtree.other_authors.present(names);
+        }
+    }
+}
+
+impl Present for SubtreeDeletion {
+    fn present(&mut self, names: &Names) {
+        self.root.present(names);
+        for (author, _) in &mut self.authors {
+            author.present(names);
+        }
+    }
+}
+
+impl Present for AuditEntry {
+    fn present(&mut self, names: &Names) {
+        self.actor.present(names);
+    }
+}
+
+impl Present for LiveEvent {
+    fn present(&mut self, names: &Names) {
+        match self {
+            LiveEvent::NodeAdded(node) => node.present(names),
+            LiveEvent::LinkAdded(link) => link.present(names),
+            _ => {}
+        }
+    }
+}
+
+impl Present for ServerMessage {
+    fn present(&mut self, names: &Names) {
+        match self {
+            ServerMessage::ReturnAdventureNodes(nodes) | ServerMessage::ReturnSubtree { nodes, .. } => {
+                nodes.present(names)
+            }
+            ServerMessage::NodeCreated { node } | ServerMessage::NodeQueued { node } => node.present(names),
+            ServerMessage::ReturnChanges(changes) => changes.present(names),
+            ServerMessage::ReturnIdentity(user) => user.present(names),
+            ServerMessage::ReturnModerationQueue(queue) => queue.present(names),
+            ServerMessage::ReturnMySubmissions(submissions) => submissions.present(names),
+            ServerMessage::ReturnReports(reports) => reports.present(names),
+            ServerMessage::ReturnPurgePlan(plan) => plan.present(names),
+            ServerMessage::ReturnDeletedNodes(deleted) => deleted.present(names),
+            ServerMessage::ReturnSubtreeDeletion(deletion) => deletion.present(names),
+            ServerMessage::LinkCreated(link) => link.present(names),
+            ServerMessage::ReturnLinks(links) => links.present(names),
+            ServerMessage::ReturnNodeDetails(details) => details.links.present(names),
+            _ => {}
+        }
+    }
+}
+
+/// Upserts `identity` into `users`, adopting its username's rows if `first_login`.
+/// Returns whether any rows were adopted.
+fn record_login(conn: &Connection, identity: &Identity, first_login: bool) -> rusqlite::Result<bool> {
+    let tx = conn.unchecked_transaction()?;
+    let claimed: bool = tx.query_row(
+        "SELECT EXISTS (SELECT 1 FROM users WHERE username = ?1 AND id != ?2)",
+        params![identity.username, identity.id],
+        |row| row.get(0),
+    )?;
+    tx.execute(
+        "INSERT INTO users (id, username) VALUES (?1, ?2)
+         ON CONFLICT (id) DO UPDATE SET username = excluded.username, last_seen = unixepoch()",
+        params![identity.id, identity.username],
+    )?;
+
+    let mut adopted = 0;
+    if first_login && !claimed && identity.id.starts_with(LEGACY_PROVIDER_PREFIX) {
+        for (table, column) in USER_COLUMNS {
+            adopted += tx.execute(
+                &format!("UPDATE OR IGNORE {table} SET {column} = ?1 WHERE {column} = ?2"),
+                params![identity.id, identity.username],
+            )?;
+        }
+    }
+    tx.commit()?;
+
+    if adopted > 0 {
+        tracing::info!("Moved {} rows naming {} over to {}", adopted, identity.username, identity.id);
+    }
+    Ok(adopted > 0)
+}
+
+impl AppState {
+    /// The stable id of the user an admin named, for storing.
+    pub fn resolve_user(&self, name: &str) -> Result<String, ApiError> {
+        resolve(&self.db, name.trim()).map_err(|e| {
+            tracing::error!("Failed to look up user {}: {}", name, e);
+            ApiError::Internal
+        })
+    }
+
+    pub fn present<T: Present>(&self, mut value: T) -> T {
+        value.present(&self.names);
+        value
+    }
+
+    /// Records the name `identity` logged in with. The first time a Newgrounds id is seen,
+    /// rows from before ids that name its username are handed over to it, unless another id
+    /// already claimed that username.
+    pub fn remember_user(&mut self, identity: &Identity) -> Result<(), ApiError> {
+        let first_login = !self.names.contains_key(&identity.id);
+        let adopted = record_login(&self.db, identity, first_login).map_err(|e| {
+            tracing::error!("Failed to remember {}: {}", identity.id, e);
+            ApiError::Internal
+        })?;
+
+        if adopted {
+            // Adopted nodes are in memory under the old name
+            for node in &mut self.nodes {
+                if node.created_by.as_deref() == Some(identity.username.as_str()) {
+                    node.created_by = Some(identity.id.clone());
+                }
+            }
+        }
+        self.names.insert(identity.id.clone(), identity.username.clone());
+        Ok(())
+    }
+}
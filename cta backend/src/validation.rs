@@ -0,0 +1,160 @@
+use shared::mechanics::{self, NodeMechanics};
+use shared::{AdventureNode, ApiError, NodeLink};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::get_env;
+
+/// Limits applied to submitted nodes, read from the environment at startup.
+pub struct ValidationConfig {
+    pub choice_text_min: usize,
+    pub choice_text_max: usize,
+    pub story_text_min: usize,
+    pub story_text_max: usize,
+    /// Maximum number of options that may branch from a single node.
+    pub max_siblings: usize,
+}
+
+fn env_usize(key: &str, default: usize) -> usize {
+    get_env(key, &default.to_string()).parse().unwrap_or(default)
+}
+
+impl ValidationConfig {
+    pub fn from_env() -> Self {
+        Self {
+            choice_text_min: env_usize("CHOICE_TEXT_MIN_CHARS", 1),
+            choice_text_max: env_usize("CHOICE_TEXT_MAX_CHARS", 200),
+            story_text_min: env_usize("STORY_TEXT_MIN_CHARS", 1),
+            story_text_max: env_usize("STORY_TEXT_MAX_CHARS", 20_000),
+            max_siblings: env_usize("MAX_SIBLINGS", 20),
+        }
+    }
+}
+
+fn invalid(field: &str, reason: impl Into<String>) -> ApiError {
+    ApiError::Validation {
+        field: field.into(),
+        reason: reason.into(),
+    }
+}
+
+/// NFC-normalizes and trims `text`, dropping control characters except those in `keep`.
+fn clean_text(text: &str, keep: &[char]) -> String {
+    text.replace("\r\n", "\n")
+        .nfc()
+        .filter(|c| !c.is_control() || keep.contains(c))
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+fn check_length(field: &str, label: &str, text: &str, min: usize, max: usize) -> Result<(), ApiError> {
+    let len = text.chars().count();
+    if len < min {
+        if min == 1 {
+            return Err(invalid(field, format!("{} can't be empty", label)));
+        }
+        return Err(invalid(field, format!("{} must be at least {} characters", label, min)));
+    }
+    if len > max {
+        return Err(invalid(field, format!("{} must be at most {} characters (got {})", label, max, len)));
+    }
+    Ok(())
+}
+
+/// Cleans up a submitted node's text and checks it against `config` and the current graph.
+pub fn validate_node(
+    mut node: AdventureNode,
+    nodes: &[AdventureNode],
+    links: &[NodeLink],
+    config: &ValidationConfig,
+) -> Result<AdventureNode, ApiError> {
+    node.choice_text = clean_text(&node.choice_text, &[]);
+    node.story_text = clean_text(&node.story_text, &['\n', '\t']);
+
+    check_length(
+        "choice_text",
+        "Choice text",
+        &node.choice_text,
+        config.choice_text_min,
+        config.choice_text_max,
+    )?;
+    check_length(
+        "story_text",
+        "Story text",
+        &node.story_text,
+        config.story_text_min,
+        config.story_text_max,
+    )?;
+
+    if let Some(parent_id) = &node.parent_id {
+        if !nodes.iter().any(|n| &n.id == parent_id) {
+            return Err(invalid("parent_id", "The part of the story you're continuing no longer exists"));
+        }
+        check_siblings(parent_id, nodes, links, config)?;
+    }
+
+    Ok(node)
+}
+
+/// Checks `parent_id` has room for another option, counting both the nodes below it and
+/// the links offered after it.
+pub fn check_siblings(
+    parent_id: &str,
+    nodes: &[AdventureNode],
+    links: &[NodeLink],
+    config: &ValidationConfig,
+) -> Result<(), ApiError> {
+    if option_count(parent_id, nodes, links) >= config.max_siblings {
+        return Err(invalid(
+            "parent_id",
+            format!("This point already has the maximum of {} options", config.max_siblings),
+        ));
+    }
+    Ok(())
+}
+
+/// Cleans up a link's choice text and checks that both ends are in the story and the
+/// node it's offered after has room for another option.
+pub fn validate_link(
+    mut link: NodeLink,
+    nodes: &[AdventureNode],
+    links: &[NodeLink],
+    config: &ValidationConfig,
+) -> Result<NodeLink, ApiError> {
+    link.choice_text = clean_text(&link.choice_text, &[]);
+    check_length(
+        "choice_text",
+        "Choice text",
+        &link.choice_text,
+        config.choice_text_min,
+        config.choice_text_max,
+    )?;
+
+    if !nodes.iter().any(|n| n.id == link.parent_id) {
+        return Err(invalid("parent_id", "The part of the story you're continuing no longer exists"));
+    }
+    if !nodes.iter().any(|n| n.id == link.target_id) {
+        return Err(invalid("target_id", "The part of the story you're linking to no longer exists"));
+    }
+    if links.iter().any(|l| l.parent_id == link.parent_id && l.target_id == link.target_id) {
+        return Err(invalid("target_id", "There's already a choice here leading there"));
+    }
+
+    check_siblings(&link.parent_id, nodes, links, config)?;
+    Ok(link)
+}
+
+/// How many choices readers get at `parent_id`: the nodes below it plus the links after it.
+fn option_count(parent_id: &str, nodes: &[AdventureNode], links: &[NodeLink]) -> usize {
+    nodes.iter().filter(|n| n.parent_id.as_deref() == Some(parent_id)).count()
+        + links.iter().filter(|l| l.parent_id == parent_id).count()
+}
+
+/// Trims a node's condition and effects and checks that both parse.
+pub fn validate_mechanics(mut m: NodeMechanics) -> Result<NodeMechanics, ApiError> {
+    m.condition = clean_text(&m.condition, &[]);
+    m.effects = clean_text(&m.effects, &['\n']);
+    mechanics::parse_condition(&m.condition).map_err(|e| invalid("condition", e.to_string()))?;
+    mechanics::parse_effects(&m.effects).map_err(|e| invalid("effects", e.to_string()))?;
+    Ok(m)
+}
@@ -70,6 +70,8 @@ Restart=always
 Environment=RUST_LOG=info
 Environment=HOST=127.0.0.1
 Environment=PORT=8080
+# Caddy sets X-Forwarded-For to the real client, which rate limits key on
+Environment=TRUST_PROXY_HEADERS=true
 
 [Install]
 WantedBy=multi-user.target
@@ -1,36 +1,225 @@
 use std::collections::HashMap;
+use std::fmt;
 
-use crate::domain::adventure::AdventureGraph;
-use shared::{AdventureNode, ServerMessage};
+use shared::chance::Roll;
+use shared::mechanics::NodeMechanics;
+use shared::{
+    AdventureNode, ApiError, Capabilities, ChangeSet, Ending, NodeDetails, NodeEnding, NodeLink, NodeRules,
+    ServerMessage,
+};
 
-pub async fn fetch_adventure() -> Result<AdventureGraph, String> {
-    match super::api_fetch(ServerMessage::RequestAdventureNodes).await? {
-        ServerMessage::ReturnAdventureNodes(nodes) => Ok(AdventureGraph::from_nodes(nodes)),
-        ServerMessage::Error(e) => Err(e),
-        other => Err(format!("Unexpected response: {:?}", other)),
+use super::RequestError;
+
+/// A failed adventure request, classified by how the UI should react to it.
+#[derive(Clone, Debug)]
+pub enum AdventureError {
+    /// The server couldn't be reached or sent something we can't read.
+    Unreachable(String),
+    /// The user has to sign in before trying again.
+    SignInRequired,
+    /// The server refused the request; show why and keep the story on screen.
+    Rejected(String),
+    /// Our copy of the graph is out of date with the server; refetch it.
+    Stale(String),
+    /// A field of the submitted node was rejected.
+    InvalidField { field: String, reason: String },
+    /// Too many requests; the user may retry after this many seconds.
+    SlowDown { retry_after: u64 },
+    /// The server broke while handling the request.
+    ServerFault,
+    /// This build is too old for the server; the page has to be reloaded.
+    Outdated,
+}
+
+impl fmt::Display for AdventureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unreachable(e) => write!(f, "Couldn't reach the server: {}", e),
+            Self::SignInRequired => write!(f, "You need to sign in to do that"),
+            Self::Rejected(reason) => write!(f, "{}", reason),
+            Self::Stale(reason) => write!(f, "{}", reason),
+            Self::InvalidField { reason, .. } => write!(f, "{}", reason),
+            Self::SlowDown { retry_after } => {
+                write!(f, "You're going too fast. Try again in {}s", retry_after)
+            }
+            Self::ServerFault => write!(f, "The server ran into a problem. Try again later"),
+            Self::Outdated => write!(f, "A newer version of the game is available. Reload the page to continue"),
+        }
+    }
+}
+
+impl From<RequestError> for AdventureError {
+    fn from(e: RequestError) -> Self {
+        match e {
+            RequestError::Transport(e) => Self::Unreachable(e),
+            RequestError::Outdated => Self::Outdated,
+            RequestError::Api(ApiError::Unauthenticated) => Self::SignInRequired,
+            RequestError::Api(ApiError::Forbidden) => Self::Rejected("You aren't allowed to do that".into()),
+            RequestError::Api(ApiError::NotFound) => {
+                Self::Stale("That part of the story no longer exists".into())
+            }
+            RequestError::Api(ApiError::HasChildren) => {
+                Self::Stale("Someone has already continued from here, so it can't be deleted".into())
+            }
+            RequestError::Api(ApiError::Validation { field, reason }) => {
+                Self::InvalidField { field, reason }
+            }
+            RequestError::Api(ApiError::RateLimited { retry_after }) => Self::SlowDown { retry_after },
+            RequestError::Api(ApiError::BadRequest(reason)) => Self::Rejected(reason),
+            RequestError::Api(ApiError::Internal) => Self::ServerFault,
+        }
+    }
+}
+
+pub(super) fn unexpected(other: ServerMessage) -> AdventureError {
+    AdventureError::Unreachable(format!("Unexpected response: {:?}", other))
+}
+
+/// `root_id` (or every root) and `depth` levels below it, plus a sync cursor.
+pub async fn fetch_subtree(root_id: Option<String>, depth: u32) -> Result<(Vec<AdventureNode>, u64), AdventureError> {
+    match super::api_fetch(ServerMessage::RequestSubtree { root_id, depth }).await? {
+        ServerMessage::ReturnSubtree { nodes, cursor } => Ok((nodes, cursor)),
+        other => Err(unexpected(other)),
+    }
+}
+
+/// The nodes leading to `node_id`, root first.
+pub async fn fetch_ancestors(node_id: String) -> Result<Vec<AdventureNode>, AdventureError> {
+    match super::api_fetch(ServerMessage::RequestAncestors { node_id }).await? {
+        ServerMessage::ReturnAdventureNodes(nodes) => Ok(nodes),
+        other => Err(unexpected(other)),
     }
 }
 
-pub async fn fetch_descendant_counts() -> Result<HashMap<String, u64>, String> {
+/// Nodes added and removed since `cursor`; 0 fetches everything.
+pub async fn fetch_changes(cursor: u64) -> Result<ChangeSet, AdventureError> {
+    match super::api_fetch(ServerMessage::RequestChangesSince { cursor }).await? {
+        ServerMessage::ReturnChanges(changes) => Ok(changes),
+        other => Err(unexpected(other)),
+    }
+}
+
+/// The links and rules of `node_ids`.
+pub async fn fetch_node_details(node_ids: Vec<String>) -> Result<NodeDetails, AdventureError> {
+    match super::api_fetch(ServerMessage::RequestNodeDetails { node_ids }).await? {
+        ServerMessage::ReturnNodeDetails(details) => Ok(details),
+        other => Err(unexpected(other)),
+    }
+}
+
+/// The links and rules of nodes added, or whose links or rules changed, since `cursor`.
+pub async fn fetch_detail_changes(cursor: u64) -> Result<NodeDetails, AdventureError> {
+    match super::api_fetch(ServerMessage::RequestDetailChangesSince { cursor }).await? {
+        ServerMessage::ReturnNodeDetails(details) => Ok(details),
+        other => Err(unexpected(other)),
+    }
+}
+
+/// Every ending of the story starting at `root_id`.
+pub async fn fetch_story_endings(root_id: String) -> Result<Vec<NodeEnding>, AdventureError> {
+    match super::api_fetch(ServerMessage::RequestStoryEndings { root_id }).await? {
+        ServerMessage::ReturnEndings(endings) => Ok(endings),
+        other => Err(unexpected(other)),
+    }
+}
+
+pub async fn fetch_descendant_counts() -> Result<HashMap<String, u64>, AdventureError> {
     match super::api_fetch(ServerMessage::RequestDescendantCounts).await? {
         ServerMessage::ReturnDescendantCounts(counts) => Ok(counts),
-        ServerMessage::Error(e) => Err(e),
-        other => Err(format!("Unexpected response: {:?}", other)),
+        other => Err(unexpected(other)),
     }
 }
 
-pub async fn submit_node(node: AdventureNode, session_id: Option<String>) -> Result<(), String> {
-    match super::api_fetch(ServerMessage::SubmitAdventureNode { node, session_id }).await? {
-        ServerMessage::Ok => Ok(()),
-        ServerMessage::Error(e) => Err(e),
-        other => Err(format!("Unexpected response: {:?}", other)),
+/// A submitted node with its server-assigned ID.
+pub enum Submitted {
+    Published(AdventureNode),
+    /// Stored, but hidden from other readers until a moderator approves it.
+    Queued(AdventureNode),
+}
+
+/// Submits a new node (with an empty `id`) along with its rules, which are saved with it or not at all.
+pub async fn submit_node(node: AdventureNode, rules: NodeRules, session_id: Option<String>) -> Result<Submitted, AdventureError> {
+    match super::api_fetch(ServerMessage::SubmitNodeWithRules { node, rules, session_id }).await? {
+        ServerMessage::NodeCreated { node } => Ok(Submitted::Published(node)),
+        ServerMessage::NodeQueued { node } => Ok(Submitted::Queued(node)),
+        other => Err(unexpected(other)),
     }
 }
 
-pub async fn delete_node(node_id: String, session_id: Option<String>) -> Result<(), String> {
+pub async fn delete_node(node_id: String, session_id: Option<String>) -> Result<(), AdventureError> {
     match super::api_fetch(ServerMessage::DeleteAdventureNode { node_id, session_id }).await? {
         ServerMessage::Ok => Ok(()),
-        ServerMessage::Error(e) => Err(e),
-        other => Err(format!("Unexpected response: {:?}", other)),
+        other => Err(unexpected(other)),
+    }
+}
+
+/// Puts back a node that was deleted. Whoever deleted it may shortly afterwards; admins any time.
+pub async fn restore_node(node_id: String, session_id: Option<String>) -> Result<(), AdventureError> {
+    match super::api_fetch(ServerMessage::RestoreNode { node_id, session_id }).await? {
+        ServerMessage::Ok => Ok(()),
+        other => Err(unexpected(other)),
+    }
+}
+
+/// What the holder of `session_id` may do to each of `node_ids`.
+pub async fn fetch_capabilities(node_ids: Vec<String>, session_id: Option<String>) -> Result<Capabilities, AdventureError> {
+    match super::api_fetch(ServerMessage::RequestCapabilities { node_ids, session_id }).await? {
+        ServerMessage::ReturnCapabilities(capabilities) => Ok(capabilities),
+        other => Err(unexpected(other)),
+    }
+}
+
+/// The signed-in user's nodes that only they can see.
+pub async fn fetch_own_unlisted_nodes(session_id: Option<String>) -> Result<Vec<AdventureNode>, AdventureError> {
+    match super::api_fetch(ServerMessage::RequestOwnUnlistedNodes { session_id }).await? {
+        ServerMessage::ReturnAdventureNodes(nodes) => Ok(nodes),
+        other => Err(unexpected(other)),
+    }
+}
+
+/// Adds a choice leading to an existing node (with an empty `id`).
+pub async fn create_link(link: NodeLink, session_id: Option<String>) -> Result<NodeLink, AdventureError> {
+    match super::api_fetch(ServerMessage::CreateLink { link, session_id }).await? {
+        ServerMessage::LinkCreated(link) => Ok(link),
+        other => Err(unexpected(other)),
+    }
+}
+
+pub async fn delete_link(link_id: String, session_id: Option<String>) -> Result<(), AdventureError> {
+    match super::api_fetch(ServerMessage::DeleteLink { link_id, session_id }).await? {
+        ServerMessage::Ok => Ok(()),
+        other => Err(unexpected(other)),
+    }
+}
+
+/// Sets what a node requires and changes; both empty clears them.
+pub async fn set_mechanics(mechanics: NodeMechanics, session_id: Option<String>) -> Result<(), AdventureError> {
+    match super::api_fetch(ServerMessage::SetNodeMechanics { mechanics, session_id }).await? {
+        ServerMessage::Ok => Ok(()),
+        other => Err(unexpected(other)),
+    }
+}
+
+/// Makes a node a random branch, or an ordinary one again with `None`.
+pub async fn set_random_branch(node_id: String, roll: Option<Roll>, session_id: Option<String>) -> Result<(), AdventureError> {
+    match super::api_fetch(ServerMessage::SetRandomBranch { node_id, roll, session_id }).await? {
+        ServerMessage::Ok => Ok(()),
+        other => Err(unexpected(other)),
+    }
+}
+
+/// Sets the weight or threshold of an outcome of a random branch.
+pub async fn set_outcome_value(node_id: String, value: Option<u32>, session_id: Option<String>) -> Result<(), AdventureError> {
+    match super::api_fetch(ServerMessage::SetOutcomeValue { node_id, value, session_id }).await? {
+        ServerMessage::Ok => Ok(()),
+        other => Err(unexpected(other)),
+    }
+}
+
+/// Makes a node an ending, or an ordinary one again with `None`.
+pub async fn set_ending(node_id: String, ending: Option<Ending>, session_id: Option<String>) -> Result<(), AdventureError> {
+    match super::api_fetch(ServerMessage::SetEnding { node_id, ending, session_id }).await? {
+        ServerMessage::Ok => Ok(()),
+        other => Err(unexpected(other)),
     }
 }
@@ -0,0 +1,35 @@
+use shared::LiveEvent;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::config::API_BASE;
+
+/// Opens the server-sent event stream and calls `on_event` for every change.
+/// The browser reconnects on its own if the connection drops, or if the server closes
+/// it because we fell behind; `on_open` runs on every (re)connect.
+pub fn subscribe_live_events(
+    on_event: impl Fn(LiveEvent) + 'static,
+    on_open: impl Fn() + 'static,
+) -> Result<(), String> {
+    let url = format!("{}/api/events", API_BASE);
+    let source = web_sys::EventSource::new(&url)
+        .map_err(|e| format!("EventSource error: {:?}", e))?;
+
+    let on_message = Closure::<dyn FnMut(web_sys::MessageEvent)>::new(move |ev: web_sys::MessageEvent| {
+        let Some(data) = ev.data().as_string() else {
+            return;
+        };
+        match serde_json::from_str::<LiveEvent>(&data) {
+            Ok(event) => on_event(event),
+            Err(e) => log::warn!("Ignoring malformed live event: {}", e),
+        }
+    });
+    source.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+    on_message.forget();
+
+    let on_open = Closure::<dyn FnMut()>::new(on_open);
+    source.set_onopen(Some(on_open.as_ref().unchecked_ref()));
+    on_open.forget();
+
+    Ok(())
+}
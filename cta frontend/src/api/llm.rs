@@ -15,12 +15,14 @@ struct LlmRequest {
 }
 
 #[wasm_bindgen(inline_js = "
-export function stream_llm_fetch(url, body_json, on_chunk) {
+export function stream_llm_fetch(url, body_json, token, on_chunk) {
     return new Promise(async (resolve, reject) => {
         try {
+            const headers = { 'Content-Type': 'application/json' };
+            if (token) headers['Authorization'] = `Bearer ${token}`;
             const resp = await fetch(url, {
                 method: 'POST',
-                headers: { 'Content-Type': 'application/json' },
+                headers,
                 body: body_json,
             });
             if (!resp.ok) {
@@ -29,6 +31,10 @@ export function stream_llm_fetch(url, body_json, on_chunk) {
                     const body = await resp.json();
                     if (body.error) msg = body.error;
                 } catch {}
+                const retry_after = resp.headers.get('Retry-After');
+                if (resp.status === 429 && retry_after) {
+                    msg = `You're generating too fast. Try again in ${retry_after}s`;
+                }
                 reject(msg);
                 return;
             }
@@ -64,6 +70,7 @@ extern "C" {
     fn stream_llm_fetch(
         url: &str,
         body_json: &str,
+        token: Option<String>,
         on_chunk: &Closure<dyn FnMut(String)>,
     ) -> js_sys::Promise;
 }
@@ -73,6 +80,7 @@ pub async fn call_llm_stream(
     api_key: &str,
     model: &str,
     prompt: &str,
+    session_id: Option<String>,
     on_chunk: impl Fn(&str) + 'static,
 ) -> Result<(), String> {
     let body = LlmRequest {
@@ -93,7 +101,7 @@ pub async fn call_llm_stream(
         on_chunk(&chunk);
     }) as Box<dyn FnMut(String)>);
 
-    let promise = stream_llm_fetch(&url, &body_json, &closure);
+    let promise = stream_llm_fetch(&url, &body_json, session_id, &closure);
     closure.forget();
 
     wasm_bindgen_futures::JsFuture::from(promise)
@@ -1,17 +1,54 @@
 pub mod adventure;
 pub mod counter;
+pub mod live;
 pub mod llm;
+pub mod moderation;
 pub mod newgrounds;
+pub mod session;
+
+use std::fmt;
 
 use js_sys::Uint8Array;
-use shared::ServerMessage;
+use shared::{ApiError, EnvelopeHeader, RequestEnvelope, ServerMessage};
 use wasm_bindgen::JsCast;
 use wasm_bindgen_futures::JsFuture;
 
-use crate::config::API_BASE;
+use crate::config::{API_BASE, BUILD_ID};
+
+/// Failure of a call to the bincode `/api` endpoint.
+#[derive(Clone, Debug)]
+pub enum RequestError {
+    /// No usable response: network failure, bad status without a body, or undecodable bytes.
+    Transport(String),
+    /// The server answered with a structured error.
+    Api(ApiError),
+    /// The server no longer understands this build's protocol; the page must be reloaded.
+    Outdated,
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Transport(e) => write!(f, "{}", e),
+            Self::Api(e) => write!(f, "{}", e),
+            Self::Outdated => write!(f, "Client outdated, please refresh the page"),
+        }
+    }
+}
+
+impl From<String> for RequestError {
+    fn from(e: String) -> Self {
+        Self::Transport(e)
+    }
+}
 
-pub async fn api_fetch(msg: ServerMessage) -> Result<ServerMessage, String> {
-    let bytes = bincode::serialize(&msg)
+/// Sends a message to `/api`. A `ServerMessage::Error` reply is returned as `RequestError::Api`.
+pub async fn api_fetch(msg: ServerMessage) -> Result<ServerMessage, RequestError> {
+    let envelope = RequestEnvelope {
+        header: EnvelopeHeader::current(BUILD_ID),
+        message: msg,
+    };
+    let bytes = bincode::serialize(&envelope)
         .map_err(|e| format!("Serialize error: {}", e))?;
 
     let body = Uint8Array::from(bytes.as_slice());
@@ -31,7 +68,7 @@ pub async fn api_fetch(msg: ServerMessage) -> Result<ServerMessage, String> {
     let request = web_sys::Request::new_with_str_and_init(&url, &opts)
         .map_err(|e| format!("Request error: {:?}", e))?;
 
-    let window = web_sys::window().ok_or("No window object")?;
+    let window = web_sys::window().ok_or_else(|| "No window object".to_string())?;
     let resp_value = JsFuture::from(window.fetch_with_request(&request))
         .await
         .map_err(|e| format!("Fetch error: {:?}", e))?;
@@ -40,8 +77,10 @@ pub async fn api_fetch(msg: ServerMessage) -> Result<ServerMessage, String> {
         .dyn_into()
         .map_err(|_| "Response cast failed".to_string())?;
 
-    if !resp.ok() {
-        return Err(format!("API error: status {}", resp.status()));
+    let status = resp.status();
+    // 426 Upgrade Required: our protocol version is no longer accepted
+    if status == 426 {
+        return Err(RequestError::Outdated);
     }
 
     let buffer = JsFuture::from(
@@ -54,6 +93,11 @@ pub async fn api_fetch(msg: ServerMessage) -> Result<ServerMessage, String> {
     let uint8 = Uint8Array::new(&buffer);
     let response_bytes = uint8.to_vec();
 
-    bincode::deserialize(&response_bytes)
-        .map_err(|e| format!("Deserialize error: {}", e))
+    // Error statuses still carry a bincode `ServerMessage::Error` body when they come from us
+    match bincode::deserialize(&response_bytes) {
+        Ok(ServerMessage::Error(e)) => Err(RequestError::Api(e)),
+        Ok(msg) if resp.ok() => Ok(msg),
+        Err(e) if resp.ok() => Err(format!("Deserialize error: {}", e).into()),
+        _ => Err(format!("API error: status {}", status).into()),
+    }
 }
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+
+use shared::{
+    DeletedNode, PendingNode, PurgeMode, PurgePlan, ReportReason, ReportResolution, ReportedNode, ReviewDecision, ServerMessage,
+    Submission, SubtreeDeletion,
+};
+
+use super::adventure::{unexpected, AdventureError};
+
+/// Submissions waiting for review, oldest first. Only moderators may ask.
+pub async fn fetch_moderation_queue(session_id: Option<String>) -> Result<Vec<PendingNode>, AdventureError> {
+    match super::api_fetch(ServerMessage::RequestModerationQueue { session_id }).await? {
+        ServerMessage::ReturnModerationQueue(queue) => Ok(queue),
+        other => Err(unexpected(other)),
+    }
+}
+
+/// Why content filters held queued nodes, by node ID. Only moderators may ask.
+pub async fn fetch_flag_reasons(session_id: Option<String>) -> Result<HashMap<String, String>, AdventureError> {
+    match super::api_fetch(ServerMessage::RequestFlagReasons { session_id }).await? {
+        ServerMessage::ReturnFlagReasons(reasons) => Ok(reasons),
+        other => Err(unexpected(other)),
+    }
+}
+
+/// Deleted nodes that haven't been purged yet, newest first. Only admins may ask.
+pub async fn fetch_deleted_nodes(session_id: Option<String>) -> Result<Vec<DeletedNode>, AdventureError> {
+    match super::api_fetch(ServerMessage::RequestDeletedNodes { session_id }).await? {
+        ServerMessage::ReturnDeletedNodes(nodes) => Ok(nodes),
+        other => Err(unexpected(other)),
+    }
+}
+
+/// IDs that were reviewed, and the ones that couldn't be with why.
+pub type ReviewOutcome = (Vec<String>, Vec<(String, AdventureError)>);
+
+/// Applies `decision` to every node in `node_ids`.
+pub async fn review_nodes(
+    node_ids: Vec<String>,
+    decision: ReviewDecision,
+    session_id: Option<String>,
+) -> Result<ReviewOutcome, AdventureError> {
+    match super::api_fetch(ServerMessage::ReviewNodes { node_ids, decision, session_id }).await? {
+        ServerMessage::Reviewed { done, failed } => Ok((
+            done,
+            failed
+                .into_iter()
+                .map(|(id, e)| (id, super::RequestError::Api(e).into()))
+                .collect(),
+        )),
+        other => Err(unexpected(other)),
+    }
+}
+
+/// The signed-in user's submissions that are pending or were rejected, newest first.
+pub async fn fetch_my_submissions(session_id: Option<String>) -> Result<Vec<Submission>, AdventureError> {
+    match super::api_fetch(ServerMessage::RequestMySubmissions { session_id }).await? {
+        ServerMessage::ReturnMySubmissions(submissions) => Ok(submissions),
+        other => Err(unexpected(other)),
+    }
+}
+
+pub async fn report_node(
+    node_id: String,
+    reason: ReportReason,
+    details: String,
+    session_id: Option<String>,
+) -> Result<(), AdventureError> {
+    match super::api_fetch(ServerMessage::ReportNode { node_id, reason, details, session_id }).await? {
+        ServerMessage::Ok => Ok(()),
+        other => Err(unexpected(other)),
+    }
+}
+
+/// Nodes with open reports, most reported first. Only moderators may ask.
+pub async fn fetch_reports(session_id: Option<String>) -> Result<Vec<ReportedNode>, AdventureError> {
+    match super::api_fetch(ServerMessage::RequestReports { session_id }).await? {
+        ServerMessage::ReturnReports(reports) => Ok(reports),
+        other => Err(unexpected(other)),
+    }
+}
+
+pub async fn resolve_reports(
+    node_id: String,
+    resolution: ReportResolution,
+    session_id: Option<String>,
+) -> Result<(), AdventureError> {
+    match super::api_fetch(ServerMessage::ResolveReports { node_id, resolution, session_id }).await? {
+        ServerMessage::Ok => Ok(()),
+        other => Err(unexpected(other)),
+    }
+}
+
+/// Bans `username`, or with `shadow` lets them keep writing where only they see it. Admins only.
+pub async fn ban_user(username: String, shadow: bool, session_id: Option<String>) -> Result<(), AdventureError> {
+    match super::api_fetch(ServerMessage::BanUser { username, shadow, session_id }).await? {
+        ServerMessage::Ok => Ok(()),
+        other => Err(unexpected(other)),
+    }
+}
+
+pub async fn unban_user(username: String, session_id: Option<String>) -> Result<(), AdventureError> {
+    match super::api_fetch(ServerMessage::UnbanUser { username, session_id }).await? {
+        ServerMessage::Ok => Ok(()),
+        other => Err(unexpected(other)),
+    }
+}
+
+/// Deletes everything `username` wrote, or with `dry_run` only says what would go.
+pub async fn purge_user(
+    username: String,
+    mode: PurgeMode,
+    dry_run: bool,
+    session_id: Option<String>,
+) -> Result<PurgePlan, AdventureError> {
+    match super::api_fetch(ServerMessage::PurgeUser { username, mode, dry_run, session_id }).await? {
+        ServerMessage::ReturnPurgePlan(plan) => Ok(plan),
+        other => Err(unexpected(other)),
+    }
+}
+
+/// Deletes `node_id` and everything below it, or with `dry_run` only says what would go.
+pub async fn delete_subtree(
+    node_id: String,
+    dry_run: bool,
+    session_id: Option<String>,
+) -> Result<SubtreeDeletion, AdventureError> {
+    match super::api_fetch(ServerMessage::DeleteSubtree { node_id, dry_run, session_id }).await? {
+        ServerMessage::ReturnSubtreeDeletion(deletion) => Ok(deletion),
+        other => Err(unexpected(other)),
+    }
+}
+
+/// Moves `node_id` and everything below it under `new_parent_id`, or makes it a new story.
+pub async fn move_node(
+    node_id: String,
+    new_parent_id: Option<String>,
+    session_id: Option<String>,
+) -> Result<(), AdventureError> {
+    match super::api_fetch(ServerMessage::MoveNode { node_id, new_parent_id, session_id }).await? {
+        ServerMessage::Ok => Ok(()),
+        other => Err(unexpected(other)),
+    }
+}
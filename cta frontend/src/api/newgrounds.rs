@@ -57,7 +57,7 @@ pub async fn check_session(session_id: &str) -> Result<Option<NgUser>, String> {
 
     let resp = Request::post(GATEWAY_URL)
         .header("Content-Type", "application/x-www-form-urlencoded")
-        .body(format!("input={}", payload.to_string()))
+        .body(format!("input={}", payload))
         .map_err(|e| e.to_string())?
         .send()
         .await
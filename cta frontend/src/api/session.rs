@@ -0,0 +1,49 @@
+use shared::{ServerMessage, SessionToken, UserInfo};
+
+use super::RequestError;
+
+const STORAGE_KEY: &str = "cta_session";
+
+/// Trades a Newgrounds session ID for a backend session token and remembers it.
+pub async fn login(session_id: String) -> Result<SessionToken, RequestError> {
+    match super::api_fetch(ServerMessage::Login { session_id: Some(session_id) }).await? {
+        ServerMessage::LoggedIn(token) => {
+            save_token(&token);
+            Ok(token)
+        }
+        other => Err(RequestError::Transport(format!("Unexpected response: {:?}", other))),
+    }
+}
+
+/// The token from the last login, unless it has expired.
+pub fn stored_token() -> Option<SessionToken> {
+    let token: SessionToken = web_sys::window()
+        .and_then(|w| w.local_storage().ok().flatten())
+        .and_then(|storage| storage.get_item(STORAGE_KEY).ok().flatten())
+        .and_then(|json| serde_json::from_str(&json).ok())?;
+
+    let now = (js_sys::Date::now() / 1000.0) as u64;
+    (token.expires_at > now).then_some(token)
+}
+
+fn save_token(token: &SessionToken) {
+    if let Some(storage) = web_sys::window().and_then(|w| w.local_storage().ok().flatten()) {
+        if let Ok(json) = serde_json::to_string(token) {
+            let _ = storage.set_item(STORAGE_KEY, &json);
+        }
+    }
+}
+
+/// What to send as `session_id` with writes: our session token. The server only takes
+/// the raw Newgrounds session ID at login.
+pub fn credential() -> Option<String> {
+    stored_token().map(|t| t.token)
+}
+
+/// Who the server thinks we are, with our role. `None` when signed out.
+pub async fn fetch_identity() -> Result<Option<UserInfo>, RequestError> {
+    match super::api_fetch(ServerMessage::RequestIdentity { session_id: credential() }).await? {
+        ServerMessage::ReturnIdentity(info) => Ok(info),
+        other => Err(RequestError::Transport(format!("Unexpected response: {:?}", other))),
+    }
+}
@@ -0,0 +1,81 @@
+use leptos::prelude::*;
+
+use crate::state::moderation::use_moderation_state;
+
+/// An admin's preview of deleting `node_id` and everything below it, with a confirm button.
+#[component]
+pub fn BranchDeletion(node_id: String) -> impl IntoView {
+    let moderation = use_moderation_state();
+    let deletion = moderation.subtree_deletion();
+    let busy = moderation.busy();
+    let error = moderation.error();
+
+    let previewed = move || deletion.get().filter(|d| d.root.id == node_id);
+
+    view! {
+        {move || previewed().map(|d| {
+            let authors = d
+                .authors
+                .iter()
+                .map(|(author, count)| format!("{} ({})", author, count))
+                .collect::<Vec<_>>()
+                .join(", ");
+            view! {
+                <div class="purge-plan">
+                    <p>{format!("Deletes this and everything below it: {} nodes by {}.", d.deleted.len(), authors)}</p>
+                    <p class="hint">"They're archived first, and the deletion is recorded in the audit log."</p>
+                    {move || error.get().map(|message| view! { <p class="form-error">{message}</p> })}
+                    <div class="moderation-actions">
+                        <button
+                            class="reject-btn"
+                            disabled=move || busy.get()
+                            on:click=move |_| moderation.confirm_subtree_deletion()
+                        >
+                            "Delete branch"
+                        </button>
+                        <button class="cancel-btn" on:click=move |_| deletion.set(None)>"Cancel"</button>
+                    </div>
+                </div>
+            }
+        })}
+    }
+}
+
+/// Shown while an admin is moving a node: where to put it, or how to back out.
+#[component]
+pub fn MoveBanner() -> impl IntoView {
+    let moderation = use_moderation_state();
+    let moving = moderation.moving();
+    let busy = moderation.busy();
+    let error = moderation.error();
+
+    view! {
+        {move || moving.get().map(|node| view! {
+            <div class="undo-banner">
+                <p>
+                    {format!(
+                        "Moving \"{}\": open the node it should continue from and press Move here.",
+                        node.choice_text
+                    )}
+                    {move || error.get().map(|message| view! { <span class="form-error">{format!(" {}", message)}</span> })}
+                </p>
+                <button
+                    class="restart-btn"
+                    disabled=move || busy.get()
+                    on:click=move |_| moderation.move_to(None)
+                >
+                    "Make it a new story"
+                </button>
+                <button
+                    class="cancel-btn"
+                    on:click=move |_| {
+                        error.set(None);
+                        moving.set(None);
+                    }
+                >
+                    "Cancel"
+                </button>
+            </div>
+        })}
+    }
+}
@@ -1,32 +1,21 @@
 use leptos::callback::Callable;
 use leptos::ev;
 use leptos::prelude::*;
+use shared::chance::{Roll, MAX_OUTCOME_VALUE};
+use shared::mechanics::{parse_condition, parse_effects};
 use wasm_bindgen::prelude::*;
 
+use crate::api::adventure::AdventureError;
 use crate::api::llm::call_llm_stream;
-use crate::api::newgrounds::get_session_id;
-use crate::domain::adventure::AdventureNode;
+use crate::api::session::credential;
+use crate::domain::adventure::{AdventureNode, Ending, NodeRules};
 use crate::state::adventure::use_adventure_state;
 use crate::state::llm::use_llm_state;
 
+use super::helpers::copy_to_clipboard;
 use super::ContributeMode;
 
 #[wasm_bindgen(inline_js = "
-export function copy_to_clipboard(text) {
-    if (navigator.clipboard && navigator.clipboard.writeText) {
-        return navigator.clipboard.writeText(text);
-    }
-    const textarea = document.createElement('textarea');
-    textarea.value = text;
-    textarea.style.position = 'fixed';
-    textarea.style.opacity = '0';
-    document.body.appendChild(textarea);
-    textarea.select();
-    document.execCommand('copy');
-    document.body.removeChild(textarea);
-    return Promise.resolve();
-}
-
 export function auto_resize_story_textarea() {
     requestAnimationFrame(() => {
         const el = document.querySelector('.contribute-form textarea');
@@ -38,7 +27,6 @@ export function auto_resize_story_textarea() {
 }
 ")]
 extern "C" {
-    fn copy_to_clipboard(text: &str) -> js_sys::Promise;
     fn auto_resize_story_textarea();
 }
 
@@ -75,6 +63,15 @@ pub fn ContributeForm(
         .expect("NG username signal must be provided by App");
     let (choice_text, set_choice_text) = signal(String::new());
     let (story_text, set_story_text) = signal(String::new());
+    let (condition, set_condition) = signal(String::new());
+    let (effects, set_effects) = signal(String::new());
+    let (random, set_random) = signal(false);
+    let (dice, set_dice) = signal(false);
+    let (dice_count, set_dice_count) = signal("2".to_string());
+    let (dice_sides, set_dice_sides) = signal("6".to_string());
+    let (dice_bonus, set_dice_bonus) = signal(String::new());
+    let (outcome_value, set_outcome_value) = signal(String::new());
+    let (ending, set_ending) = signal::<Option<Ending>>(None);
     let (submitting, set_submitting) = signal(false);
     let (copied, set_copied) = signal(false);
     let (llm_loading, set_llm_loading) = signal(false);
@@ -82,22 +79,110 @@ pub fn ContributeForm(
     let (llm_gen, set_llm_gen) = signal(0u64);
     let show_cancel = on_cancel.is_some();
     let is_new_story = mode == ContributeMode::NewStory;
+    let submit_error = state.submit_error();
+    let offline = state.offline();
+    let contribute_parent = (!parent_id.is_empty()).then(|| parent_id.clone());
+    let allowed = Memo::new(move |_| state.can_contribute(contribute_parent.as_deref()));
+
+    // Seconds until the server accepts another submission after rate limiting us
+    let (cooldown, set_cooldown) = signal(0u64);
+
+    let cooling_down = move || cooldown.get() > 0;
+
+    // A failed submission leaves the form open, so let the user try again
+    Effect::new(move |_| {
+        let error = submit_error.get();
+        if error.is_some() {
+            set_submitting.set(false);
+        }
+        if let Some(AdventureError::SlowDown { retry_after }) = error {
+            let ticking = cooldown.get_untracked() > 0;
+            set_cooldown.set(retry_after);
+            if !ticking {
+                tick_cooldown(set_cooldown);
+            }
+        }
+    });
+
+    let field_error = move |name: &'static str| {
+        move || match submit_error.get() {
+            Some(AdventureError::InvalidField { field, reason }) if field == name => Some(reason),
+            _ => None,
+        }
+    };
+    // Checked as they're typed; the server checks them again
+    let condition_error = move || parse_condition(&condition.get()).err().map(|e| e.to_string());
+    let effects_error = move || parse_effects(&effects.get()).err().map(|e| e.to_string());
+    // Continuing a random branch means writing one of its outcomes
+    let graph = state.graph();
+    let roll_parent_id = parent_id.clone();
+    let parent_roll = Memo::new(move |_| graph.with(|g| g.roll(&roll_parent_id).cloned()));
+    let roll = move || -> Result<Option<Roll>, String> {
+        if !random.get() {
+            return Ok(None);
+        }
+        if !dice.get() {
+            return Ok(Some(Roll::Weighted));
+        }
+        let number = |text: String| text.trim().parse::<u32>().map_err(|_| "Dice need a number".to_string());
+        let roll = Roll::Dice {
+            count: number(dice_count.get())?,
+            sides: number(dice_sides.get())?,
+            bonus: dice_bonus.get().trim().to_string(),
+        };
+        roll.check()?;
+        Ok(Some(roll))
+    };
+    let outcome = move || -> Result<Option<u32>, String> {
+        let text = outcome_value.get();
+        if parent_roll.get().is_none() || text.trim().is_empty() {
+            return Ok(None);
+        }
+        match text.trim().parse::<u32>() {
+            Ok(value) if value <= MAX_OUTCOME_VALUE => Ok(Some(value)),
+            _ => Err(format!("Use a whole number from 0 to {}", MAX_OUTCOME_VALUE)),
+        }
+    };
+    let mechanics_invalid = move || {
+        condition_error().is_some() || effects_error().is_some() || roll().is_err() || outcome().is_err()
+    };
+    let form_error = move || match submit_error.get() {
+        Some(AdventureError::InvalidField { field, .. })
+            if ["choice_text", "story_text", "condition", "effects"].contains(&field.as_str()) =>
+        {
+            None
+        }
+        Some(AdventureError::SlowDown { .. }) => match cooldown.get() {
+            0 => None,
+            seconds => Some(AdventureError::SlowDown { retry_after: seconds }.to_string()),
+        },
+        other => other.map(|e| e.to_string()),
+    };
 
     let on_submit = move |ev: ev::SubmitEvent| {
         ev.prevent_default();
         set_submitting.set(true);
 
-        let session_id = get_session_id();
+        let session_id = credential();
 
         let node = AdventureNode {
-            id: format!("user_{}", js_sys::Date::now() as u64),
+            // Assigned by the server
+            id: String::new(),
             parent_id: if parent_id.is_empty() { None } else { Some(parent_id.clone()) },
             choice_text: choice_text.get(),
             story_text: story_text.get(),
             created_by: None,
         };
 
-        state.add_node(node, session_id);
+        let rules = NodeRules {
+            condition: condition.get(),
+            effects: effects.get(),
+            roll: roll().ok().flatten(),
+            outcome_value: outcome().ok().flatten(),
+            ending: ending.get(),
+        };
+
+        state.add_node(node, rules, session_id);
     };
 
     let on_copy_prompt = move |_: ev::MouseEvent| {
@@ -157,6 +242,7 @@ pub fn ContributeForm(
                 &config.api_key,
                 &config.model,
                 &prompt,
+                credential(),
                 move |chunk| {
                     if llm_gen.get() == gen {
                         set_story_text.update(|s| s.push_str(chunk));
@@ -236,6 +322,7 @@ pub fn ContributeForm(
                         on:input=move |ev| set_choice_text.set(event_target_value(&ev))
                         required
                     />
+                    {move || field_error("choice_text")().map(|reason| view! { <p class="field-error">{reason}</p> })}
                 </div>
 
                 <div class="form-group">
@@ -253,17 +340,138 @@ pub fn ContributeForm(
                         }
                         required
                     />
+                    {move || field_error("story_text")().map(|reason| view! { <p class="field-error">{reason}</p> })}
                 </div>
 
+                <Show when=move || !is_new_story>
+                    <div class="form-group">
+                        <label>"Does the story end here?"</label>
+                        <select on:change=move |ev| set_ending.set(event_target_value(&ev).parse().ok())>
+                            <option value="" selected=move || ending.get().is_none()>"No, it goes on"</option>
+                            {Ending::ALL.into_iter().map(|kind| view! {
+                                <option value=kind.as_str() selected=move || ending.get() == Some(kind)>
+                                    {kind.label()}
+                                </option>
+                            }).collect::<Vec<_>>()}
+                        </select>
+                    </div>
+                </Show>
+
+                <details class="mechanics-fields">
+                    <summary>"Conditions and effects (optional)"</summary>
+                    <div class="form-group">
+                        <label>"Requires"</label>
+                        <input
+                            type="text"
+                            placeholder="e.g., has_key and gold >= 10"
+                            prop:value=move || condition.get()
+                            on:input=move |ev| set_condition.set(event_target_value(&ev))
+                        />
+                        {move || {
+                            condition_error()
+                                .or_else(field_error("condition"))
+                                .map(|reason| view! { <p class="field-error">{reason}</p> })
+                        }}
+                    </div>
+                    <div class="form-group">
+                        <label>"Effects"</label>
+                        <input
+                            type="text"
+                            placeholder="e.g., remove key; gold -= 10; set door_open"
+                            prop:value=move || effects.get()
+                            on:input=move |ev| set_effects.set(event_target_value(&ev))
+                        />
+                        {move || {
+                            effects_error()
+                                .or_else(field_error("effects"))
+                                .map(|reason| view! { <p class="field-error">{reason}</p> })
+                        }}
+                    </div>
+                    <p class="hint">
+                        "Readers who don't meet the condition can't choose this. Effects apply when they do: "
+                        "set/unset a flag, add/remove an item, or change a counter with =, += and -=."
+                    </p>
+                    {move || parent_roll.get().map(|roll| {
+                        let label = match roll {
+                            Roll::Weighted => "Weight: how likely this outcome is next to the others",
+                            Roll::Dice { .. } => "Lowest roll that leads here",
+                        };
+                        view! {
+                            <div class="form-group">
+                                <label>{label}</label>
+                                <input
+                                    type="number"
+                                    min="0"
+                                    placeholder="0"
+                                    prop:value=move || outcome_value.get()
+                                    on:input=move |ev| set_outcome_value.set(event_target_value(&ev))
+                                />
+                                {move || outcome().err().map(|reason| view! { <p class="field-error">{reason}</p> })}
+                            </div>
+                        }
+                    })}
+                    <label class="llm-enable-checkbox">
+                        <input
+                            type="checkbox"
+                            prop:checked=move || random.get()
+                            on:change=move |_| set_random.update(|r| *r = !*r)
+                        />
+                        <span>"Leave what happens next to chance"</span>
+                    </label>
+                    <Show when=move || random.get()>
+                        <div class="form-group">
+                            <select on:change=move |ev| set_dice.set(event_target_value(&ev) == "dice")>
+                                <option value="weighted" selected=move || !dice.get()>"Weighted draw between outcomes"</option>
+                                <option value="dice" selected=move || dice.get()>"Dice check"</option>
+                            </select>
+                        </div>
+                        <Show when=move || dice.get()>
+                            <div class="form-group dice-fields">
+                                <input
+                                    type="number"
+                                    min="1"
+                                    title="Dice"
+                                    prop:value=move || dice_count.get()
+                                    on:input=move |ev| set_dice_count.set(event_target_value(&ev))
+                                />
+                                <span>"d"</span>
+                                <input
+                                    type="number"
+                                    min="2"
+                                    title="Sides"
+                                    prop:value=move || dice_sides.get()
+                                    on:input=move |ev| set_dice_sides.set(event_target_value(&ev))
+                                />
+                                <span>"+"</span>
+                                <input
+                                    type="text"
+                                    placeholder="bonus variable, e.g., lockpicking"
+                                    prop:value=move || dice_bonus.get()
+                                    on:input=move |ev| set_dice_bonus.set(event_target_value(&ev))
+                                />
+                            </div>
+                        </Show>
+                        {move || roll().err().map(|reason| view! { <p class="field-error">{reason}</p> })}
+                    </Show>
+                </details>
+
+                {move || form_error().map(|message| view! { <p class="form-error">{message}</p> })}
+
                 <div class="form-actions">
-                    <button type="submit" class="submit-btn" disabled=move || submitting.get() || ng_username.get().is_none()>
+                    <button type="submit" class="submit-btn" disabled=move || submitting.get() || offline.get() || !allowed.get() || cooling_down() || mechanics_invalid()>
                         {move || {
-                            if ng_username.get().is_none() {
-                                "You must be signed into Newgrounds to upload"
+                            if offline.get() {
+                                "Offline: contributions are paused".to_string()
+                            } else if cooling_down() {
+                                format!("Wait {}s", cooldown.get())
+                            } else if ng_username.get().is_none() {
+                                "Sign in to upload".to_string()
+                            } else if !allowed.get() {
+                                "You can't add to the story here".to_string()
                             } else if submitting.get() {
-                                "Submitting..."
+                                "Submitting...".to_string()
                             } else {
-                                "Upload and Add to Story"
+                                "Upload and Add to Story".to_string()
                             }
                         }}
                     </button>
@@ -285,3 +493,17 @@ pub fn ContributeForm(
         </div>
     }
 }
+
+/// Counts `cooldown` down to zero, a second at a time.
+fn tick_cooldown(cooldown: WriteSignal<u64>) {
+    gloo_timers::callback::Timeout::new(1_000, move || {
+        let remaining = cooldown.try_update(|seconds| {
+            *seconds = seconds.saturating_sub(1);
+            *seconds
+        });
+        if remaining.is_some_and(|seconds| seconds > 0) {
+            tick_cooldown(cooldown);
+        }
+    })
+    .forget();
+}
@@ -0,0 +1,65 @@
+use leptos::prelude::*;
+use shared::DeletedNode;
+
+use crate::state::moderation::use_moderation_state;
+
+use super::helpers::time_ago;
+
+/// Deleted nodes that haven't been purged yet, for admins to put back.
+#[component]
+pub fn DeletedNodes() -> impl IntoView {
+    let moderation = use_moderation_state();
+    let deleted = moderation.deleted();
+    let busy = moderation.busy();
+    let error = moderation.error();
+
+    // Deletions don't notify moderators, so catch up on opening
+    moderation.refresh();
+
+    view! {
+        <div class="moderation-queue">
+            <div class="moderation-header">
+                <h2>"Deleted nodes"</h2>
+                <button class="cancel-btn" on:click=move |_| moderation.close()>"Back to the story"</button>
+            </div>
+
+            {move || error.get().map(|message| view! { <p class="form-error">{message}</p> })}
+
+            <Show
+                when=move || !deleted.with(|d| d.is_empty())
+                fallback=|| view! { <p class="hint">"Nothing has been deleted."</p> }
+            >
+                <For
+                    each=move || deleted.get()
+                    key=|deleted| deleted.node.id.clone()
+                    children=move |deleted: DeletedNode| {
+                        let author = deleted.node.created_by.clone().unwrap_or_else(|| "anonymous".into());
+                        let deleted_by = deleted.deleted_by.clone().unwrap_or_else(|| "unknown".into());
+                        let id = deleted.node.id.clone();
+                        view! {
+                            <article class="pending-node">
+                                <div class="pending-node-header">
+                                    <span class="pending-node-context">
+                                        {format!("Deleted by {} {}", deleted_by, time_ago(deleted.deleted_at))}
+                                    </span>
+                                    <span class="pending-node-author">{author}</span>
+                                </div>
+                                <h3 class="story-title">{deleted.node.choice_text}</h3>
+                                <p class="story-text">{deleted.node.story_text}</p>
+                                <div class="moderation-actions">
+                                    <button
+                                        class="submit-btn"
+                                        disabled=move || busy.get()
+                                        on:click=move |_| moderation.restore(id.clone())
+                                    >
+                                        "Restore"
+                                    </button>
+                                </div>
+                            </article>
+                        }
+                    }
+                />
+            </Show>
+        </div>
+    }
+}
@@ -0,0 +1,74 @@
+use leptos::prelude::*;
+
+use crate::domain::adventure::NodeEnding;
+use crate::state::adventure::use_adventure_state;
+
+use super::contribute_form::ContributeForm;
+use super::ContributeMode;
+
+/// Shown in place of the options when the reader reaches an ending nobody has written past:
+/// what kind it was, how many of the story's endings they've found, and a way to play again.
+#[component]
+pub fn EndingScreen(ending: NodeEnding) -> impl IntoView {
+    let state = use_adventure_state();
+    let graph = state.graph();
+    let show_contribute = state.show_contribute();
+    let root_id = ending.root_id.clone();
+    let node_id = ending.node_id.clone();
+    state.load_story_endings(root_id.clone());
+
+    let found = Memo::new(move |_| state.discovered_endings(&root_id));
+    let total = {
+        let root_id = ending.root_id.clone();
+        move || graph.with(|g| g.endings_in(&root_id).len())
+    };
+    let progress = move || {
+        let total = total();
+        let found = found.with(|f| f.len()).min(total);
+        if total == 1 {
+            "This story has just the one ending, and you found it".to_string()
+        } else {
+            format!("You've found {} of this story's {} endings", found, total)
+        }
+    };
+    let contribute_parent = node_id.clone();
+
+    view! {
+        <div id="story-end" class=format!("ending-screen ending-{}", ending.ending.as_str())>
+            <p class="ending-the-end">"The End"</p>
+            <h3 class="ending-kind">{ending.ending.label()}</h3>
+            <p class="ending-progress">{progress}</p>
+            <ul class="ending-list">
+                {move || found.get().into_iter().map(|found| {
+                    let choice_text = graph.with(|g| g.node(&found.node_id).map(|n| n.choice_text.clone()));
+                    view! {
+                        <li class=format!("ending-{}", found.ending.as_str())>
+                            <span class="ending-badge">{found.ending.label()}</span>
+                            {choice_text.map(|text| format!(" after “{}”", text))}
+                        </li>
+                    }
+                }).collect::<Vec<_>>()}
+            </ul>
+            <div class="ending-actions">
+                <button class="option-btn" on:click=move |_| state.restart_story()>
+                    <span class="option-text">"Play this story again"</span>
+                </button>
+                <button class="link-start-btn" on:click=move |_| state.reset_path()>
+                    "Choose another story"
+                </button>
+                <Show when=move || state.can_contribute(Some(&contribute_parent))>
+                    <button class="link-start-btn" on:click=move |_| state.toggle_contribute()>
+                        {move || if show_contribute.get() { "Cancel" } else { "Write what happens after anyway" }}
+                    </button>
+                </Show>
+            </div>
+            <Show when=move || show_contribute.get()>
+                <ContributeForm
+                    parent_id=node_id.clone()
+                    mode=ContributeMode::AfterEnding
+                    on_cancel=Callback::new(move |_| state.close_contribute())
+                />
+            </Show>
+        </div>
+    }
+}
@@ -2,10 +2,16 @@ use leptos::prelude::*;
 
 use crate::domain::adventure::AdventureNode;
 use crate::state::adventure::{use_adventure_state, LoadState};
+use crate::state::moderation::{use_moderation_state, ModerationScreen};
 
+use super::deleted_nodes::DeletedNodes;
+use super::helpers::reload_page;
+use super::moderation_queue::ModerationQueue;
+use super::reported_nodes::ReportedNodes;
 use super::sidebar::Sidebar;
 use super::story_header::StoryHeader;
 use super::story_scroll::StoryScroll;
+use super::user_admin::UserAdmin;
 
 #[component]
 pub fn Game() -> impl IntoView {
@@ -43,12 +49,52 @@ pub fn Game() -> impl IntoView {
 
     let current_parent_id = Memo::new(move |_| path.get().last().cloned());
 
+    // Re-ask what the reader may do whenever the path, its options or the signed-in user change
+    let ng_username = use_context::<RwSignal<Option<String>>>()
+        .expect("NG username signal must be provided by App");
+    let capabilities_key = Memo::new(move |_| {
+        let p = path.get();
+        let option_counts: Vec<(usize, usize)> = graph.with(|g| {
+            p.iter()
+                .map(|id| (g.children_ids(id).len(), g.links_from(id).len()))
+                .collect()
+        });
+        (p, option_counts, ng_username.get())
+    });
+    Effect::new(move |_| {
+        capabilities_key.track();
+        state.refresh_capabilities();
+    });
+
+    Effect::new(move |_| {
+        ng_username.track();
+        state.refresh_submissions();
+        state.refresh_own_nodes();
+    });
+
+    // Moderators' queue follows submissions and reviews as they happen
+    let moderation = use_moderation_state();
+    let review_activity = state.review_activity();
+    Effect::new(move |_| {
+        review_activity.track();
+        if moderation.available() {
+            moderation.refresh();
+        } else {
+            moderation.close();
+        }
+    });
+    let moderation_screen = moderation.screen();
+
     let is_loading = Memo::new(move |_| matches!(load_state.get(), LoadState::Loading));
     let error_message = Memo::new(move |_| match load_state.get() {
         LoadState::Error(msg) => Some(msg),
         _ => None,
     });
     let is_ready = Memo::new(move |_| matches!(load_state.get(), LoadState::Ready));
+    let notice = state.notice();
+    let undo = state.undo();
+    let outdated = state.outdated();
+    let offline = state.offline();
 
     view! {
         <div class="app-layout">
@@ -69,7 +115,52 @@ pub fn Game() -> impl IntoView {
                     </div>
                 </Show>
 
-                <Show when=move || is_ready.get()>
+                <Show when=move || outdated.get()>
+                    <div class="reload-banner">
+                        <p>"A newer version of the game is available."</p>
+                        <button class="restart-btn" on:click=move |_| reload_page()>"Reload"</button>
+                    </div>
+                </Show>
+
+                <Show when=move || offline.get() && is_ready.get()>
+                    <div class="offline-banner">
+                        <p>"Offline: showing a saved copy of the story. It's read-only until the server is back."</p>
+                    </div>
+                </Show>
+
+                <Show when=move || notice.get().is_some()>
+                    <div class="notice-banner">
+                        <p>{move || notice.get().unwrap_or_default()}</p>
+                        <button class="notice-dismiss" on:click=move |_| state.dismiss_notice()>
+                            "\u{2715}"
+                        </button>
+                    </div>
+                </Show>
+
+                <Show when=move || undo.get().is_some()>
+                    <div class="undo-banner">
+                        <p>{move || undo.get().map(|n| format!("Deleted \"{}\".", n.choice_text)).unwrap_or_default()}</p>
+                        <button class="restart-btn" on:click=move |_| state.undo_delete()>"Undo"</button>
+                    </div>
+                </Show>
+
+                <Show when=move || moderation_screen.get() == Some(ModerationScreen::Queue)>
+                    <ModerationQueue />
+                </Show>
+
+                <Show when=move || moderation_screen.get() == Some(ModerationScreen::Reports)>
+                    <ReportedNodes />
+                </Show>
+
+                <Show when=move || moderation_screen.get() == Some(ModerationScreen::Users)>
+                    <UserAdmin />
+                </Show>
+
+                <Show when=move || moderation_screen.get() == Some(ModerationScreen::Deleted)>
+                    <DeletedNodes />
+                </Show>
+
+                <Show when=move || is_ready.get() && moderation_screen.get().is_none()>
                     <StoryScroll
                         segments=segments
                         current_options=current_options
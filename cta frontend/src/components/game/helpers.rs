@@ -1,9 +1,51 @@
 use leptos::web_sys;
+use wasm_bindgen::prelude::*;
 
-pub fn scroll_to_segment(id: &str) {
+#[wasm_bindgen(inline_js = "
+export function copy_to_clipboard(text) {
+    if (navigator.clipboard && navigator.clipboard.writeText) {
+        return navigator.clipboard.writeText(text);
+    }
+    const textarea = document.createElement('textarea');
+    textarea.value = text;
+    textarea.style.position = 'fixed';
+    textarea.style.opacity = '0';
+    document.body.appendChild(textarea);
+    textarea.select();
+    document.execCommand('copy');
+    document.body.removeChild(textarea);
+    return Promise.resolve();
+}
+")]
+extern "C" {
+    pub fn copy_to_clipboard(text: &str) -> js_sys::Promise;
+}
+
+/// Scrolls to the `index`th node on the path. Indices rather than IDs, since a path that
+/// loops back passes through some nodes more than once.
+pub fn scroll_to_segment(index: usize) {
     if let Some(doc) = web_sys::window().and_then(|w| w.document()) {
-        if let Some(el) = doc.get_element_by_id(&format!("segment-{}", id)) {
-            let _ = el.scroll_into_view_with_bool(true);
+        if let Some(el) = doc.get_element_by_id(&format!("segment-{}", index)) {
+            el.scroll_into_view_with_bool(true);
         }
     }
 }
+
+pub fn reload_page() {
+    if let Some(window) = web_sys::window() {
+        let _ = window.location().reload();
+    }
+}
+
+/// How long ago `unix_secs` was, roughly: "5 minutes ago".
+pub fn time_ago(unix_secs: u64) -> String {
+    let now = (js_sys::Date::now() / 1000.0) as u64;
+    let secs = now.saturating_sub(unix_secs);
+    let (count, unit) = match secs {
+        0..60 => return "just now".into(),
+        60..3_600 => (secs / 60, "minute"),
+        3_600..86_400 => (secs / 3_600, "hour"),
+        _ => (secs / 86_400, "day"),
+    };
+    format!("{} {}{} ago", count, unit, if count == 1 { "" } else { "s" })
+}
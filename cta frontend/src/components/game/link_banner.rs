@@ -0,0 +1,45 @@
+use leptos::prelude::*;
+
+use crate::state::adventure::use_adventure_state;
+
+/// Shown while the reader is making a link: its choice text, and how to back out.
+#[component]
+pub fn LinkBanner() -> impl IntoView {
+    let state = use_adventure_state();
+    let linking = state.linking();
+    let graph = state.graph();
+    // Only redrawn when a link is started, so typing doesn't lose focus
+    let parent_id = Memo::new(move |_| linking.with(|d| d.as_ref().map(|d| d.parent_id.clone())));
+
+    view! {
+        {move || parent_id.get().map(|parent_id| {
+            let from = graph.with_untracked(|g| g.node(&parent_id).map(|n| n.choice_text.clone())).unwrap_or_default();
+            view! {
+                <div class="link-banner">
+                    <p>
+                        {format!(
+                            "Linking from \"{}\": open the node this choice should lead to and press Link here.",
+                            from
+                        )}
+                    </p>
+                    <input
+                        type="text"
+                        placeholder="Choice text, e.g., Go back to the crossroads"
+                        prop:value=move || linking.with(|d| d.as_ref().map(|d| d.choice_text.clone()).unwrap_or_default())
+                        on:input=move |ev| linking.update(|d| {
+                            if let Some(d) = d {
+                                d.choice_text = event_target_value(&ev);
+                            }
+                        })
+                    />
+                    {move || {
+                        linking
+                            .with(|d| d.as_ref().and_then(|d| d.error.clone()))
+                            .map(|message| view! { <p class="form-error">{message}</p> })
+                    }}
+                    <button class="cancel-btn" on:click=move |_| state.cancel_link()>"Cancel"</button>
+                </div>
+            }
+        })}
+    }
+}
@@ -1,13 +1,23 @@
+mod branch_admin;
 mod contribute_form;
+mod deleted_nodes;
+mod ending_screen;
 mod game_layout;
 mod helpers;
+mod link_banner;
+mod moderation_queue;
+mod report_form;
+mod reported_nodes;
 mod sidebar;
 mod story_header;
 mod story_scroll;
+mod user_admin;
 
 #[derive(Clone, Copy, PartialEq)]
 pub(crate) enum ContributeMode {
     DeadEnd,
+    /// Writing past a node its author meant as an ending.
+    AfterEnding,
     Branch,
     NewStory,
 }
@@ -16,6 +26,7 @@ impl ContributeMode {
     pub fn title(self) -> &'static str {
         match self {
             Self::DeadEnd => "Continue the story",
+            Self::AfterEnding => "Continue past the ending",
             Self::Branch => "Add a new path",
             Self::NewStory => "Start a new story",
         }
@@ -24,6 +35,7 @@ impl ContributeMode {
     pub fn hint(self) -> &'static str {
         match self {
             Self::DeadEnd => "A next path hasn't been written yet. Add one.",
+            Self::AfterEnding => "This was written as an ending, but the story could still go on.",
             Self::Branch => "Create a new option branching from this point.",
             Self::NewStory => "Write the opening of a brand new adventure.",
         }
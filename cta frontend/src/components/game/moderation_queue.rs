@@ -0,0 +1,108 @@
+use leptos::prelude::*;
+use shared::{PendingNode, ReviewDecision};
+
+use crate::state::moderation::use_moderation_state;
+
+/// Submissions waiting for review, with bulk approve and reject.
+#[component]
+pub fn ModerationQueue() -> impl IntoView {
+    let moderation = use_moderation_state();
+    let queue = moderation.queue();
+    let selected = moderation.selected();
+    let busy = moderation.busy();
+    let error = moderation.error();
+    let flags = moderation.flags();
+    let (reason, set_reason) = signal(String::new());
+
+    let none_selected = move || selected.with(|s| s.is_empty());
+
+    let on_reject = move |_| {
+        moderation.review(ReviewDecision::Reject { reason: reason.get() });
+        set_reason.set(String::new());
+    };
+
+    view! {
+        <div class="moderation-queue">
+            <div class="moderation-header">
+                <h2>"Moderation queue"</h2>
+                <button class="cancel-btn" on:click=move |_| moderation.close()>"Back to the story"</button>
+            </div>
+
+            <Show
+                when=move || !queue.with(|q| q.is_empty())
+                fallback=|| view! { <p class="hint">"Nothing is waiting for review."</p> }
+            >
+                <div class="moderation-actions">
+                    <label class="moderation-select-all">
+                        <input
+                            type="checkbox"
+                            prop:checked=move || selected.with(|s| s.len()) == queue.with(|q| q.len())
+                            on:change=move |_| moderation.toggle_all()
+                        />
+                        <span>{move || format!("{} selected", selected.with(|s| s.len()))}</span>
+                    </label>
+                    <button
+                        class="submit-btn"
+                        disabled=move || busy.get() || none_selected()
+                        on:click=move |_| moderation.review(ReviewDecision::Approve)
+                    >
+                        "Approve"
+                    </button>
+                    <input
+                        type="text"
+                        class="moderation-reason"
+                        placeholder="Reason for rejecting"
+                        prop:value=move || reason.get()
+                        on:input=move |ev| set_reason.set(event_target_value(&ev))
+                    />
+                    <button
+                        class="reject-btn"
+                        disabled=move || busy.get() || none_selected() || reason.with(|r| r.trim().is_empty())
+                        on:click=on_reject
+                    >
+                        "Reject"
+                    </button>
+                </div>
+
+                {move || error.get().map(|message| view! { <p class="form-error">{message}</p> })}
+
+                <For
+                    each=move || queue.get()
+                    key=|pending| pending.node.id.clone()
+                    children=move |pending: PendingNode| {
+                        let id = pending.node.id.clone();
+                        let toggle_id = id.clone();
+                        let context = match pending.parent_choice_text {
+                            Some(parent) => format!("Continues \u{201c}{}\u{201d}", parent),
+                            None => "New story".to_string(),
+                        };
+                        let author = pending.node.created_by.unwrap_or_else(|| "anonymous".into());
+                        let flag_id = id.clone();
+                        let flag_reason = move || flags.with(|f| f.get(&flag_id).cloned());
+                        view! {
+                            <article class="pending-node" class:selected=move || selected.with(|s| s.contains(&id))>
+                                <label class="pending-node-header">
+                                    <input
+                                        type="checkbox"
+                                        prop:checked={
+                                            let id = toggle_id.clone();
+                                            move || selected.with(|s| s.contains(&id))
+                                        }
+                                        on:change=move |_| moderation.toggle_selected(&toggle_id)
+                                    />
+                                    <span class="pending-node-context">{context}</span>
+                                    <span class="pending-node-author">{author}</span>
+                                </label>
+                                {move || flag_reason().map(|reason| view! {
+                                    <p class="pending-node-flag">{format!("Flagged: {}", reason)}</p>
+                                })}
+                                <h3 class="story-title">{pending.node.choice_text}</h3>
+                                <p class="story-text">{pending.node.story_text}</p>
+                            </article>
+                        }
+                    }
+                />
+            </Show>
+        </div>
+    }
+}
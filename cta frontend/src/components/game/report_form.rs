@@ -0,0 +1,42 @@
+use leptos::callback::Callable;
+use leptos::ev;
+use leptos::prelude::*;
+use shared::ReportReason;
+
+use crate::state::adventure::use_adventure_state;
+
+/// Inline form for reporting `node_id` to moderators.
+#[component]
+pub fn ReportForm(node_id: String, on_close: Callback<()>) -> impl IntoView {
+    let state = use_adventure_state();
+    let (reason, set_reason) = signal(ReportReason::Spam);
+    let (details, set_details) = signal(String::new());
+
+    let on_submit = move |ev: ev::SubmitEvent| {
+        ev.prevent_default();
+        state.report_node(node_id.clone(), reason.get(), details.get());
+        on_close.run(());
+    };
+
+    view! {
+        <form class="report-form" on:submit=on_submit>
+            <select on:change=move |ev| {
+                if let Ok(r) = event_target_value(&ev).parse() {
+                    set_reason.set(r);
+                }
+            }>
+                {ReportReason::ALL.map(|r| view! {
+                    <option value=r.as_str() selected=move || reason.get() == r>{r.label()}</option>
+                })}
+            </select>
+            <input
+                type="text"
+                placeholder="Anything moderators should know (optional)"
+                prop:value=move || details.get()
+                on:input=move |ev| set_details.set(event_target_value(&ev))
+            />
+            <button type="submit" class="reject-btn">"Report"</button>
+            <button type="button" class="cancel-btn" on:click=move |_| on_close.run(())>"Cancel"</button>
+        </form>
+    }
+}
@@ -0,0 +1,96 @@
+use leptos::prelude::*;
+use shared::{ReportResolution, ReportedNode};
+
+use crate::state::moderation::use_moderation_state;
+
+/// Nodes readers have reported, with the path leading to each and what to do about it.
+#[component]
+pub fn ReportedNodes() -> impl IntoView {
+    let moderation = use_moderation_state();
+    let reports = moderation.reports();
+    let busy = moderation.busy();
+    let error = moderation.error();
+
+    view! {
+        <div class="moderation-queue">
+            <div class="moderation-header">
+                <h2>"Reported nodes"</h2>
+                <button class="cancel-btn" on:click=move |_| moderation.close()>"Back to the story"</button>
+            </div>
+
+            {move || error.get().map(|message| view! { <p class="form-error">{message}</p> })}
+
+            <Show
+                when=move || !reports.with(|r| r.is_empty())
+                fallback=|| view! { <p class="hint">"Nothing has been reported."</p> }
+            >
+                <For
+                    each=move || reports.get()
+                    key=|reported| (reported.node.id.clone(), reported.reports.len())
+                    children=move |reported: ReportedNode| {
+                        let path = reported
+                            .path
+                            .iter()
+                            .map(|n| n.choice_text.as_str())
+                            .collect::<Vec<_>>()
+                            .join(" \u{203a} ");
+                        let author = reported.node.created_by.clone().unwrap_or_else(|| "anonymous".into());
+                        let resolve = {
+                            let id = reported.node.id.clone();
+                            move |resolution| moderation.resolve(id.clone(), resolution)
+                        };
+                        let dismiss = resolve.clone();
+                        let delete = resolve.clone();
+                        view! {
+                            <article class="pending-node">
+                                <div class="pending-node-header">
+                                    <span class="pending-node-context">
+                                        {if path.is_empty() { "New story".to_string() } else { path }}
+                                    </span>
+                                    {reported.hidden.then(|| view! { <span class="report-hidden">"hidden"</span> })}
+                                    <span class="pending-node-author">{author}</span>
+                                </div>
+                                <h3 class="story-title">{reported.node.choice_text}</h3>
+                                <p class="story-text">{reported.node.story_text}</p>
+                                <ul class="report-list">
+                                    {reported.reports.into_iter().map(|report| view! {
+                                        <li>
+                                            <strong>{report.reason.label()}</strong>
+                                            {(!report.details.is_empty()).then(|| format!(": {}", report.details))}
+                                            <span class="report-reporter">{report.reporter}</span>
+                                        </li>
+                                    }).collect::<Vec<_>>()}
+                                </ul>
+                                <div class="moderation-actions">
+                                    <button
+                                        class="restart-btn"
+                                        title="Close the reports and leave the node as it is"
+                                        disabled=move || busy.get()
+                                        on:click=move |_| resolve(ReportResolution::Resolve)
+                                    >
+                                        "Resolve"
+                                    </button>
+                                    <button
+                                        class="restart-btn"
+                                        title="The reports were unfounded; put the node back if it was hidden"
+                                        disabled=move || busy.get()
+                                        on:click=move |_| dismiss(ReportResolution::Dismiss)
+                                    >
+                                        "Dismiss"
+                                    </button>
+                                    <button
+                                        class="reject-btn"
+                                        disabled=move || busy.get()
+                                        on:click=move |_| delete(ReportResolution::Delete)
+                                    >
+                                        "Delete"
+                                    </button>
+                                </div>
+                            </article>
+                        }
+                    }
+                />
+            </Show>
+        </div>
+    }
+}
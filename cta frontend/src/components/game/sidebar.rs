@@ -4,6 +4,7 @@ use wasm_bindgen::prelude::*;
 use crate::domain::adventure::AdventureNode;
 use crate::state::adventure::use_adventure_state;
 use crate::state::llm::{use_llm_state, LlmProvider};
+use crate::state::moderation::{use_moderation_state, ModerationScreen};
 
 use super::helpers::scroll_to_segment;
 
@@ -36,6 +37,11 @@ pub fn Sidebar(
     let path = state.path();
     let llm = use_llm_state();
     let settings_open = llm.settings_open();
+    let moderation = use_moderation_state();
+    let moderation_queue = moderation.queue();
+    let reports = moderation.reports();
+    let screen = moderation.screen();
+    let game_state = state.game_state();
 
     view! {
         <aside class="sidebar">
@@ -51,18 +57,22 @@ pub fn Sidebar(
                     each={move || segments.get()}
                     key={|(i, unit)| (*i, unit.id.clone())}
                     children={move |(i, unit): (usize, AdventureNode)| {
-                        let id_for_scroll = unit.id.clone();
                         let is_current = move || i == path.get().len().saturating_sub(1);
+                        // Reached by a link rather than as one of the previous node's options
+                        let parent_id = unit.parent_id.clone();
+                        let linked = move || {
+                            i > 0 && path.with(|p| p.get(i - 1).map(String::as_str) != parent_id.as_deref())
+                        };
                         view! {
                             <div class="path-item-row">
                                 <button
                                     class="path-item"
                                     class:active={is_current}
-                                    on:click={
-                                        let id = id_for_scroll.clone();
-                                        move |_| scroll_to_segment(&id)
-                                    }
+                                    on:click=move |_| scroll_to_segment(i)
                                 >
+                                    <Show when=linked.clone()>
+                                        <span class="path-link-marker" title="Reached by a link">"↪ "</span>
+                                    </Show>
                                     {unit.choice_text.clone()}
                                 </button>
                                 <Show when={move || !is_current()}>
@@ -80,6 +90,61 @@ pub fn Sidebar(
                 />
             </nav>
 
+            // What the path so far has given the reader
+            <Show when=move || game_state.with(|s| !s.is_empty())>
+                <h2 class="sidebar-title">"Inventory"</h2>
+                <ul class="inventory-list">
+                    {move || game_state.with(|s| {
+                        let items = s.items.iter().map(|(name, count)| match count {
+                            1 => name.clone(),
+                            n => format!("{} \u{00D7}{}", name, n),
+                        });
+                        let counters = s.counters.iter().map(|(name, value)| format!("{}: {}", name, value));
+                        let flags = s.flags.iter().map(|name| format!("\u{2713} {}", name));
+                        items
+                            .chain(counters)
+                            .chain(flags)
+                            .map(|entry| view! { <li>{entry}</li> })
+                            .collect::<Vec<_>>()
+                    })}
+                </ul>
+            </Show>
+
+            <Show when=move || moderation.available()>
+                <button
+                    class="moderation-toggle"
+                    class:active=move || screen.get() == Some(ModerationScreen::Queue)
+                    on:click=move |_| moderation.toggle_screen(ModerationScreen::Queue)
+                >
+                    {move || format!("Moderation queue ({})", moderation_queue.with(|q| q.len()))}
+                </button>
+                <button
+                    class="moderation-toggle"
+                    class:active=move || screen.get() == Some(ModerationScreen::Reports)
+                    on:click=move |_| moderation.toggle_screen(ModerationScreen::Reports)
+                >
+                    {move || format!("Reported nodes ({})", reports.with(|r| r.len()))}
+                </button>
+                <Show when=move || moderation.can_ban()>
+                    <button
+                        class="moderation-toggle"
+                        class:active=move || screen.get() == Some(ModerationScreen::Users)
+                        on:click=move |_| moderation.toggle_screen(ModerationScreen::Users)
+                    >
+                        "Users"
+                    </button>
+                </Show>
+                <Show when=move || moderation.can_restore()>
+                    <button
+                        class="moderation-toggle"
+                        class:active=move || screen.get() == Some(ModerationScreen::Deleted)
+                        on:click=move |_| moderation.toggle_screen(ModerationScreen::Deleted)
+                    >
+                        "Deleted nodes"
+                    </button>
+                </Show>
+            </Show>
+
             <div class="llm-settings-section">
                 <label class="llm-enable-checkbox">
                     <input
@@ -168,7 +233,7 @@ pub fn Sidebar(
                                                             class:active=move || llm.config().get().model == model
                                                             on:click=move |_| llm.update_model(model2.clone())
                                                         >
-                                                            {m.split('/').last().unwrap_or(m)}
+                                                            {m.split('/').next_back().unwrap_or(m)}
                                                         </button>
                                                     }
                                                 }).collect::<Vec<_>>()}
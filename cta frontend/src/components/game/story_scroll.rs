@@ -1,11 +1,20 @@
 use leptos::prelude::*;
+use shared::chance::{Roll, RollResult};
+use shared::SubmissionStatus;
 use wasm_bindgen::prelude::*;
 
-use crate::api::newgrounds::get_session_id;
+use crate::api::session::credential;
 use crate::domain::adventure::AdventureNode;
 use crate::state::adventure::use_adventure_state;
+use crate::state::moderation::use_moderation_state;
+
+use super::branch_admin::{BranchDeletion, MoveBanner};
+use super::helpers::copy_to_clipboard;
+use super::link_banner::LinkBanner;
 
 use super::contribute_form::ContributeForm;
+use super::ending_screen::EndingScreen;
+use super::report_form::ReportForm;
 use super::ContributeMode;
 
 #[wasm_bindgen(inline_js = "
@@ -41,15 +50,25 @@ pub fn StoryScroll(
     current_parent_id: Memo<Option<String>>,
 ) -> impl IntoView {
     let state = use_adventure_state();
+    let moderation = use_moderation_state();
     let path = state.path();
     let show_contribute = state.show_contribute();
     let counts = state.descendant_counts();
     let graph = state.graph();
-    let ng_username = use_context::<RwSignal<Option<String>>>()
-        .expect("NG username signal must be provided by App");
+    let offline = state.offline();
+    let linking = state.linking();
+
+    // Choices here that lead elsewhere in the story
+    let current_links = Memo::new(move |_| {
+        current_parent_id
+            .get()
+            .map(|id| graph.with(|g| g.links_from(&id).into_iter().cloned().collect::<Vec<_>>()))
+            .unwrap_or_default()
+    });
 
     let at_root = Memo::new(move |_| path.get().is_empty());
     let (fullscreen, set_fullscreen) = signal(false);
+    let (link_copied, set_link_copied) = signal(false);
 
     let fs_closure = Closure::new(move |is_fs: bool| {
         set_fullscreen.set(is_fs);
@@ -59,6 +78,8 @@ pub fn StoryScroll(
 
     view! {
         <div class="story-scroll">
+            <MoveBanner />
+            <LinkBanner />
             <Show when=move || !at_root.get()>
                 <button
                     class="back-to-root-btn"
@@ -68,6 +89,22 @@ pub fn StoryScroll(
                 </button>
             </Show>
 
+            <Show when=move || !at_root.get()>
+                <button
+                    class="share-btn"
+                    title="Copy a link to this point in the story. Anyone opening it gets the same rolls"
+                    on:click=move |_| {
+                        if let Some(url) = state.share_url() {
+                            let _ = copy_to_clipboard(&url);
+                            set_link_copied.set(true);
+                            gloo_timers::callback::Timeout::new(2_000, move || set_link_copied.set(false)).forget();
+                        }
+                    }
+                >
+                    {move || if link_copied.get() { "Copied!" } else { "Copy link" }}
+                </button>
+            </Show>
+
             <button
                 class="fullscreen-btn"
                 title=move || if fullscreen.get() { "Exit fullscreen" } else { "Enter fullscreen" }
@@ -90,30 +127,28 @@ pub fn StoryScroll(
                 key={|(i, unit)| (*i, unit.id.clone())}
                 children={move |(i, unit): (usize, AdventureNode)| {
                     let is_last = move || i == path.get().len().saturating_sub(1);
-                    let segment_id = format!("segment-{}", unit.id);
+                    let segment_id = format!("segment-{}", i);
 
                     let unit_id = unit.id.clone();
-                    let unit_created_by = unit.created_by.clone();
-
-                    let can_delete = move || {
-                        let current_user = ng_username.get();
-                        let no_children = graph.get().children_ids(&unit_id).is_empty();
-
-                        if current_user.as_deref() == Some("comicstosteal") {
-                            return no_children;
-                        }
-
-                        if !is_last() {
-                            return false;
-                        }
-                        match (&current_user, &unit_created_by) {
-                            (Some(user), Some(creator)) if user == creator => {}
-                            _ => return false,
-                        }
-                        no_children
-                    };
+                    let can_delete = move || !offline.get() && state.node_capabilities(&unit_id).delete;
+                    let unit_id = unit.id.clone();
+                    let can_report = move || !offline.get() && state.node_capabilities(&unit_id).report;
+                    let (reporting, set_reporting) = signal(false);
+                    let report_node_id = unit.id.clone();
 
                     let delete_node_id = unit.id.clone();
+                    let branch_node_id = unit.id.clone();
+                    let preview_node_id = unit.id.clone();
+                    let can_restructure = move || !offline.get() && moderation.can_restructure();
+                    let move_node = unit.clone();
+                    let target_id = unit.id.clone();
+                    let moving = moderation.moving();
+                    let unit_id = unit.id.clone();
+                    let can_move_here = move || {
+                        can_restructure() && moving.with(|m| m.as_ref().is_some_and(|n| n.id != unit_id))
+                    };
+                    let link_target_id = unit.id.clone();
+                    let can_link_here = move || !offline.get() && linking.with(|d| d.is_some());
 
                     view! {
                         <article
@@ -140,7 +175,7 @@ pub fn StoryScroll(
                                                 class="delete-btn-inline"
                                                 title="Delete this node"
                                                 on:click=move |_| {
-                                                    state.delete_node(delete_id.clone(), get_session_id())
+                                                    state.delete_node(delete_id.clone(), credential())
                                                 }
                                             >
                                                 "Delete"
@@ -148,8 +183,91 @@ pub fn StoryScroll(
                                         }
                                     }
                                 </Show>
+                                <Show when=can_restructure>
+                                    {
+                                        let preview_id = preview_node_id.clone();
+                                        let move_node = move_node.clone();
+                                        view! {
+                                            <button
+                                                class="delete-btn-inline"
+                                                title="Delete this and everything below it"
+                                                on:click=move |_| moderation.preview_subtree_deletion(preview_id.clone())
+                                            >
+                                                "Delete branch"
+                                            </button>
+                                            <button
+                                                class="delete-btn-inline"
+                                                title="Move this and everything below it somewhere else"
+                                                on:click=move |_| moving.set(Some(move_node.clone()))
+                                            >
+                                                "Move"
+                                            </button>
+                                        }
+                                    }
+                                </Show>
+                                <Show when=can_move_here>
+                                    {
+                                        let target_id = target_id.clone();
+                                        view! {
+                                            <button
+                                                class="delete-btn-inline"
+                                                title="Put the node being moved here, as an option after this"
+                                                on:click=move |_| moderation.move_to(Some(target_id.clone()))
+                                            >
+                                                "Move here"
+                                            </button>
+                                        }
+                                    }
+                                </Show>
+                                <Show when=can_link_here>
+                                    {
+                                        let target_id = link_target_id.clone();
+                                        view! {
+                                            <button
+                                                class="delete-btn-inline"
+                                                title="Make the new link lead here"
+                                                on:click=move |_| state.link_to(target_id.clone())
+                                            >
+                                                "Link here"
+                                            </button>
+                                        }
+                                    }
+                                </Show>
+                                <Show when=move || can_report() && !reporting.get()>
+                                    <button
+                                        class="delete-btn-inline"
+                                        title="Report this to moderators"
+                                        on:click=move |_| set_reporting.set(true)
+                                    >
+                                        "Report"
+                                    </button>
+                                </Show>
                             </div>
+                            <BranchDeletion node_id=branch_node_id.clone() />
+                            <Show when=move || reporting.get()>
+                                <ReportForm
+                                    node_id=report_node_id.clone()
+                                    on_close=Callback::new(move |()| set_reporting.set(false))
+                                />
+                            </Show>
                             <p class="story-text">{unit.story_text.clone()}</p>
+                            {
+                                let unit_id = unit.id.clone();
+                                move || {
+                                    graph
+                                        .with(|g| g.mechanics(&unit_id).map(|m| m.effects.clone()))
+                                        .filter(|effects| !effects.is_empty())
+                                        .map(|effects| view! { <p class="segment-effects">{effects}</p> })
+                                }
+                            }
+                            {
+                                let unit_id = unit.id.clone();
+                                move || {
+                                    graph.with(|g| g.ending(&unit_id).map(|e| e.ending)).map(|ending| view! {
+                                        <p class=format!("segment-ending ending-{}", ending.as_str())>{ending.label()}</p>
+                                    })
+                                }
+                            }
                         </article>
                     }
                 }}
@@ -158,19 +276,81 @@ pub fn StoryScroll(
             // Options for current segment
             {move || {
                 let opts = current_options.get();
-                let parent_id = current_parent_id.get().unwrap_or_default();
+                let links = current_links.get();
+                let parent_id = current_parent_id.get();
+                let loaded = graph.with(|g| g.children_loaded(parent_id.as_deref()));
+                let parent_id = parent_id.unwrap_or_default();
                 let is_root = at_root.get();
+
+                if !loaded {
+                    // Still fetching what comes next
+                    return view! {
+                        <div id="story-end" class="loading">
+                            <span class="loading-dots">"..."</span>
+                        </div>
+                    }.into_any();
+                }
                 let contribute_mode = if is_root {
                     ContributeMode::NewStory
                 } else {
                     ContributeMode::DeadEnd
                 };
 
-                if opts.is_empty() {
+                // An ending nobody has written past doesn't ask for more
+                if opts.is_empty() && links.is_empty() {
+                    if let Some(ending) = graph.with(|g| g.ending(&parent_id).cloned()) {
+                        return view! { <EndingScreen ending=ending /> }.into_any();
+                    }
+                }
+
+                // What comes next at a random branch is rolled for, not chosen
+                if let Some(roll) = graph.with(|g| g.roll(&parent_id).cloned()) {
+                    let result = state.current_roll();
+                    let outcome = result
+                        .as_ref()
+                        .and_then(|r| r.outcome.as_ref())
+                        .and_then(|id| graph.with(|g| g.node(id).cloned()));
+                    let Some(outcome) = outcome else {
+                        return view! {
+                            <div id="story-end">
+                                <p class="roll-summary">"This is left to chance, but nobody has written an outcome yet."</p>
+                                <ContributeForm parent_id=parent_id mode=contribute_mode />
+                            </div>
+                        }.into_any();
+                    };
+                    let summary = result.map(|r| describe_roll(&roll, &r)).unwrap_or_default();
+                    let outcome_text = outcome.choice_text.clone();
+                    let contribute_parent = Some(parent_id.clone());
+                    return view! {
+                        <div id="story-end">
+                            <div class="roll-result">
+                                <p class="roll-summary">{summary}</p>
+                                <button class="option-btn" on:click=move |_| state.choose(&outcome)>
+                                    <span class="option-text">{outcome_text}</span>
+                                </button>
+                            </div>
+                            <Show when=move || state.can_contribute(contribute_parent.as_deref())>
+                                <button class="link-start-btn" on:click=move |_| state.toggle_contribute()>
+                                    {move || if show_contribute.get() { "Cancel" } else { "Write another possible outcome" }}
+                                </button>
+                            </Show>
+                            <Show when=move || show_contribute.get()>
+                                <ContributeForm
+                                    parent_id=parent_id.clone()
+                                    mode=ContributeMode::Branch
+                                    on_cancel=Callback::new(move |_| state.close_contribute())
+                                />
+                            </Show>
+                        </div>
+                    }.into_any();
+                }
+
+                if opts.is_empty() && links.is_empty() {
                     // No options - show contribute form directly
                     view! {
                         <div id="story-end">
-                            <ContributeForm parent_id=parent_id mode=contribute_mode />
+                            <ContributeForm parent_id=parent_id.clone() mode=contribute_mode />
+                            <StartLinkButton parent_id=parent_id />
                         </div>
                     }.into_any()
                 } else {
@@ -179,6 +359,8 @@ pub fn StoryScroll(
                     } else {
                         ContributeMode::Branch
                     };
+                    let contribute_parent = (!parent_id.is_empty()).then(|| parent_id.clone());
+                    let link_parent = parent_id.clone();
 
                     // Has options - show them with "add your own" button
                     view! {
@@ -190,6 +372,10 @@ pub fn StoryScroll(
                                 {opts.into_iter().map(|opt| {
                                     let o = opt.clone();
                                     let opt_id = opt.id.clone();
+                                    let unmet = {
+                                        let opt_id = opt.id.clone();
+                                        Memo::new(move |_| state.unmet_condition(&opt_id))
+                                    };
                                     let count_label = move || {
                                         let n = counts.get().get(&opt_id).copied().unwrap_or(0);
                                         if n == 0 {
@@ -199,8 +385,16 @@ pub fn StoryScroll(
                                         }
                                     };
                                     view! {
-                                        <button class="option-btn" on:click=move |_| state.choose(&o)>
+                                        <button
+                                            class="option-btn"
+                                            class:locked=move || unmet.get().is_some()
+                                            disabled=move || unmet.get().is_some()
+                                            on:click=move |_| state.choose(&o)
+                                        >
                                             <span class="option-text">{opt.choice_text}</span>
+                                            {move || unmet.get().map(|condition| view! {
+                                                <span class="option-requires">{format!("Requires {}", condition)}</span>
+                                            })}
                                             {move || {
                                                 let label = count_label();
                                                 if label.is_empty() {
@@ -212,13 +406,61 @@ pub fn StoryScroll(
                                         </button>
                                     }
                                 }).collect::<Vec<_>>()}
-                                <button
-                                    class="option-btn add-option-btn"
-                                    on:click=move |_| state.toggle_contribute()
-                                >
-                                    {move || if show_contribute.get() { "Cancel" } else if is_root { "Start a new story" } else { "Add your own option" }}
-                                </button>
+                                {links.into_iter().map(|link| {
+                                    let leads_to = graph.with(|g| g.node(&link.target_id).map(|n| n.choice_text.clone()));
+                                    let link_id = link.id.clone();
+                                    let can_remove = {
+                                        let link_id = link_id.clone();
+                                        move || !offline.get() && state.link_capabilities(&link_id).remove
+                                    };
+                                    let l = link.clone();
+                                    let unmet = {
+                                        let target_id = link.target_id.clone();
+                                        Memo::new(move |_| state.unmet_condition(&target_id))
+                                    };
+                                    view! {
+                                        <div class="link-option-row">
+                                            <button
+                                                class="option-btn link-option-btn"
+                                                class:locked=move || unmet.get().is_some()
+                                                disabled=move || unmet.get().is_some()
+                                                on:click=move |_| state.follow_link(&l)
+                                            >
+                                                <span class="option-text">{link.choice_text}</span>
+                                                {move || unmet.get().map(|condition| view! {
+                                                    <span class="option-requires">{format!("Requires {}", condition)}</span>
+                                                })}
+                                                {leads_to.map(|text| view! {
+                                                    <span class="option-count" title="Where this leads">{format!("↪ {}", text)}</span>
+                                                })}
+                                            </button>
+                                            <Show when=can_remove.clone()>
+                                                {
+                                                    let link_id = link_id.clone();
+                                                    view! {
+                                                        <button
+                                                            class="delete-btn-inline"
+                                                            title="Remove this link"
+                                                            on:click=move |_| state.remove_link(link_id.clone())
+                                                        >
+                                                            "Remove"
+                                                        </button>
+                                                    }
+                                                }
+                                            </Show>
+                                        </div>
+                                    }
+                                }).collect::<Vec<_>>()}
+                                <Show when=move || state.can_contribute(contribute_parent.as_deref())>
+                                    <button
+                                        class="option-btn add-option-btn"
+                                        on:click=move |_| state.toggle_contribute()
+                                    >
+                                        {move || if show_contribute.get() { "Cancel" } else if is_root { "Start a new story" } else { "Add your own option" }}
+                                    </button>
+                                </Show>
                             </div>
+                            {(!is_root).then(|| view! { <StartLinkButton parent_id=link_parent /> })}
 
                             <Show when=move || show_contribute.get()>
                                 <div>
@@ -233,6 +475,62 @@ pub fn StoryScroll(
                     }.into_any()
                 }
             }}
+
+            // The reader's own additions here that other readers can't see yet
+            {move || {
+                let submissions = state.submissions_under(current_parent_id.get().as_deref());
+                (!submissions.is_empty()).then(|| view! {
+                    <div class="my-submissions">
+                        {submissions.into_iter().map(|submission| {
+                            let (class, status) = match submission.status {
+                                SubmissionStatus::Pending => ("submission pending", "Waiting for review".to_string()),
+                                SubmissionStatus::Rejected { reason } => {
+                                    ("submission rejected", format!("Rejected: {}", reason))
+                                }
+                            };
+                            view! {
+                                <div class=class>
+                                    <span class="option-text">{submission.node.choice_text}</span>
+                                    <span class="submission-status">{status}</span>
+                                </div>
+                            }
+                        }).collect::<Vec<_>>()}
+                    </div>
+                })
+            }}
         </div>
     }
 }
+
+/// Lets the reader add a choice after `parent_id` that leads to a node written elsewhere.
+#[component]
+fn StartLinkButton(parent_id: String) -> impl IntoView {
+    let state = use_adventure_state();
+    let linking = state.linking();
+    let parent_id = (!parent_id.is_empty()).then_some(parent_id);
+    let can_link = move || {
+        parent_id.is_some() && linking.with(|d| d.is_none()) && state.can_contribute(parent_id.as_deref())
+    };
+
+    view! {
+        <Show when=can_link>
+            <button class="link-start-btn" on:click=move |_| state.start_link()>
+                "Link to an existing node"
+            </button>
+        </Show>
+    }
+}
+
+/// What a roll came to, e.g. "2d6 + lockpicking: 4 + 5 + 2 = 11".
+fn describe_roll(roll: &Roll, result: &RollResult) -> String {
+    match roll {
+        Roll::Weighted => format!("\u{1F3B2} Left to chance: drew {}", result.total),
+        Roll::Dice { bonus, .. } => {
+            let mut parts: Vec<String> = result.rolls.iter().map(|r| r.to_string()).collect();
+            if !bonus.is_empty() {
+                parts.push(format!("{} ({})", result.bonus, bonus));
+            }
+            format!("\u{1F3B2} {}: {} = {}", roll, parts.join(" + "), result.total)
+        }
+    }
+}
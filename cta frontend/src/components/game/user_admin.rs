@@ -0,0 +1,122 @@
+use leptos::prelude::*;
+use shared::PurgeMode;
+
+use crate::state::moderation::use_moderation_state;
+
+/// Admin tools for dealing with a user: bans, shadowbans and purging what they wrote.
+#[component]
+pub fn UserAdmin() -> impl IntoView {
+    let moderation = use_moderation_state();
+    let busy = moderation.busy();
+    let error = moderation.error();
+    let done = moderation.done();
+    let plan = moderation.purge_plan();
+    let (username, set_username) = signal(String::new());
+    let (mode, set_mode) = signal(PurgeMode::Reparent);
+
+    let target = move || username.get().trim().to_string();
+    let no_target = move || busy.get() || username.with(|u| u.trim().is_empty());
+    // A preview only stands for the user and mode it was made with
+    let forget_plan = move || plan.set(None);
+
+    view! {
+        <div class="moderation-queue">
+            <div class="moderation-header">
+                <h2>"Users"</h2>
+                <button class="cancel-btn" on:click=move |_| moderation.close()>"Back to the story"</button>
+            </div>
+
+            {move || error.get().map(|message| view! { <p class="form-error">{message}</p> })}
+            {move || done.get().map(|message| view! { <p class="hint">{message}</p> })}
+
+            <div class="moderation-actions">
+                <input
+                    type="text"
+                    class="moderation-reason"
+                    placeholder="Username"
+                    prop:value=move || username.get()
+                    on:input=move |ev| {
+                        set_username.set(event_target_value(&ev));
+                        forget_plan();
+                    }
+                />
+                <button class="reject-btn" disabled=no_target on:click=move |_| moderation.ban(target(), false)>
+                    "Ban"
+                </button>
+                <button
+                    class="reject-btn"
+                    title="They can keep writing, but nobody else sees it"
+                    disabled=no_target
+                    on:click=move |_| moderation.ban(target(), true)
+                >
+                    "Shadowban"
+                </button>
+                <button class="restart-btn" disabled=no_target on:click=move |_| moderation.unban(target())>
+                    "Unban"
+                </button>
+            </div>
+
+            <h3>"Purge everything they wrote"</h3>
+            <div class="moderation-actions">
+                <label>
+                    <input
+                        type="radio"
+                        name="purge-mode"
+                        prop:checked=move || mode.get() == PurgeMode::Reparent
+                        on:change=move |_| {
+                            set_mode.set(PurgeMode::Reparent);
+                            forget_plan();
+                        }
+                    />
+                    " Keep other authors' continuations"
+                </label>
+                <label>
+                    <input
+                        type="radio"
+                        name="purge-mode"
+                        prop:checked=move || mode.get() == PurgeMode::Cascade
+                        on:change=move |_| {
+                            set_mode.set(PurgeMode::Cascade);
+                            forget_plan();
+                        }
+                    />
+                    " Delete everything below their nodes"
+                </label>
+                <button
+                    class="restart-btn"
+                    disabled=no_target
+                    on:click=move |_| moderation.purge(target(), mode.get(), true)
+                >
+                    "Preview"
+                </button>
+            </div>
+
+            {move || plan.get().filter(|p| !p.deleted.is_empty()).map(|p| view! {
+                <div class="purge-plan">
+                    <p>
+                        {format!("{} nodes deleted", p.deleted.len())}
+                        {(!p.reparented.is_empty())
+                            .then(|| format!(", {} moved up to the nearest surviving node", p.reparented.len()))}
+                    </p>
+                    <ul class="report-list">
+                        {p.subtrees.into_iter().map(|subtree| view! {
+                            <li>
+                                <strong>{subtree.root.choice_text}</strong>
+                                {format!(": {} nodes", subtree.deleted)}
+                                {(!subtree.other_authors.is_empty())
+                                    .then(|| format!(", also by {}", subtree.other_authors.join(", ")))}
+                            </li>
+                        }).collect::<Vec<_>>()}
+                    </ul>
+                    <button
+                        class="reject-btn"
+                        disabled=move || busy.get()
+                        on:click=move |_| moderation.purge(target(), mode.get_untracked(), false)
+                    >
+                        "Confirm purge"
+                    </button>
+                </div>
+            })}
+        </div>
+    }
+}
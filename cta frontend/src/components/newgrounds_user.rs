@@ -1,13 +1,22 @@
 use leptos::prelude::*;
+use shared::Role;
 
 #[component]
 pub fn NewgroundsUser() -> impl IntoView {
     let username = use_context::<RwSignal<Option<String>>>()
         .expect("NG username signal must be provided by App");
+    let role = use_context::<RwSignal<Option<Role>>>()
+        .expect("User role signal must be provided by App");
+
+    // Only roles that differ from an ordinary writer are worth pointing out
+    let role_label = move || role.get().filter(|r| *r != Role::DEFAULT).map(|r| r.as_str().replace('_', " "));
 
     view! {
         <Show when=move || username.get().is_some()>
-            <span class="ng-user">{move || username.get().unwrap_or_default()}</span>
+            <span class="ng-user">
+                {move || username.get().unwrap_or_default()}
+                {move || role_label().map(|label| view! { <span class="ng-role">{label}</span> })}
+            </span>
         </Show>
     }
 }
@@ -4,3 +4,10 @@ pub const API_BASE: &str = match option_env!("API_BASE") {
     Some(url) => url,
     None => "http://localhost:8080",
 };
+
+/// Identifies this build in requests to the backend. Set via `CTA_BUILD_ID` at build time,
+/// otherwise the crate version.
+pub const BUILD_ID: &str = match option_env!("CTA_BUILD_ID") {
+    Some(id) => id,
+    None => env!("CARGO_PKG_VERSION"),
+};
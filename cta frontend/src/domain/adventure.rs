@@ -1,8 +1,10 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use serde::{Deserialize, Serialize};
 
-pub use shared::AdventureNode;
+use shared::chance::{ChanceTable, Roll};
+use shared::mechanics::{self, GameState, NodeMechanics};
+pub use shared::{AdventureNode, Ending, NodeDetails, NodeEnding, NodeLink, NodeRules};
 
 /// Indexed adventure graph with fast lookups.
 #[derive(Clone, Default, Serialize, Deserialize)]
@@ -10,25 +12,253 @@ pub struct AdventureGraph {
     nodes: HashMap<String, AdventureNode>,
     children_by_parent: HashMap<String, Vec<String>>,
     root_ids: Vec<String>,
+    /// Nodes whose complete list of children has been fetched.
+    #[serde(default)]
+    children_loaded: HashSet<String>,
+    /// Whether `root_ids` lists every root on the server.
+    #[serde(default)]
+    roots_loaded: bool,
+    /// Choices leading to nodes written elsewhere, by ID. Their targets may not be loaded yet.
+    #[serde(default)]
+    links: HashMap<String, NodeLink>,
+    /// Conditions and effects of the nodes that have any, by node ID.
+    #[serde(default)]
+    mechanics: HashMap<String, NodeMechanics>,
+    /// Which nodes are random branches, and what their outcomes are worth.
+    #[serde(default)]
+    chance: ChanceTable,
+    /// Nodes that end the story, by node ID.
+    #[serde(default)]
+    endings: HashMap<String, NodeEnding>,
 }
 
 impl AdventureGraph {
-    pub fn from_nodes(nodes: impl IntoIterator<Item = AdventureNode>) -> Self {
-        let mut graph = AdventureGraph::default();
+    /// Adds `node`, or updates it if it's already known, moving it when its parent changed.
+    pub fn insert_node(&mut self, node: AdventureNode) {
+        if let Some(existing) = self.nodes.get(&node.id) {
+            if existing.parent_id == node.parent_id {
+                self.nodes.insert(node.id.clone(), node);
+                return;
+            }
+            let existing = existing.clone();
+            self.detach(&existing);
+        }
+        let node_id = node.id.clone();
+        if let Some(parent_id) = node.parent_id.clone() {
+            self.children_by_parent
+                .entry(parent_id)
+                .or_default()
+                .push(node_id.clone());
+        } else {
+            self.root_ids.push(node_id.clone());
+        }
+        self.nodes.insert(node_id, node);
+    }
+
+    pub fn remove_node(&mut self, id: &str) -> Option<AdventureNode> {
+        let node = self.nodes.remove(id)?;
+        self.children_loaded.remove(id);
+        self.endings.remove(id);
+        // The server stops offering links with a missing end too
+        self.links.retain(|_, link| link.parent_id != id && link.target_id != id);
+        self.detach(&node);
+        Some(node)
+    }
+
+    /// Takes `node` out of its parent's children (or the roots).
+    fn detach(&mut self, node: &AdventureNode) {
+        match &node.parent_id {
+            Some(parent_id) => {
+                if let Some(siblings) = self.children_by_parent.get_mut(parent_id) {
+                    siblings.retain(|sibling| *sibling != node.id);
+                }
+            }
+            None => self.root_ids.retain(|root| *root != node.id),
+        }
+    }
+
+    /// Merges the reply to a `RequestSubtree { root_id, depth }`, recording which nodes
+    /// now have all their children present.
+    pub fn insert_subtree(&mut self, root_id: Option<&str>, depth: u32, nodes: Vec<AdventureNode>) {
         for node in nodes {
-            let node_id = node.id.clone();
-            if let Some(parent_id) = node.parent_id.clone() {
-                graph
-                    .children_by_parent
-                    .entry(parent_id)
-                    .or_default()
-                    .push(node_id.clone());
-            } else {
-                graph.root_ids.push(node_id.clone());
+            self.insert_node(node);
+        }
+
+        let mut level = match root_id {
+            Some(id) => vec![id.to_string()],
+            None => {
+                self.roots_loaded = true;
+                self.root_ids.clone()
+            }
+        };
+        // Every level above the deepest one came back with all its children
+        for _ in 0..depth {
+            let mut next = Vec::new();
+            for id in level {
+                next.extend(self.children_ids(&id).iter().cloned());
+                self.children_loaded.insert(id);
+            }
+            level = next;
+        }
+    }
+
+    /// Records that `id` has no children we haven't seen, e.g. because it was just created.
+    pub fn mark_children_loaded(&mut self, id: &str) {
+        self.children_loaded.insert(id.to_string());
+    }
+
+    /// Whether the options below `parent_id` (or the roots, for `None`) are all present.
+    pub fn children_loaded(&self, parent_id: Option<&str>) -> bool {
+        match parent_id {
+            Some(id) => self.children_loaded.contains(id),
+            None => self.roots_loaded,
+        }
+    }
+
+    /// Applies an incremental sync. Safe to repeat: known nodes and unknown removals are ignored.
+    /// Nodes also come back when they're moved, restored or shown again, with children
+    /// from before the cursor, so only new nodes without any count as fully loaded.
+    pub fn apply_changes(&mut self, added: Vec<AdventureNode>, removed: &[String], has_children: impl Fn(&str) -> bool) {
+        for node in added {
+            if self.node(&node.id).is_none() && !has_children(&node.id) {
+                self.mark_children_loaded(&node.id);
+            }
+            self.insert_node(node);
+        }
+        for id in removed {
+            self.remove_node(id);
+        }
+    }
+
+    /// Replaces the links and rules of the nodes `details` is for with the server's.
+    pub fn apply_details(&mut self, details: NodeDetails) {
+        let node_ids: HashSet<&String> = details.node_ids.iter().collect();
+        self.links.retain(|_, link| !node_ids.contains(&link.parent_id));
+        self.mechanics.retain(|id, _| !node_ids.contains(id));
+        self.chance.branches.retain(|id, _| !node_ids.contains(id));
+        self.chance.outcomes.retain(|id, _| !node_ids.contains(id));
+        self.endings.retain(|id, _| !node_ids.contains(id));
+
+        for link in details.links {
+            self.insert_link(link);
+        }
+        for m in details.mechanics {
+            self.set_mechanics(m);
+        }
+        self.chance.branches.extend(details.chance.branches);
+        self.chance.outcomes.extend(details.chance.outcomes);
+        for ending in details.endings {
+            self.set_ending(ending);
+        }
+    }
+
+    pub fn insert_link(&mut self, link: NodeLink) {
+        self.links.insert(link.id.clone(), link);
+    }
+
+    pub fn remove_link(&mut self, id: &str) {
+        self.links.remove(id);
+    }
+
+    /// Links offered after `parent_id`, oldest first.
+    pub fn links_from(&self, parent_id: &str) -> Vec<&NodeLink> {
+        let mut links: Vec<&NodeLink> = self.links.values().filter(|l| l.parent_id == parent_id).collect();
+        links.sort_by(|a, b| a.id.cmp(&b.id));
+        links
+    }
+
+    /// Whether a reader at `from` (or choosing a story, for `None`) can reach `node` in one
+    /// step, as one of its options or through a link.
+    pub fn leads_to(&self, from: Option<&str>, node: &AdventureNode) -> bool {
+        node.parent_id.as_deref() == from
+            || from.is_some_and(|from| self.links.values().any(|l| l.parent_id == from && l.target_id == node.id))
+    }
+
+    /// Sets one node's mechanics, or clears them when both are empty.
+    pub fn set_mechanics(&mut self, m: NodeMechanics) {
+        if m.is_empty() {
+            self.mechanics.remove(&m.node_id);
+        } else {
+            self.mechanics.insert(m.node_id.clone(), m);
+        }
+    }
+
+    pub fn mechanics(&self, node_id: &str) -> Option<&NodeMechanics> {
+        self.mechanics.get(node_id)
+    }
+
+    pub fn set_roll(&mut self, node_id: String, roll: Option<Roll>) {
+        match roll {
+            Some(roll) => self.chance.branches.insert(node_id, roll),
+            None => self.chance.branches.remove(&node_id),
+        };
+    }
+
+    pub fn set_outcome_value(&mut self, node_id: String, value: Option<u32>) {
+        match value {
+            Some(value) => self.chance.outcomes.insert(node_id, value),
+            None => self.chance.outcomes.remove(&node_id),
+        };
+    }
+
+    /// How `node_id`'s outcome is picked, if it's a random branch.
+    pub fn roll(&self, node_id: &str) -> Option<&Roll> {
+        self.chance.branches.get(node_id)
+    }
+
+    /// Replaces the endings of the story starting at `root_id` with the server's list.
+    pub fn set_story_endings(&mut self, root_id: &str, endings: Vec<NodeEnding>) {
+        self.endings.retain(|_, ending| ending.root_id != root_id);
+        for ending in endings {
+            self.set_ending(ending);
+        }
+    }
+
+    pub fn set_ending(&mut self, ending: NodeEnding) {
+        self.endings.insert(ending.node_id.clone(), ending);
+    }
+
+    pub fn clear_ending(&mut self, node_id: &str) {
+        self.endings.remove(node_id);
+    }
+
+    /// How `node_id` ends the story, if it does.
+    pub fn ending(&self, node_id: &str) -> Option<&NodeEnding> {
+        self.endings.get(node_id)
+    }
+
+    /// Every ending of the story starting at `root_id`, loaded or not, once they've been
+    /// fetched with `set_story_endings`.
+    pub fn endings_in(&self, root_id: &str) -> Vec<&NodeEnding> {
+        self.endings.values().filter(|e| e.root_id == root_id).collect()
+    }
+
+    /// The outcomes below a random branch open to a reader in `state`, with their values.
+    pub fn outcomes(&self, node_id: &str, state: &GameState) -> Vec<(String, u32)> {
+        self.children_ids(node_id)
+            .iter()
+            .filter(|id| self.unmet_condition(id, state).is_none())
+            .map(|id| (id.clone(), self.chance.outcomes.get(id).copied().unwrap_or(0)))
+            .collect()
+    }
+
+    /// The reader's state after entering each node on `path` in turn.
+    pub fn state_along(&self, path: &[String]) -> GameState {
+        let mut state = GameState::default();
+        for id in path {
+            // The server only stores effects that parse
+            if let Some(effects) = self.mechanics(id).and_then(|m| mechanics::parse_effects(&m.effects).ok()) {
+                state.apply(&effects);
             }
-            graph.nodes.insert(node_id, node);
         }
-        graph
+        state
+    }
+
+    /// The condition readers in `state` fail to meet to choose `node_id`, if any.
+    pub fn unmet_condition(&self, node_id: &str, state: &GameState) -> Option<&str> {
+        let m = self.mechanics(node_id)?;
+        let condition = mechanics::parse_condition(&m.condition).ok()?;
+        (!condition.holds(state)).then_some(m.condition.as_str())
     }
 
     pub fn node(&self, id: &str) -> Option<&AdventureNode> {
@@ -16,6 +16,8 @@ use crate::pages::home::Home;
 use crate::components::newgrounds_user::NewgroundsUser;
 use crate::components::server_counter::ServerCounter;
 use crate::api::newgrounds::{check_session, get_session_id};
+use crate::api::session::{fetch_identity, login, stored_token};
+use shared::Role;
 
 /// An app router which renders the homepage and handles 404's
 #[component]
@@ -27,16 +29,44 @@ pub fn App() -> impl IntoView {
     let ng_username = RwSignal::new(None::<String>);
     provide_context(ng_username);
 
+    // Role of the signed-in user, once the server has told us
+    let user_role = RwSignal::new(None::<Role>);
+    provide_context(user_role);
+
+    let load_role = move || {
+        spawn_local(async move {
+            match fetch_identity().await {
+                Ok(info) => user_role.set(info.map(|i| i.role)),
+                Err(e) => log::error!("Failed to fetch role: {}", e),
+            }
+        });
+    };
+
     Effect::new(move |_| {
-        if let Some(session_id) = get_session_id() {
-            spawn_local(async move {
-                match check_session(&session_id).await {
-                    Ok(Some(user)) => ng_username.set(Some(user.name)),
-                    Ok(None) => {}
-                    Err(e) => log::error!("Newgrounds session check failed: {}", e),
+        let Some(session_id) = get_session_id() else {
+            // Not launched from Newgrounds; a token from an earlier visit still signs us in
+            if let Some(token) = stored_token() {
+                ng_username.set(Some(token.username));
+                load_role();
+            }
+            return;
+        };
+        spawn_local(async move {
+            // The backend verifies the session once and gives us a token for later requests
+            match login(session_id.clone()).await {
+                Ok(token) => {
+                    ng_username.set(Some(token.username));
+                    load_role();
+                    return;
                 }
-            });
-        }
+                Err(e) => log::error!("Login failed: {}", e),
+            }
+            match check_session(&session_id).await {
+                Ok(Some(user)) => ng_username.set(Some(user.name)),
+                Ok(None) => {}
+                Err(e) => log::error!("Newgrounds session check failed: {}", e),
+            }
+        });
     });
 
     view! {
@@ -1,6 +1,7 @@
 use crate::components::game::Game;
 use crate::state::adventure::provide_adventure_state;
 use crate::state::llm::provide_llm_state;
+use crate::state::moderation::provide_moderation_state;
 use leptos::prelude::*;
 
 /// Default Home Page
@@ -8,6 +9,7 @@ use leptos::prelude::*;
 pub fn Home() -> impl IntoView {
     provide_adventure_state();
     provide_llm_state();
+    provide_moderation_state();
 
     view! {
         <ErrorBoundary fallback=|errors| {
@@ -1,10 +1,87 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 use leptos::prelude::*;
 use leptos::task::spawn_local;
+use serde::{Deserialize, Serialize};
 
-use crate::api::adventure::{delete_node, fetch_adventure, fetch_descendant_counts, submit_node};
-use crate::domain::adventure::{AdventureGraph, AdventureNode};
+use shared::chance::{self, RollResult};
+use shared::mechanics::{GameState, NodeMechanics};
+use shared::{
+    Capabilities, LinkCapabilities, LiveEvent, NodeCapabilities, NodeEnding, NodeLink, ReportReason, Submission, UNDO_WINDOW_SECS,
+};
+
+use crate::api::adventure::{
+    create_link, delete_link, delete_node, fetch_ancestors, fetch_changes, fetch_descendant_counts,
+    fetch_detail_changes, fetch_node_details, fetch_own_unlisted_nodes, fetch_story_endings, fetch_subtree,
+    restore_node, submit_node, AdventureError, Submitted,
+};
+use crate::api::adventure::fetch_capabilities;
+use crate::api::moderation::{fetch_my_submissions, report_node};
+use crate::api::live::subscribe_live_events;
+use crate::api::session::credential;
+use crate::domain::adventure::{AdventureGraph, AdventureNode, NodeRules};
+
+/// How many levels below the current node are fetched ahead of the reader.
+const LOOKAHEAD: u32 = 2;
+
+const CACHE_KEY: &str = "cta_adventure_cache";
+
+/// The last synced story, kept in localStorage so it shows up instantly (or at all,
+/// when the server is down).
+#[derive(Serialize, Deserialize)]
+struct AdventureCache {
+    graph: AdventureGraph,
+    descendant_counts: HashMap<String, u64>,
+    sync_cursor: u64,
+}
+
+fn load_cache() -> Option<AdventureCache> {
+    web_sys::window()
+        .and_then(|w| w.local_storage().ok().flatten())
+        .and_then(|storage| storage.get_item(CACHE_KEY).ok().flatten())
+        .and_then(|json| serde_json::from_str(&json).ok())
+}
+
+fn save_cache(cache: &AdventureCache) {
+    if let Some(storage) = web_sys::window().and_then(|w| w.local_storage().ok().flatten()) {
+        if let Ok(json) = serde_json::to_string(cache) {
+            let _ = storage.set_item(CACHE_KEY, &json);
+        }
+    }
+}
+
+const ENDINGS_KEY: &str = "cta_discovered_endings";
+
+/// The endings the reader has reached in each story (by root ID), kept across visits.
+fn load_discovered_endings() -> HashMap<String, BTreeSet<String>> {
+    web_sys::window()
+        .and_then(|w| w.local_storage().ok().flatten())
+        .and_then(|storage| storage.get_item(ENDINGS_KEY).ok().flatten())
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+fn save_discovered_endings(discovered: &HashMap<String, BTreeSet<String>>) {
+    if let Some(storage) = web_sys::window().and_then(|w| w.local_storage().ok().flatten()) {
+        if let Ok(json) = serde_json::to_string(discovered) {
+            let _ = storage.set_item(ENDINGS_KEY, &json);
+        }
+    }
+}
+
+/// A value from the page's query string.
+fn query_param(key: &str) -> Option<String> {
+    let search = web_sys::window()?.location().search().ok()?;
+    search.trim_start_matches('?').split('&').find_map(|pair| {
+        let (k, value) = pair.split_once('=')?;
+        (k == key).then(|| value.to_string())
+    })
+}
+
+/// A fresh seed for a new playthrough's rolls.
+fn new_seed() -> u64 {
+    (js_sys::Math::random() * 2f64.powi(53)) as u64
+}
 
 #[derive(Clone)]
 pub enum LoadState {
@@ -13,6 +90,17 @@ pub enum LoadState {
     Error(String),
 }
 
+/// A link being made: where its choice is offered, and where the reader was when they
+/// went looking for the node it leads to.
+#[derive(Clone)]
+pub struct LinkDraft {
+    pub parent_id: String,
+    pub choice_text: String,
+    /// Why the server turned the link down, last time.
+    pub error: Option<String>,
+    return_path: Vec<String>,
+}
+
 #[derive(Clone, Copy)]
 pub struct AdventureState {
     graph: RwSignal<AdventureGraph>,
@@ -20,18 +108,107 @@ pub struct AdventureState {
     load_state: RwSignal<LoadState>,
     path: RwSignal<Vec<String>>,
     show_contribute: RwSignal<bool>,
+    /// Dismissible message about a failed action, shown above the story.
+    notice: RwSignal<Option<String>>,
+    /// Why the last submission failed, shown inside the contribute form.
+    submit_error: RwSignal<Option<AdventureError>>,
+    /// Set once the server rejects this build's protocol version.
+    outdated: RwSignal<bool>,
+    /// Where the next incremental sync picks up from.
+    sync_cursor: RwSignal<u64>,
+    /// Set while the server can't be reached; the cached story is shown read-only.
+    offline: RwSignal<bool>,
+    /// What the server says the reader may do along the current path.
+    capabilities: RwSignal<Capabilities>,
+    /// The reader's own submissions that are waiting for review or were rejected.
+    my_submissions: RwSignal<Vec<Submission>>,
+    /// Bumped whenever a submission is queued or reviewed or a node is reported, so the
+    /// moderation screens can refetch.
+    review_activity: RwSignal<u64>,
+    /// The node the reader just deleted, while they can still undo it.
+    undo: RwSignal<Option<AdventureNode>>,
+    /// Set while the reader is picking the node a new link leads to.
+    linking: RwSignal<Option<LinkDraft>>,
+    /// Flags, items and counters picked up along `path`.
+    game_state: Memo<GameState>,
+    /// Decides every roll on this playthrough, so going back can't change one and a
+    /// shared link (which carries it) rolls the same.
+    seed: RwSignal<u64>,
+    /// IDs of the ending nodes the reader has reached, by the root of their story.
+    discovered_endings: RwSignal<HashMap<String, BTreeSet<String>>>,
 }
 
 impl AdventureState {
     pub fn new() -> Self {
+        let graph = RwSignal::new(AdventureGraph::default());
+        let path = RwSignal::new(Vec::new());
         let state = Self {
-            graph: RwSignal::new(AdventureGraph::default()),
+            graph,
             descendant_counts: RwSignal::new(HashMap::new()),
             load_state: RwSignal::new(LoadState::Loading),
-            path: RwSignal::new(Vec::new()),
+            path,
             show_contribute: RwSignal::new(false),
+            notice: RwSignal::new(None),
+            submit_error: RwSignal::new(None),
+            outdated: RwSignal::new(false),
+            sync_cursor: RwSignal::new(0),
+            offline: RwSignal::new(false),
+            capabilities: RwSignal::new(Capabilities::default()),
+            my_submissions: RwSignal::new(Vec::new()),
+            review_activity: RwSignal::new(0),
+            undo: RwSignal::new(None),
+            linking: RwSignal::new(None),
+            game_state: Memo::new(move |_| graph.with(|g| path.with(|p| g.state_along(p)))),
+            seed: RwSignal::new(query_param("seed").and_then(|s| s.parse().ok()).unwrap_or_else(new_seed)),
+            discovered_endings: RwSignal::new(load_discovered_endings()),
         };
-        state.reload();
+        state.listen();
+
+        match load_cache() {
+            Some(cache) => {
+                // Show the saved story right away and catch up in the background
+                state.graph.set(cache.graph);
+                state.descendant_counts.set(cache.descendant_counts);
+                state.sync_cursor.set(cache.sync_cursor);
+                state.load_state.set(LoadState::Ready);
+                state.refresh();
+            }
+            None => state.reload(),
+        }
+        // Opened from a shared link
+        if let Some(node_id) = query_param("node") {
+            state.open_node(node_id);
+        }
+
+        // Reaching an ending counts as finding it, even if someone has continued past it
+        Effect::new(move |_| {
+            let reached = path
+                .with(|p| p.last().cloned())
+                .and_then(|id| graph.with(|g| g.ending(&id).cloned()));
+            let Some(reached) = reached else {
+                return;
+            };
+            let known = state
+                .discovered_endings
+                .with_untracked(|d| d.get(&reached.root_id).is_some_and(|found| found.contains(&reached.node_id)));
+            if !known {
+                state.discovered_endings.update(|d| {
+                    d.entry(reached.root_id).or_default().insert(reached.node_id);
+                    save_discovered_endings(d);
+                });
+            }
+        });
+
+        Effect::new(move |_| {
+            if matches!(state.load_state.get(), LoadState::Ready) {
+                save_cache(&AdventureCache {
+                    graph: state.graph.get(),
+                    descendant_counts: state.descendant_counts.get(),
+                    sync_cursor: state.sync_cursor.get(),
+                });
+            }
+        });
+
         state
     }
 
@@ -55,41 +232,493 @@ impl AdventureState {
         self.show_contribute
     }
 
-    pub fn reload(&self) {
-        let graph = self.graph;
-        let counts = self.descendant_counts;
-        let load_state = self.load_state;
-        let path = self.path;
-        let show_contribute = self.show_contribute;
+    pub fn notice(&self) -> RwSignal<Option<String>> {
+        self.notice
+    }
+
+    pub fn undo(&self) -> RwSignal<Option<AdventureNode>> {
+        self.undo
+    }
+
+    pub fn linking(&self) -> RwSignal<Option<LinkDraft>> {
+        self.linking
+    }
+
+    pub fn game_state(&self) -> Memo<GameState> {
+        self.game_state
+    }
+
+    /// How chance plays out at the end of the path, if the reader is at a random branch.
+    pub fn current_roll(&self) -> Option<RollResult> {
+        let seed = self.seed.get();
+        let state = self.game_state.get();
+        self.path.with(|p| {
+            let (node_id, before) = p.split_last()?;
+            self.graph.with(|g| {
+                let roll = g.roll(node_id)?;
+                // Looping back to a branch rolls afresh
+                let visit = before.iter().filter(|id| *id == node_id).count();
+                let outcomes = g.outcomes(node_id, &state);
+                Some(chance::roll(roll, chance::seed_for(seed, node_id, visit), &outcomes, &state))
+            })
+        })
+    }
+
+    /// A link to where the reader is now that rolls the same way for whoever opens it.
+    pub fn share_url(&self) -> Option<String> {
+        let node_id = self.path.with_untracked(|p| p.last().cloned())?;
+        let location = web_sys::window()?.location();
+        let base = format!("{}{}", location.origin().ok()?, location.pathname().ok()?);
+        Some(format!("{}?node={}&seed={}", base, node_id, self.seed.get_untracked()))
+    }
+
+    /// The endings of the story starting at `root_id` the reader has found, that are still endings.
+    pub fn discovered_endings(&self, root_id: &str) -> Vec<NodeEnding> {
+        self.discovered_endings.with(|d| {
+            let Some(found) = d.get(root_id) else {
+                return Vec::new();
+            };
+            self.graph.with(|g| found.iter().filter_map(|id| g.ending(id).cloned()).collect())
+        })
+    }
+
+    /// The condition the reader doesn't meet to choose `node_id` from here, if any.
+    pub fn unmet_condition(&self, node_id: &str) -> Option<String> {
+        let state = self.game_state.get();
+        self.graph.with(|g| g.unmet_condition(node_id, &state).map(String::from))
+    }
+
+    pub fn submit_error(&self) -> RwSignal<Option<AdventureError>> {
+        self.submit_error
+    }
+
+    pub fn outdated(&self) -> RwSignal<bool> {
+        self.outdated
+    }
+
+    pub fn offline(&self) -> RwSignal<bool> {
+        self.offline
+    }
+
+    pub fn review_activity(&self) -> RwSignal<u64> {
+        self.review_activity
+    }
+
+    /// The reader's unpublished submissions below `parent_id`, or new stories for `None`.
+    pub fn submissions_under(&self, parent_id: Option<&str>) -> Vec<Submission> {
+        self.my_submissions.with(|subs| {
+            subs.iter()
+                .filter(|s| s.node.parent_id.as_deref() == parent_id)
+                .cloned()
+                .collect()
+        })
+    }
+
+    /// Fetches the reader's pending and rejected submissions; signed-out readers have none.
+    pub fn refresh_submissions(&self) {
+        let state = *self;
+        let Some(session_id) = credential() else {
+            state.my_submissions.set(Vec::new());
+            return;
+        };
 
-        load_state.set(LoadState::Loading);
         spawn_local(async move {
-            match fetch_adventure().await {
-                Ok(data) => {
-                    graph.set(data);
-                    path.set(Vec::new());
-                    show_contribute.set(false);
-                    // Fetch descendant counts in parallel-ish (after graph)
-                    if let Ok(c) = fetch_descendant_counts().await {
-                        counts.set(c);
+            match fetch_my_submissions(Some(session_id)).await {
+                Ok(submissions) => state.my_submissions.set(submissions),
+                Err(AdventureError::SignInRequired) => state.my_submissions.set(Vec::new()),
+                Err(error) => log::error!("Failed to fetch submissions: {}", error),
+            }
+        });
+    }
+
+    /// Merges in the reader's own nodes that the server keeps out of everyone else's story.
+    pub fn refresh_own_nodes(&self) {
+        let state = *self;
+        let Some(session_id) = credential() else {
+            return;
+        };
+
+        spawn_local(async move {
+            match fetch_own_unlisted_nodes(Some(session_id)).await {
+                Ok(nodes) if !nodes.is_empty() => state.graph.update(|g| {
+                    for node in nodes {
+                        g.mark_children_loaded(&node.id);
+                        g.insert_node(node);
                     }
-                    load_state.set(LoadState::Ready);
+                }),
+                Ok(_) | Err(AdventureError::SignInRequired) => {}
+                Err(error) => log::error!("Failed to fetch own nodes: {}", error),
+            }
+        });
+    }
+
+    /// Allowed actions on `node_id`; everything is off until the server says otherwise.
+    pub fn node_capabilities(&self, node_id: &str) -> NodeCapabilities {
+        self.capabilities
+            .with(|c| c.nodes.get(node_id).copied())
+            .unwrap_or_default()
+    }
+
+    /// Allowed actions on the link `link_id`, off until the server says otherwise.
+    pub fn link_capabilities(&self, link_id: &str) -> LinkCapabilities {
+        self.capabilities
+            .with(|c| c.links.get(link_id).copied())
+            .unwrap_or_default()
+    }
+
+    /// Whether the reader may add an option below `parent_id`, or a new story for `None`.
+    pub fn can_contribute(&self, parent_id: Option<&str>) -> bool {
+        match parent_id {
+            Some(id) => self.node_capabilities(id).branch,
+            None => self.capabilities.with(|c| c.new_story),
+        }
+    }
+
+    /// Asks the server what the reader may do to the nodes on the current path.
+    pub fn refresh_capabilities(&self) {
+        let state = *self;
+        let node_ids = self.path.get_untracked();
+
+        spawn_local(async move {
+            match fetch_capabilities(node_ids.clone(), credential()).await {
+                // Drop answers for a path the reader has already left
+                Ok(capabilities) if state.path.with_untracked(|p| *p == node_ids) => {
+                    state.capabilities.set(capabilities);
                 }
+                Ok(_) => {}
+                Err(error) => log::error!("Failed to fetch capabilities: {}", error),
+            }
+        });
+    }
+
+    pub fn dismiss_notice(&self) {
+        self.notice.set(None);
+    }
+
+    pub fn reload(&self) {
+        let state = *self;
+
+        self.load_state.set(LoadState::Loading);
+        self.graph.set(AdventureGraph::default());
+        self.sync_cursor.set(0);
+        self.path.set(Vec::new());
+        self.show_contribute.set(false);
+        spawn_local(async move {
+            match state.load_roots().await {
+                Ok(()) => state.load_state.set(LoadState::Ready),
                 Err(error) => {
-                    load_state.set(LoadState::Error(error));
+                    state.check_outdated(&error);
+                    state.load_state.set(LoadState::Error(error.to_string()));
+                }
+            }
+        });
+    }
+
+    /// Fetches the first few levels of every story; deeper nodes load as the reader gets near them.
+    async fn load_roots(self) -> Result<(), AdventureError> {
+        let (nodes, cursor) = fetch_subtree(None, LOOKAHEAD).await?;
+        let node_ids = nodes.iter().map(|n| n.id.clone()).collect();
+        self.graph.update(|g| g.insert_subtree(None, LOOKAHEAD, nodes));
+        self.sync_cursor.set(cursor);
+
+        self.load_details(node_ids).await;
+        if let Ok(c) = fetch_descendant_counts().await {
+            self.descendant_counts.set(c);
+        }
+        Ok(())
+    }
+
+    /// Fetches the links and rules of `node_ids`, which arrive without them.
+    async fn load_details(self, node_ids: Vec<String>) {
+        if node_ids.is_empty() {
+            return;
+        }
+        match fetch_node_details(node_ids).await {
+            Ok(details) => self.graph.update(|g| g.apply_details(details)),
+            Err(error) => log::error!("Failed to load links and rules: {}", error),
+        }
+    }
+
+    /// Fetches every ending of the story starting at `root_id`, to count the ones left to find.
+    pub fn load_story_endings(&self, root_id: String) {
+        let state = *self;
+        spawn_local(async move {
+            match fetch_story_endings(root_id.clone()).await {
+                Ok(endings) => state.graph.update(|g| g.set_story_endings(&root_id, endings)),
+                Err(error) => log::error!("Failed to load endings: {}", error),
+            }
+        });
+    }
+
+    /// Makes sure the options below `node_id` are present, fetching them (and the level
+    /// after) if they aren't. Readers see a placeholder until they arrive.
+    fn ensure_loaded(&self, node_id: String) {
+        let (loaded, lookahead_loaded) = self.graph.with_untracked(|g| {
+            let loaded = g.children_loaded(Some(&node_id));
+            let lookahead_loaded = g
+                .children_ids(&node_id)
+                .iter()
+                .all(|id| g.children_loaded(Some(id)));
+            (loaded, lookahead_loaded)
+        });
+        if loaded && lookahead_loaded {
+            return;
+        }
+
+        let state = *self;
+        spawn_local(async move {
+            match fetch_subtree(Some(node_id.clone()), LOOKAHEAD).await {
+                Ok((nodes, _)) => {
+                    state.offline.set(false);
+                    let node_ids = nodes.iter().map(|n| n.id.clone()).collect();
+                    state.graph.update(|g| g.insert_subtree(Some(&node_id), LOOKAHEAD, nodes));
+                    state.load_details(node_ids).await;
+                }
+                Err(error) => {
+                    log::error!("Failed to load options: {}", error);
+                    // Only bother the reader if they're stuck looking at the placeholder
+                    if !loaded {
+                        state.path.update(|p| {
+                            if let Some(i) = p.iter().position(|id| *id == node_id) {
+                                p.truncate(i);
+                            }
+                        });
+                        state.report(error);
+                    }
+                }
+            }
+        });
+    }
+
+    /// Jumps straight to `node_id`, loading the path leading to it first.
+    pub fn open_node(&self, node_id: String) {
+        let state = *self;
+        spawn_local(async move {
+            match fetch_ancestors(node_id.clone()).await {
+                Ok(ancestors) => {
+                    let mut path: Vec<String> = ancestors.iter().map(|n| n.id.clone()).collect();
+                    // The rules along the way decide what the reader can do here
+                    let mut node_ids = path.clone();
+                    path.push(node_id.clone());
+                    state.graph.update(|g| {
+                        for node in ancestors {
+                            g.insert_node(node);
+                        }
+                    });
+                    // The node itself arrives with its subtree
+                    match fetch_subtree(Some(node_id.clone()), LOOKAHEAD).await {
+                        Ok((nodes, _)) => {
+                            node_ids.extend(nodes.iter().map(|n| n.id.clone()));
+                            state.graph.update(|g| g.insert_subtree(Some(&node_id), LOOKAHEAD, nodes));
+                            state.load_details(node_ids).await;
+                            state.path.set(path);
+                            state.show_contribute.set(false);
+                        }
+                        Err(error) => state.report(error),
+                    }
                 }
+                Err(error) => state.report(error),
             }
         });
     }
 
+    /// Pulls in whatever changed on the server since the last sync, then refreshes counts.
+    async fn sync(self) -> Result<(), AdventureError> {
+        let cursor = self.sync_cursor.get_untracked();
+        let changes = fetch_changes(cursor).await?;
+        self.offline.set(false);
+        // Counted after the changes, so they cover every node that came back with children
+        let counts = fetch_descendant_counts().await.ok();
+        self.graph.update(|g| {
+            g.apply_changes(changes.added, &changes.removed, |id| match &counts {
+                Some(counts) => counts.get(id).is_some_and(|&n| n > 0),
+                // Without counts, let the children be fetched when the reader gets there
+                None => true,
+            })
+        });
+        self.sync_cursor.set(changes.cursor);
+
+        // Trim the path back to the last node that still exists
+        let graph = self.graph;
+        self.path.update(|p| {
+            let keep = graph.with_untracked(|g| p.iter().take_while(|id| g.node(id).is_some()).count());
+            p.truncate(keep);
+        });
+
+        // Asked after the changes, so nothing changed in between is missed by the next sync
+        if let Ok(details) = fetch_detail_changes(cursor).await {
+            self.graph.update(|g| g.apply_details(details));
+        }
+        if let Some(counts) = counts {
+            self.descendant_counts.set(counts);
+        }
+        Ok(())
+    }
+
+    /// Applies changes other readers make as they happen. Whenever the stream
+    /// (re)connects we sync, to catch anything sent while it was down.
+    fn listen(&self) {
+        let state = *self;
+        let on_open = move || {
+            if matches!(state.load_state.get_untracked(), LoadState::Ready) {
+                state.refresh();
+            }
+        };
+        if let Err(e) = subscribe_live_events(move |event| state.apply_event(event), on_open) {
+            log::error!("Failed to subscribe to live updates: {}", e);
+        }
+    }
+
+    fn apply_event(&self, event: LiveEvent) {
+        match event {
+            LiveEvent::NodeAdded(node) => {
+                // Step the reader out of a node that was moved from under them. Paths can
+                // pass through a node more than once, and may have reached it by a link.
+                let moved_at = self.graph.with_untracked(|g| {
+                    self.path.with_untracked(|p| {
+                        p.iter().enumerate().position(|(i, id)| {
+                            let from = i.checked_sub(1).map(|j| p[j].as_str());
+                            *id == node.id && !g.leads_to(from, &node)
+                        })
+                    })
+                });
+                if let Some(i) = moved_at {
+                    self.path.update(|p| p.truncate(i));
+                }
+                // A node moved here from elsewhere in the story may bring children we haven't fetched
+                let has_children = self.descendant_counts.with_untracked(|c| c.get(&node.id).is_some_and(|&n| n > 0));
+                self.graph.update(|g| {
+                    if g.node(&node.id).is_none() && !has_children {
+                        g.mark_children_loaded(&node.id);
+                    }
+                    g.insert_node(node);
+                })
+            }
+            LiveEvent::NodeDeleted { node_id } => {
+                self.graph.update(|g| {
+                    g.remove_node(&node_id);
+                });
+                self.descendant_counts.update(|c| {
+                    c.remove(&node_id);
+                });
+                // Step the reader back out if they were inside the removed node
+                if self.path.with_untracked(|p| p.contains(&node_id)) {
+                    self.path.update(|p| {
+                        if let Some(i) = p.iter().position(|id| *id == node_id) {
+                            p.truncate(i);
+                        }
+                    });
+                }
+            }
+            LiveEvent::CountsChanged(changed) => self.descendant_counts.update(|c| c.extend(changed)),
+            LiveEvent::SubmissionQueued { .. } | LiveEvent::NodeReported { .. } => {
+                self.review_activity.update(|n| *n += 1)
+            }
+            LiveEvent::SubmissionReviewed { node_id, .. } => {
+                self.review_activity.update(|n| *n += 1);
+                if self.my_submissions.with_untracked(|subs| subs.iter().any(|s| s.node.id == node_id)) {
+                    self.refresh_submissions();
+                }
+            }
+            LiveEvent::LinkAdded(link) => self.graph.update(|g| g.insert_link(link)),
+            LiveEvent::LinkRemoved { link_id } => {
+                // Readers already past it keep their place; it just isn't offered any more
+                self.graph.update(|g| g.remove_link(&link_id))
+            }
+            LiveEvent::MechanicsChanged(mechanics) => self.graph.update(|g| g.set_mechanics(mechanics)),
+            LiveEvent::RandomBranchChanged { node_id, roll } => self.graph.update(|g| g.set_roll(node_id, roll)),
+            LiveEvent::OutcomeValueChanged { node_id, value } => {
+                self.graph.update(|g| g.set_outcome_value(node_id, value))
+            }
+            LiveEvent::EndingSet(ending) => self.graph.update(|g| g.set_ending(ending)),
+            LiveEvent::EndingCleared { node_id } => self.graph.update(|g| g.clear_ending(&node_id)),
+        }
+    }
+
+    /// Syncs in the background without resetting the reader.
+    fn refresh(&self) {
+        let state = *self;
+        spawn_local(async move {
+            if let Err(error) = state.sync().await {
+                log::error!("Failed to refresh adventure: {}", error);
+                state.check_outdated(&error);
+                // Losing the connection gets its own banner
+                if !state.check_offline(&error) {
+                    state.notice.set(Some(error.to_string()));
+                }
+            }
+        });
+    }
+
+    /// Surfaces a failed action without tearing down the story view.
+    fn report(&self, error: AdventureError) {
+        self.check_outdated(&error);
+        self.check_offline(&error);
+        if matches!(error, AdventureError::Stale(_)) {
+            self.refresh();
+        }
+        self.notice.set(Some(error.to_string()));
+    }
+
+    /// Remembers that the server rejected this build, so the reload banner shows.
+    fn check_outdated(&self, error: &AdventureError) {
+        if matches!(error, AdventureError::Outdated) {
+            self.outdated.set(true);
+        }
+    }
+
+    /// Switches to read-only mode if `error` means the server is unreachable.
+    fn check_offline(&self, error: &AdventureError) -> bool {
+        let unreachable = matches!(error, AdventureError::Unreachable(_));
+        if unreachable {
+            self.offline.set(true);
+        }
+        unreachable
+    }
+
     pub fn choose(&self, node: &AdventureNode) {
         self.path.update(|path| path.push(node.id.clone()));
         self.show_contribute.set(false);
+        self.submit_error.set(None);
+        self.ensure_loaded(node.id.clone());
+    }
+
+    /// Takes the reader to where `link` leads, fetching it first if it's in a part of the
+    /// story they haven't been near.
+    pub fn follow_link(&self, link: &NodeLink) {
+        let state = *self;
+        let target_id = link.target_id.clone();
+        self.show_contribute.set(false);
+        self.submit_error.set(None);
+
+        if self.graph.with_untracked(|g| g.node(&target_id).is_some()) {
+            self.path.update(|p| p.push(target_id.clone()));
+            self.ensure_loaded(target_id);
+            return;
+        }
+        spawn_local(async move {
+            // The node itself arrives with its subtree
+            match fetch_subtree(Some(target_id.clone()), LOOKAHEAD).await {
+                Ok((nodes, _)) => {
+                    let node_ids = nodes.iter().map(|n| n.id.clone()).collect();
+                    state.graph.update(|g| g.insert_subtree(Some(&target_id), LOOKAHEAD, nodes));
+                    state.load_details(node_ids).await;
+                    if state.graph.with_untracked(|g| g.node(&target_id).is_some()) {
+                        state.path.update(|p| p.push(target_id));
+                    }
+                }
+                Err(error) => state.report(error),
+            }
+        });
     }
 
     pub fn revert_to(&self, index: usize) {
         self.path.update(|path| path.truncate(index + 1));
         self.show_contribute.set(false);
+        self.submit_error.set(None);
+        if let Some(id) = self.path.with_untracked(|p| p.last().cloned()) {
+            self.ensure_loaded(id);
+        }
     }
 
     pub fn toggle_contribute(&self) {
@@ -98,85 +727,237 @@ impl AdventureState {
 
     pub fn close_contribute(&self) {
         self.show_contribute.set(false);
+        self.submit_error.set(None);
     }
 
-    pub fn add_node(&self, node: AdventureNode, session_id: Option<String>) {
-        let graph = self.graph;
-        let counts = self.descendant_counts;
-        let load_state = self.load_state;
-        let path = self.path;
-        let show_contribute = self.show_contribute;
-        let new_node_id = node.id.clone();
+    /// Submits `node` along with its `rules`.
+    pub fn add_node(&self, node: AdventureNode, rules: NodeRules, session_id: Option<String>) {
+        let state = *self;
+        let submit_error = self.submit_error;
 
+        submit_error.set(None);
         spawn_local(async move {
-            match submit_node(node, session_id).await {
-                Ok(()) => {
-                    match fetch_adventure().await {
-                        Ok(data) => {
-                            graph.set(data);
-                            path.update(|p| p.push(new_node_id));
-                            show_contribute.set(false);
-                            if let Ok(c) = fetch_descendant_counts().await {
-                                counts.set(c);
-                            }
-                            load_state.set(LoadState::Ready);
-                        }
-                        Err(error) => {
-                            load_state.set(LoadState::Error(error));
-                        }
-                    }
+            match submit_node(node, rules.clone(), session_id).await {
+                Ok(Submitted::Queued(_)) => {
+                    state.refresh_submissions();
+                    state.show_contribute.set(false);
+                    state.notice.set(Some(
+                        "Thanks! Your addition will appear once a moderator approves it".into(),
+                    ));
+                }
+                Ok(Submitted::Published(created)) => {
+                    let created_id = created.id.clone();
+                    state.graph.update(|g| {
+                        // Nobody has continued from it yet
+                        g.mark_children_loaded(&created.id);
+                        g.insert_node(created);
+                        g.set_mechanics(NodeMechanics {
+                            node_id: created_id.clone(),
+                            condition: rules.condition,
+                            effects: rules.effects,
+                        });
+                        g.set_roll(created_id.clone(), rules.roll);
+                        g.set_outcome_value(created_id.clone(), rules.outcome_value);
+                        // Its ending shows up with the sync that follows, which knows which story it ends
+                    });
+                    state.path.update(|p| p.push(created_id));
+                    state.show_contribute.set(false);
+                    state.refresh();
                 }
                 Err(error) => {
                     log::error!("Failed to submit node: {}", error);
-                    load_state.set(LoadState::Error(error));
+                    state.check_outdated(&error);
+                    state.check_offline(&error);
+                    let stale = match &error {
+                        AdventureError::Stale(_) => true,
+                        // The parent vanished or filled up since we last synced
+                        AdventureError::InvalidField { field, .. } => field == "parent_id",
+                        _ => false,
+                    };
+                    if stale {
+                        state.refresh();
+                    }
+                    submit_error.set(Some(error));
                 }
             }
         });
     }
 
     pub fn delete_node(&self, node_id: String, session_id: Option<String>) {
-        let graph = self.graph;
-        let counts = self.descendant_counts;
-        let load_state = self.load_state;
-        let path = self.path;
-        let show_contribute = self.show_contribute;
+        let state = *self;
 
         spawn_local(async move {
             match delete_node(node_id.clone(), session_id).await {
                 Ok(()) => {
                     // Pop the node from path if it was the last entry
-                    path.update(|p| {
+                    state.path.update(|p| {
                         if p.last().map(|s| s.as_str()) == Some(&node_id) {
                             p.pop();
                         }
                     });
-                    show_contribute.set(false);
-                    // Reload the graph
-                    match fetch_adventure().await {
-                        Ok(data) => {
-                            graph.set(data);
-                            if let Ok(c) = fetch_descendant_counts().await {
-                                counts.set(c);
-                            }
-                            load_state.set(LoadState::Ready);
-                        }
-                        Err(error) => {
-                            load_state.set(LoadState::Error(error));
-                        }
+                    state.show_contribute.set(false);
+                    let mut removed = None;
+                    state.graph.update(|g| {
+                        removed = g.remove_node(&node_id);
+                    });
+                    if let Some(node) = removed {
+                        state.offer_undo(node);
                     }
+                    state.refresh();
                 }
                 Err(error) => {
                     log::error!("Failed to delete node: {}", error);
-                    load_state.set(LoadState::Error(error));
+                    state.report(error);
+                }
+            }
+        });
+    }
+
+    /// Starts a link from the current node: the reader goes and finds the node it should
+    /// lead to, then comes back here.
+    pub fn start_link(&self) {
+        let path = self.path.get_untracked();
+        let Some(parent_id) = path.last().cloned() else {
+            return;
+        };
+        self.show_contribute.set(false);
+        self.linking.set(Some(LinkDraft {
+            parent_id,
+            choice_text: String::new(),
+            error: None,
+            return_path: path,
+        }));
+    }
+
+    /// Drops the link being made and puts the reader back where they started it.
+    pub fn cancel_link(&self) {
+        if let Some(draft) = self.linking.get_untracked() {
+            self.path.set(draft.return_path);
+        }
+        self.linking.set(None);
+    }
+
+    /// Finishes the link being made, leading to `target_id`.
+    pub fn link_to(&self, target_id: String) {
+        let state = *self;
+        let Some(draft) = self.linking.get_untracked() else {
+            return;
+        };
+        let link = NodeLink {
+            id: String::new(),
+            parent_id: draft.parent_id.clone(),
+            target_id,
+            choice_text: draft.choice_text.clone(),
+            created_by: None,
+        };
+
+        spawn_local(async move {
+            match create_link(link, credential()).await {
+                Ok(created) => {
+                    state.graph.update(|g| g.insert_link(created));
+                    state.linking.set(None);
+                    state.path.set(draft.return_path);
+                }
+                Err(error) => {
+                    log::error!("Failed to create link: {}", error);
+                    state.check_outdated(&error);
+                    state.check_offline(&error);
+                    if matches!(&error, AdventureError::InvalidField { field, .. } if field != "choice_text") {
+                        state.refresh();
+                    }
+                    state.linking.update(|d| {
+                        if let Some(d) = d {
+                            d.error = Some(error.to_string());
+                        }
+                    });
                 }
             }
         });
     }
 
-    #[allow(dead_code)]
+    pub fn remove_link(&self, link_id: String) {
+        let state = *self;
+
+        spawn_local(async move {
+            match delete_link(link_id.clone(), credential()).await {
+                Ok(()) => state.graph.update(|g| g.remove_link(&link_id)),
+                Err(error) => {
+                    log::error!("Failed to remove link: {}", error);
+                    state.report(error);
+                }
+            }
+        });
+    }
+
+    /// Lets the reader undo deleting `node` until the server would stop them.
+    fn offer_undo(&self, node: AdventureNode) {
+        let undo = self.undo;
+        let node_id = node.id.clone();
+        undo.set(Some(node));
+        gloo_timers::callback::Timeout::new(UNDO_WINDOW_SECS as u32 * 1_000, move || {
+            undo.try_update(|undo| {
+                if undo.as_ref().is_some_and(|n| n.id == node_id) {
+                    *undo = None;
+                }
+            });
+        })
+        .forget();
+    }
+
+    /// Puts back the node the reader just deleted.
+    pub fn undo_delete(&self) {
+        let state = *self;
+        let Some(node) = self.undo.get_untracked() else {
+            return;
+        };
+        self.undo.set(None);
+
+        spawn_local(async move {
+            match restore_node(node.id.clone(), credential()).await {
+                Ok(()) => {
+                    state.graph.update(|g| {
+                        // Nothing could continue from it while it was gone
+                        g.mark_children_loaded(&node.id);
+                        g.insert_node(node);
+                    });
+                    state.refresh();
+                }
+                Err(error) => {
+                    log::error!("Failed to restore node: {}", error);
+                    state.report(error);
+                }
+            }
+        });
+    }
+
+    pub fn report_node(&self, node_id: String, reason: ReportReason, details: String) {
+        let state = *self;
+
+        spawn_local(async move {
+            match report_node(node_id, reason, details, credential()).await {
+                Ok(()) => state
+                    .notice
+                    .set(Some("Thanks for the report. A moderator will take a look".into())),
+                Err(error) => {
+                    log::error!("Failed to report node: {}", error);
+                    state.report(error);
+                }
+            }
+        });
+    }
+
+    /// Goes back to the start of the current story, with new rolls.
+    pub fn restart_story(&self) {
+        self.revert_to(0);
+        self.seed.set(new_seed());
+    }
+
+    /// Starts a new playthrough, with new rolls.
     pub fn reset_path(&self) {
         self.path.set(Vec::new());
+        self.seed.set(new_seed());
         self.show_contribute.set(false);
+        self.submit_error.set(None);
     }
 }
 
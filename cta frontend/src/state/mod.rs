@@ -1,2 +1,3 @@
 pub mod adventure;
 pub mod llm;
+pub mod moderation;
@@ -0,0 +1,379 @@
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+
+use leptos::prelude::*;
+use leptos::task::spawn_local;
+
+use shared::{
+    Action, DeletedNode, PendingNode, PurgeMode, PurgePlan, ReportResolution, ReportedNode, ReviewDecision, Role,
+    SubtreeDeletion,
+};
+
+use crate::api::adventure::AdventureError;
+use crate::api::moderation::{
+    ban_user, delete_subtree, fetch_deleted_nodes, move_node, fetch_flag_reasons, fetch_moderation_queue, fetch_reports, purge_user, resolve_reports, review_nodes, unban_user,
+};
+use crate::api::adventure::restore_node;
+use crate::api::session::credential;
+use crate::domain::adventure::AdventureNode;
+
+/// A moderators' screen shown in place of the story.
+#[derive(Clone, Copy, PartialEq)]
+pub enum ModerationScreen {
+    Queue,
+    Reports,
+    Users,
+    Deleted,
+}
+
+/// The moderation queue and reported nodes, for users whose role lets them moderate.
+#[derive(Clone, Copy)]
+pub struct ModerationState {
+    queue: RwSignal<Vec<PendingNode>>,
+    /// Why content filters held queued nodes, by node ID.
+    flags: RwSignal<HashMap<String, String>>,
+    reports: RwSignal<Vec<ReportedNode>>,
+    /// Deleted nodes an admin can still restore.
+    deleted: RwSignal<Vec<DeletedNode>>,
+    /// IDs ticked for the next bulk approve or reject.
+    selected: RwSignal<HashSet<String>>,
+    screen: RwSignal<Option<ModerationScreen>>,
+    /// Set while a review or resolution is being sent.
+    busy: RwSignal<bool>,
+    error: RwSignal<Option<String>>,
+    role: RwSignal<Option<Role>>,
+    /// The last purge preview, or what the last purge did.
+    purge_plan: RwSignal<Option<PurgePlan>>,
+    /// Confirms the last ban, unban or purge.
+    done: RwSignal<Option<String>>,
+    /// What deleting the branch an admin picked would remove, waiting for them to confirm.
+    subtree_deletion: RwSignal<Option<SubtreeDeletion>>,
+    /// The node an admin picked up to move, while they find where it should go.
+    moving: RwSignal<Option<AdventureNode>>,
+}
+
+impl ModerationState {
+    pub fn new() -> Self {
+        Self {
+            queue: RwSignal::new(Vec::new()),
+            flags: RwSignal::new(HashMap::new()),
+            reports: RwSignal::new(Vec::new()),
+            deleted: RwSignal::new(Vec::new()),
+            selected: RwSignal::new(HashSet::new()),
+            screen: RwSignal::new(None),
+            busy: RwSignal::new(false),
+            error: RwSignal::new(None),
+            role: use_context::<RwSignal<Option<Role>>>().expect("User role signal must be provided by App"),
+            purge_plan: RwSignal::new(None),
+            done: RwSignal::new(None),
+            subtree_deletion: RwSignal::new(None),
+            moving: RwSignal::new(None),
+        }
+    }
+
+    pub fn queue(&self) -> RwSignal<Vec<PendingNode>> {
+        self.queue
+    }
+
+    pub fn flags(&self) -> RwSignal<HashMap<String, String>> {
+        self.flags
+    }
+
+    pub fn reports(&self) -> RwSignal<Vec<ReportedNode>> {
+        self.reports
+    }
+
+    pub fn deleted(&self) -> RwSignal<Vec<DeletedNode>> {
+        self.deleted
+    }
+
+    pub fn selected(&self) -> RwSignal<HashSet<String>> {
+        self.selected
+    }
+
+    pub fn screen(&self) -> RwSignal<Option<ModerationScreen>> {
+        self.screen
+    }
+
+    pub fn busy(&self) -> RwSignal<bool> {
+        self.busy
+    }
+
+    pub fn error(&self) -> RwSignal<Option<String>> {
+        self.error
+    }
+
+    pub fn purge_plan(&self) -> RwSignal<Option<PurgePlan>> {
+        self.purge_plan
+    }
+
+    pub fn done(&self) -> RwSignal<Option<String>> {
+        self.done
+    }
+
+    pub fn subtree_deletion(&self) -> RwSignal<Option<SubtreeDeletion>> {
+        self.subtree_deletion
+    }
+
+    pub fn moving(&self) -> RwSignal<Option<AdventureNode>> {
+        self.moving
+    }
+
+    /// Whether the signed-in user's role (as reported by the server) lets them moderate.
+    pub fn available(&self) -> bool {
+        self.role.get().is_some_and(|role| role.allows(Action::Moderate))
+    }
+
+    /// Whether the signed-in user may ban and purge users.
+    pub fn can_ban(&self) -> bool {
+        self.role.get().is_some_and(|role| role.allows(Action::Ban))
+    }
+
+    /// Whether the signed-in user may restore anyone's deleted nodes.
+    pub fn can_restore(&self) -> bool {
+        self.role.get().is_some_and(|role| role.allows(Action::Restore))
+    }
+
+    /// Whether the signed-in user may delete and move whole branches.
+    pub fn can_restructure(&self) -> bool {
+        self.role.get().is_some_and(|role| role.allows(Action::Restructure))
+    }
+
+    /// Shows `screen`, or goes back to the story if it's already showing.
+    pub fn toggle_screen(&self, screen: ModerationScreen) {
+        self.error.set(None);
+        self.done.set(None);
+        self.screen.update(|current| {
+            *current = if *current == Some(screen) { None } else { Some(screen) };
+        });
+    }
+
+    pub fn close(&self) {
+        self.screen.set(None);
+    }
+
+    pub fn toggle_selected(&self, node_id: &str) {
+        self.selected.update(|selected| {
+            if !selected.remove(node_id) {
+                selected.insert(node_id.to_string());
+            }
+        });
+    }
+
+    /// Selects everything in the queue, or nothing if everything already is.
+    pub fn toggle_all(&self) {
+        let all: HashSet<String> = self.queue.with(|q| q.iter().map(|p| p.node.id.clone()).collect());
+        self.selected.update(|selected| {
+            *selected = if *selected == all { HashSet::new() } else { all };
+        });
+    }
+
+    pub fn refresh(&self) {
+        let state = *self;
+        if !self.role.get_untracked().is_some_and(|role| role.allows(Action::Moderate)) {
+            self.queue.set(Vec::new());
+            self.reports.set(Vec::new());
+            self.deleted.set(Vec::new());
+            self.screen.set(None);
+            return;
+        }
+        let can_restore = self.role.get_untracked().is_some_and(|role| role.allows(Action::Restore));
+
+        spawn_local(async move {
+            match fetch_reports(credential()).await {
+                Ok(reports) => state.reports.set(reports),
+                Err(error) => log::error!("Failed to fetch reports: {}", error),
+            }
+            match fetch_moderation_queue(credential()).await {
+                Ok(queue) => {
+                    // Forget selections of nodes someone else has dealt with
+                    state.selected.update(|selected| {
+                        selected.retain(|id| queue.iter().any(|p| p.node.id == *id));
+                    });
+                    state.queue.set(queue);
+                }
+                Err(error) => log::error!("Failed to fetch moderation queue: {}", error),
+            }
+            match fetch_flag_reasons(credential()).await {
+                Ok(flags) => state.flags.set(flags),
+                Err(error) => log::error!("Failed to fetch flag reasons: {}", error),
+            }
+            if can_restore {
+                match fetch_deleted_nodes(credential()).await {
+                    Ok(deleted) => state.deleted.set(deleted),
+                    Err(error) => log::error!("Failed to fetch deleted nodes: {}", error),
+                }
+            }
+        });
+    }
+
+    /// Applies `decision` to every selected submission.
+    pub fn review(&self, decision: ReviewDecision) {
+        let state = *self;
+        let node_ids: Vec<String> = self.selected.get_untracked().into_iter().collect();
+        if node_ids.is_empty() {
+            return;
+        }
+
+        self.busy.set(true);
+        self.error.set(None);
+        spawn_local(async move {
+            match review_nodes(node_ids, decision, credential()).await {
+                Ok((done, failed)) => {
+                    state.queue.update(|q| q.retain(|p| !done.contains(&p.node.id)));
+                    state.selected.update(|selected| selected.retain(|id| !done.contains(id)));
+                    if let Some((_, error)) = failed.first() {
+                        state.error.set(Some(format!(
+                            "{} of {} couldn't be reviewed: {}",
+                            failed.len(),
+                            done.len() + failed.len(),
+                            error
+                        )));
+                    }
+                }
+                Err(error) => state.error.set(Some(error.to_string())),
+            }
+            state.busy.set(false);
+            state.refresh();
+        });
+    }
+
+    /// Closes the open reports on `node_id` as `resolution` says.
+    pub fn resolve(&self, node_id: String, resolution: ReportResolution) {
+        let state = *self;
+
+        self.busy.set(true);
+        self.error.set(None);
+        spawn_local(async move {
+            match resolve_reports(node_id.clone(), resolution, credential()).await {
+                Ok(()) => state.reports.update(|r| r.retain(|reported| reported.node.id != node_id)),
+                Err(error) => state.error.set(Some(error.to_string())),
+            }
+            state.busy.set(false);
+            state.refresh();
+        });
+    }
+
+    /// Puts a deleted node back in the story.
+    pub fn restore(&self, node_id: String) {
+        let state = *self;
+
+        self.busy.set(true);
+        self.error.set(None);
+        spawn_local(async move {
+            match restore_node(node_id.clone(), credential()).await {
+                Ok(()) => state.deleted.update(|d| d.retain(|deleted| deleted.node.id != node_id)),
+                Err(error) => state.error.set(Some(error.to_string())),
+            }
+            state.busy.set(false);
+            state.refresh();
+        });
+    }
+
+    /// Works out what deleting `node_id` and everything below it would remove, for the
+    /// admin to confirm.
+    pub fn preview_subtree_deletion(&self, node_id: String) {
+        let state = *self;
+
+        self.busy.set(true);
+        self.error.set(None);
+        spawn_local(async move {
+            match delete_subtree(node_id, true, credential()).await {
+                Ok(deletion) => state.subtree_deletion.set(Some(deletion)),
+                Err(error) => state.error.set(Some(error.to_string())),
+            }
+            state.busy.set(false);
+        });
+    }
+
+    /// Deletes the previewed branch. Readers, this one included, hear about it as live events.
+    pub fn confirm_subtree_deletion(&self) {
+        let state = *self;
+        let Some(node_id) = self.subtree_deletion.with_untracked(|d| d.as_ref().map(|d| d.root.id.clone())) else {
+            return;
+        };
+
+        self.busy.set(true);
+        self.error.set(None);
+        spawn_local(async move {
+            match delete_subtree(node_id, false, credential()).await {
+                Ok(_) => state.subtree_deletion.set(None),
+                Err(error) => state.error.set(Some(error.to_string())),
+            }
+            state.busy.set(false);
+        });
+    }
+
+    /// Moves the picked up node to continue from `new_parent_id`, or to start a new story.
+    pub fn move_to(&self, new_parent_id: Option<String>) {
+        let state = *self;
+        let Some(node_id) = self.moving.with_untracked(|m| m.as_ref().map(|n| n.id.clone())) else {
+            return;
+        };
+
+        self.busy.set(true);
+        self.error.set(None);
+        spawn_local(async move {
+            match move_node(node_id, new_parent_id, credential()).await {
+                Ok(()) => state.moving.set(None),
+                Err(error) => state.error.set(Some(error.to_string())),
+            }
+            state.busy.set(false);
+        });
+    }
+
+    pub fn ban(&self, username: String, shadow: bool) {
+        let message = if shadow { "shadowbanned" } else { "banned" };
+        self.run_user_action(async move {
+            ban_user(username.clone(), shadow, credential()).await.map(|()| format!("{} is {}", username, message))
+        });
+    }
+
+    pub fn unban(&self, username: String) {
+        self.run_user_action(async move {
+            unban_user(username.clone(), credential()).await.map(|()| format!("{} is no longer banned", username))
+        });
+    }
+
+    /// Previews (with `dry_run`) or carries out deleting everything `username` wrote.
+    pub fn purge(&self, username: String, mode: PurgeMode, dry_run: bool) {
+        let state = *self;
+        self.run_user_action(async move {
+            let plan = purge_user(username.clone(), mode, dry_run, credential()).await?;
+            let message = match (dry_run, plan.deleted.len()) {
+                (_, 0) => format!("{} hasn't written anything", username),
+                (true, n) => format!("This would delete {} nodes", n),
+                (false, n) => format!("Purged {}: {} nodes deleted", username, n),
+            };
+            state.purge_plan.set(dry_run.then_some(plan));
+            Ok(message)
+        });
+    }
+
+    /// Runs an admin action on a user, reporting its outcome on the users screen.
+    fn run_user_action(
+        &self,
+        action: impl Future<Output = Result<String, AdventureError>> + 'static,
+    ) {
+        let state = *self;
+        self.busy.set(true);
+        self.error.set(None);
+        self.done.set(None);
+        spawn_local(async move {
+            match action.await {
+                Ok(message) => state.done.set(Some(message)),
+                Err(error) => state.error.set(Some(error.to_string())),
+            }
+            state.busy.set(false);
+        });
+    }
+}
+
+pub fn provide_moderation_state() {
+    let state = ModerationState::new();
+    provide_context(state);
+}
+
+pub fn use_moderation_state() -> ModerationState {
+    use_context::<ModerationState>().expect("ModerationState must be provided by an ancestor")
+}
@@ -0,0 +1,279 @@
+//! Random branches: nodes whose outcome is picked by chance instead of by the reader.
+//! Each child of a random branch is an outcome carrying a value, read as its weight for
+//! a weighted draw or as the lowest total that reaches it for a dice check.
+//!
+//! Rolls come from a small seeded generator so the same seed gives the same outcome on
+//! every machine, which is what lets a shared link land on the result its sender saw.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::mechanics::{self, GameState};
+
+/// Most dice one check may roll.
+pub const MAX_DICE: u32 = 10;
+
+/// Most sides a die may have.
+pub const MAX_SIDES: u32 = 100;
+
+/// Largest weight or threshold an outcome may carry.
+pub const MAX_OUTCOME_VALUE: u32 = 10_000;
+
+/// How a random branch picks its outcome.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Roll {
+    /// Draws an outcome with probability proportional to its value.
+    Weighted,
+    /// Rolls `count` dice with `sides` sides and adds the reader's `bonus` (a variable
+    /// name, or empty). The outcome with the highest threshold the total reaches wins.
+    Dice { count: u32, sides: u32, bonus: String },
+}
+
+impl Roll {
+    /// Checks the dice are within limits and the bonus is a usable name.
+    pub fn check(&self) -> Result<(), String> {
+        match self {
+            Roll::Weighted => Ok(()),
+            Roll::Dice { count, sides, bonus } => {
+                if !(1..=MAX_DICE).contains(count) {
+                    return Err(format!("Roll between 1 and {} dice", MAX_DICE));
+                }
+                if !(2..=MAX_SIDES).contains(sides) {
+                    return Err(format!("Dice need between 2 and {} sides", MAX_SIDES));
+                }
+                if !bonus.is_empty() && !mechanics::is_name(bonus) {
+                    return Err(format!("{:?} isn't a variable name", bonus));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Roll {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Roll::Weighted => write!(f, "weighted"),
+            Roll::Dice { count, sides, bonus } if bonus.is_empty() => write!(f, "{}d{}", count, sides),
+            Roll::Dice { count, sides, bonus } => write!(f, "{}d{} + {}", count, sides, bonus),
+        }
+    }
+}
+
+/// Every random branch and outcome value in the story, by node ID.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChanceTable {
+    pub branches: HashMap<String, Roll>,
+    pub outcomes: HashMap<String, u32>,
+}
+
+/// What a roll came to and where it leads.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RollResult {
+    /// Each die, or the single number drawn for a weighted branch.
+    pub rolls: Vec<u32>,
+    /// The reader's bonus added to a dice roll.
+    pub bonus: i64,
+    pub total: i64,
+    /// The outcome picked, or `None` if the branch has no outcomes yet.
+    pub outcome: Option<String>,
+}
+
+/// SplitMix64: tiny, and plenty for picking story outcomes.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A number from 1 to `n`.
+    fn up_to(&mut self, n: u32) -> u32 {
+        (self.next() % u64::from(n.max(1))) as u32 + 1
+    }
+}
+
+/// The seed for the `visit`th time a reader playing with `seed` reaches `node_id`.
+pub fn seed_for(seed: u64, node_id: &str, visit: usize) -> u64 {
+    // FNV-1a, so the result doesn't depend on the platform's hasher
+    let mut hash: u64 = 0xCBF2_9CE4_8422_2325;
+    for byte in node_id.bytes().chain((visit as u64).to_le_bytes()) {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01B3);
+    }
+    seed ^ hash
+}
+
+/// Rolls `roll` with `seed` for a reader in `state`, choosing among `outcomes` (node ID
+/// and value). The outcomes' order doesn't matter.
+pub fn roll(roll: &Roll, seed: u64, outcomes: &[(String, u32)], state: &GameState) -> RollResult {
+    let mut rng = Rng(seed);
+    let mut outcomes = outcomes.to_vec();
+    outcomes.sort();
+
+    match roll {
+        Roll::Weighted => {
+            // With no weights set, every outcome is as likely as the next
+            let weight = |value: u32| if outcomes.iter().all(|(_, v)| *v == 0) { 1 } else { value };
+            let total: u32 = outcomes.iter().map(|(_, v)| weight(*v)).sum();
+            let drawn = rng.up_to(total);
+            let mut reached = 0;
+            let outcome = outcomes.iter().find_map(|(id, v)| {
+                reached += weight(*v);
+                (drawn <= reached).then(|| id.clone())
+            });
+            RollResult {
+                rolls: vec![drawn],
+                bonus: 0,
+                total: drawn.into(),
+                outcome,
+            }
+        }
+        Roll::Dice { count, sides, bonus } => {
+            let rolls: Vec<u32> = (0..*count).map(|_| rng.up_to(*sides)).collect();
+            let bonus = if bonus.is_empty() { 0 } else { state.value(bonus) };
+            let total = rolls.iter().map(|&r| i64::from(r)).sum::<i64>().saturating_add(bonus);
+            // The best outcome reached, or the worst one if none is
+            let outcome = outcomes
+                .iter()
+                .filter(|(_, threshold)| i64::from(*threshold) <= total)
+                .max_by_key(|(_, threshold)| *threshold)
+                .or_else(|| outcomes.iter().min_by_key(|(_, threshold)| *threshold))
+                .map(|(id, _)| id.clone());
+            RollResult {
+                rolls,
+                bonus,
+                total,
+                outcome,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcomes(values: &[(&str, u32)]) -> Vec<(String, u32)> {
+        values.iter().map(|(id, value)| (id.to_string(), *value)).collect()
+    }
+
+    fn dice(count: u32, sides: u32, bonus: &str) -> Roll {
+        Roll::Dice { count, sides, bonus: bonus.into() }
+    }
+
+    #[test]
+    fn a_fixed_seed_rolls_the_same_everywhere() {
+        // Shared links depend on these never changing between builds or platforms
+        assert_eq!(seed_for(42, "node", 0), 12551041426354838119);
+        assert_eq!(seed_for(42, "node", 1), 10318726019387248646);
+
+        let state = GameState::default();
+        let result = roll(&dice(2, 6, ""), 42, &outcomes(&[("a", 0), ("b", 7)]), &state);
+        assert_eq!(result.rolls, vec![2, 2]);
+        assert_eq!(result.total, 4);
+        assert_eq!(result.outcome.as_deref(), Some("a"));
+
+        let result = roll(&Roll::Weighted, 42, &outcomes(&[("a", 1), ("b", 3)]), &state);
+        assert_eq!(result.rolls, vec![2]);
+        assert_eq!(result.outcome.as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn outcome_order_doesnt_matter() {
+        let state = GameState::default();
+        let forward = outcomes(&[("a", 1), ("b", 2), ("c", 3)]);
+        let backward: Vec<_> = forward.iter().rev().cloned().collect();
+        for seed in 0..100 {
+            assert_eq!(
+                roll(&Roll::Weighted, seed, &forward, &state),
+                roll(&Roll::Weighted, seed, &backward, &state),
+            );
+        }
+    }
+
+    #[test]
+    fn weighted_draws_follow_the_weights() {
+        let state = GameState::default();
+        let table = outcomes(&[("never", 0), ("rare", 1), ("common", 3)]);
+        let mut counts = HashMap::new();
+        for seed in 0..4000 {
+            let outcome = roll(&Roll::Weighted, seed, &table, &state).outcome.unwrap();
+            *counts.entry(outcome).or_insert(0) += 1;
+        }
+        assert_eq!(counts.get("never"), None);
+        let (rare, common) = (counts["rare"], counts["common"]);
+        assert!((800..1200).contains(&rare), "rare came up {} times", rare);
+        assert_eq!(rare + common, 4000);
+    }
+
+    #[test]
+    fn weighted_draws_without_weights_are_even() {
+        let state = GameState::default();
+        let table = outcomes(&[("a", 0), ("b", 0)]);
+        let a = (0..1000)
+            .filter(|&seed| roll(&Roll::Weighted, seed, &table, &state).outcome.as_deref() == Some("a"))
+            .count();
+        assert!((400..600).contains(&a), "a came up {} times", a);
+    }
+
+    #[test]
+    fn dice_lead_to_the_highest_threshold_reached() {
+        let state = GameState::default();
+        let table = outcomes(&[("fail", 0), ("pass", 7), ("great", 11)]);
+        for seed in 0..500 {
+            let result = roll(&dice(2, 6, ""), seed, &table, &state);
+            assert!(result.rolls.iter().all(|r| (1..=6).contains(r)));
+            let expected = match result.total {
+                11.. => "great",
+                7.. => "pass",
+                _ => "fail",
+            };
+            assert_eq!(result.outcome.as_deref(), Some(expected));
+        }
+    }
+
+    #[test]
+    fn dice_add_the_readers_bonus() {
+        let mut state = GameState::default();
+        state.counters.insert("lockpicking".into(), 10);
+        let table = outcomes(&[("fail", 0), ("pass", 11)]);
+        for seed in 0..100 {
+            let result = roll(&dice(1, 6, "lockpicking"), seed, &table, &state);
+            assert_eq!(result.bonus, 10);
+            assert_eq!(result.total, i64::from(result.rolls[0]) + 10);
+            assert_eq!(result.outcome.as_deref(), Some("pass"));
+        }
+    }
+
+    #[test]
+    fn dice_below_every_threshold_fall_back_to_the_lowest() {
+        let state = GameState::default();
+        let result = roll(&dice(1, 6, ""), 7, &outcomes(&[("high", 60), ("low", 50)]), &state);
+        assert_eq!(result.outcome.as_deref(), Some("low"));
+    }
+
+    #[test]
+    fn no_outcomes_means_no_outcome() {
+        let state = GameState::default();
+        assert_eq!(roll(&Roll::Weighted, 1, &[], &state).outcome, None);
+        assert_eq!(roll(&dice(1, 6, ""), 1, &[], &state).outcome, None);
+    }
+
+    #[test]
+    fn checks_dice_limits() {
+        assert!(Roll::Weighted.check().is_ok());
+        assert!(dice(MAX_DICE, MAX_SIDES, "luck").check().is_ok());
+        assert!(dice(0, 6, "").check().is_err());
+        assert!(dice(MAX_DICE + 1, 6, "").check().is_err());
+        assert!(dice(1, 1, "").check().is_err());
+        assert!(dice(1, MAX_SIDES + 1, "").check().is_err());
+        assert!(dice(1, 6, "not").check().is_err());
+        assert!(dice(1, 6, "two words").check().is_err());
+    }
+}
@@ -1,7 +1,14 @@
 use std::collections::HashMap;
+use std::fmt;
 
 use serde::{Deserialize, Serialize};
 
+pub mod chance;
+pub mod mechanics;
+
+use chance::{ChanceTable, Roll};
+use mechanics::NodeMechanics;
+
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct AdventureNode {
     pub id: String,
@@ -12,6 +19,40 @@ pub struct AdventureNode {
     pub created_by: Option<String>,
 }
 
+/// Version of the `/api` wire format. Bump it whenever a change alters the bincode
+/// layout of an existing type, and keep a decoder for the previous version in the backend.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// First field of every envelope. Bare messages from before versioning start with a
+/// small variant index instead, which is how the backend tells the two apart.
+pub const ENVELOPE_MAGIC: u32 = 0x4354_4145;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EnvelopeHeader {
+    pub magic: u32,
+    pub protocol_version: u32,
+    /// Identifies the frontend build that sent the request, for logs.
+    pub client_build: String,
+}
+
+impl EnvelopeHeader {
+    pub fn current(client_build: impl Into<String>) -> Self {
+        Self {
+            magic: ENVELOPE_MAGIC,
+            protocol_version: PROTOCOL_VERSION,
+            client_build: client_build.into(),
+        }
+    }
+}
+
+/// Body of a POST to `/api`. Generic so the backend can decode older message types
+/// behind the same header.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RequestEnvelope<M = ServerMessage> {
+    pub header: EnvelopeHeader,
+    pub message: M,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum ServerMessage {
     RequestAdventureNodes,
@@ -31,5 +72,704 @@ pub enum ServerMessage {
     },
 
     Ok,
-    Error(String),
+    Error(ApiError),
+
+    /// Reply to `SubmitAdventureNode`, carrying the node with its server-assigned ID.
+    NodeCreated {
+        node: AdventureNode,
+    },
+
+    /// Nodes added or removed since `cursor`. Pass 0 for everything.
+    RequestChangesSince {
+        cursor: u64,
+    },
+    ReturnChanges(ChangeSet),
+
+    /// `root_id` (or every root when `None`) plus up to `depth` levels below it.
+    RequestSubtree {
+        root_id: Option<String>,
+        depth: u32,
+    },
+    /// Reply to `RequestSubtree`, with a cursor for syncing changes made after it.
+    ReturnSubtree {
+        nodes: Vec<AdventureNode>,
+        cursor: u64,
+    },
+
+    /// The nodes leading to `node_id`, root first. Answered with `ReturnAdventureNodes`.
+    RequestAncestors {
+        node_id: String,
+    },
+
+    /// Trades a sign-in credential (such as a Newgrounds session ID) for a backend session
+    /// token, to send as `session_id` from then on.
+    Login {
+        session_id: Option<String>,
+    },
+    LoggedIn(SessionToken),
+
+    /// Who `session_id` belongs to and their role. Answered with `ReturnIdentity`.
+    RequestIdentity {
+        session_id: Option<String>,
+    },
+    /// `None` when the credential is missing or invalid.
+    ReturnIdentity(Option<UserInfo>),
+
+    /// Gives `username` a role. Admins only.
+    GrantRole {
+        username: String,
+        role: Role,
+        session_id: Option<String>,
+    },
+    /// Puts `username` back on the default role. Admins only.
+    RevokeRole {
+        username: String,
+        session_id: Option<String>,
+    },
+
+    /// What the holder of `session_id` may do to each of `node_ids`. Unknown IDs are left out.
+    RequestCapabilities {
+        node_ids: Vec<String>,
+        session_id: Option<String>,
+    },
+    ReturnCapabilities(Capabilities),
+
+    /// Reply to `SubmitAdventureNode` when the node has to be approved before anyone else sees it.
+    NodeQueued {
+        node: AdventureNode,
+    },
+
+    /// Submissions waiting for review, oldest first. Moderators only.
+    RequestModerationQueue {
+        session_id: Option<String>,
+    },
+    ReturnModerationQueue(Vec<PendingNode>),
+
+    /// Approves or rejects each of `node_ids`. Moderators only. Answered with `Reviewed`.
+    ReviewNodes {
+        node_ids: Vec<String>,
+        decision: ReviewDecision,
+        session_id: Option<String>,
+    },
+    Reviewed {
+        done: Vec<String>,
+        /// Nodes that couldn't be reviewed, e.g. because their parent was deleted meanwhile.
+        failed: Vec<(String, ApiError)>,
+    },
+
+    /// The caller's own submissions that are pending or were rejected, newest first.
+    RequestMySubmissions {
+        session_id: Option<String>,
+    },
+    ReturnMySubmissions(Vec<Submission>),
+
+    /// Flags a node for moderators. Reporting the same node twice does nothing.
+    ReportNode {
+        node_id: String,
+        reason: ReportReason,
+        details: String,
+        session_id: Option<String>,
+    },
+
+    /// Nodes with open reports, most reported first. Moderators only.
+    RequestReports {
+        session_id: Option<String>,
+    },
+    ReturnReports(Vec<ReportedNode>),
+
+    /// Closes the open reports on `node_id`. Moderators only.
+    ResolveReports {
+        node_id: String,
+        resolution: ReportResolution,
+        session_id: Option<String>,
+    },
+
+    /// Stops `username` from submitting. With `shadow` they may carry on, but what they
+    /// write (including what they wrote before) is only shown to themselves. Admins only.
+    BanUser {
+        username: String,
+        shadow: bool,
+        session_id: Option<String>,
+    },
+    /// Lifts a ban or shadowban, putting shadowbanned nodes back in the story. Admins only.
+    UnbanUser {
+        username: String,
+        session_id: Option<String>,
+    },
+
+    /// Deletes every node written by `username`, or with `dry_run` only reports what would
+    /// go. Admins only. Answered with `ReturnPurgePlan`.
+    PurgeUser {
+        username: String,
+        mode: PurgeMode,
+        dry_run: bool,
+        session_id: Option<String>,
+    },
+    ReturnPurgePlan(PurgePlan),
+
+    /// The caller's nodes that are only shown to them. Answered with `ReturnAdventureNodes`.
+    RequestOwnUnlistedNodes {
+        session_id: Option<String>,
+    },
+
+    /// Why content filters held queued nodes for review, by node ID. Moderators only.
+    RequestFlagReasons {
+        session_id: Option<String>,
+    },
+    ReturnFlagReasons(HashMap<String, String>),
+
+    /// Brings back a deleted node. Whoever deleted it may within `UNDO_WINDOW_SECS`;
+    /// admins may any time before it's purged. Answered with `Ok`.
+    RestoreNode {
+        node_id: String,
+        session_id: Option<String>,
+    },
+    /// Deleted nodes that haven't been purged yet, newest first. Admins only.
+    RequestDeletedNodes {
+        session_id: Option<String>,
+    },
+    ReturnDeletedNodes(Vec<DeletedNode>),
+
+    /// Deletes a node and everything below it, archiving them first, or with `dry_run`
+    /// only says what would go. Admins only. Answered with `ReturnSubtreeDeletion`.
+    DeleteSubtree {
+        node_id: String,
+        dry_run: bool,
+        session_id: Option<String>,
+    },
+    ReturnSubtreeDeletion(SubtreeDeletion),
+
+    /// Moves a node, and everything below it, to continue from `new_parent_id` instead
+    /// (`None` makes it a story of its own). Admins only. Answered with `Ok`.
+    MoveNode {
+        node_id: String,
+        new_parent_id: Option<String>,
+        session_id: Option<String>,
+    },
+
+    /// Adds a choice after `link.parent_id` that leads to the existing `link.target_id`
+    /// (the `id` is assigned by the server). Answered with `LinkCreated`.
+    CreateLink {
+        link: NodeLink,
+        session_id: Option<String>,
+    },
+    LinkCreated(NodeLink),
+    /// Removes a link. Writers may remove their own; moderators any. Answered with `Ok`.
+    DeleteLink {
+        link_id: String,
+        session_id: Option<String>,
+    },
+    /// Every link between nodes in the story. Answered with `ReturnLinks`.
+    RequestLinks,
+    ReturnLinks(Vec<NodeLink>),
+
+    /// Sets what a node requires and changes (both empty clears them). Writers may set
+    /// their own nodes' until someone continues from them; moderators any. Answered with `Ok`.
+    SetNodeMechanics {
+        mechanics: NodeMechanics,
+        session_id: Option<String>,
+    },
+    /// The mechanics of every node that has any. Answered with `ReturnMechanics`.
+    RequestMechanics,
+    ReturnMechanics(Vec<NodeMechanics>),
+
+    /// Makes a node a random branch whose outcome is rolled for, or (with `None`) an
+    /// ordinary one again. Same rules as `SetNodeMechanics`. Answered with `Ok`.
+    SetRandomBranch {
+        node_id: String,
+        roll: Option<Roll>,
+        session_id: Option<String>,
+    },
+    /// Sets the weight or threshold of an outcome of a random branch (`None` clears it).
+    /// Same rules as `SetNodeMechanics`. Answered with `Ok`.
+    SetOutcomeValue {
+        node_id: String,
+        value: Option<u32>,
+        session_id: Option<String>,
+    },
+    /// Every random branch and outcome value. Answered with `ReturnChance`.
+    RequestChance,
+    ReturnChance(ChanceTable),
+
+    /// Marks a node as one of the story's endings, or (with `None`) as an ordinary node
+    /// again. Same rules as `SetNodeMechanics`. Answered with `Ok`.
+    SetEnding {
+        node_id: String,
+        ending: Option<Ending>,
+        session_id: Option<String>,
+    },
+    /// Every ending in the story. Answered with `ReturnEndings`.
+    RequestEndings,
+    ReturnEndings(Vec<NodeEnding>),
+
+    /// `SubmitAdventureNode` for a node that starts out with rules, which are saved with
+    /// it or not at all. Answered like `SubmitAdventureNode`.
+    SubmitNodeWithRules {
+        node: AdventureNode,
+        rules: NodeRules,
+        session_id: Option<String>,
+    },
+
+    /// Links and rules of `node_ids`, e.g. the nodes of a subtree just fetched. Answered
+    /// with `ReturnNodeDetails`.
+    RequestNodeDetails {
+        node_ids: Vec<String>,
+    },
+    /// Links and rules of the nodes published, or whose links or rules changed, at or after
+    /// `cursor` (the cursor of `RequestChangesSince`). Answered with `ReturnNodeDetails`.
+    RequestDetailChangesSince {
+        cursor: u64,
+    },
+    ReturnNodeDetails(NodeDetails),
+    /// Every ending of the story starting at `root_id`. Answered with `ReturnEndings`.
+    RequestStoryEndings {
+        root_id: String,
+    },
+}
+
+/// The rules a node is written with. Changing them afterwards goes through
+/// `SetNodeMechanics`, `SetRandomBranch`, `SetOutcomeValue` and `SetEnding`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct NodeRules {
+    /// What the node requires; empty always holds.
+    pub condition: String,
+    /// What entering the node changes.
+    pub effects: String,
+    /// Makes the node a random branch.
+    pub roll: Option<Roll>,
+    /// Weight or threshold, when the node is an outcome of a random branch.
+    pub outcome_value: Option<u32>,
+    /// Makes the node one of the story's endings, which only moderators can write past.
+    pub ending: Option<Ending>,
+}
+
+/// What kind of finale a node is. Nodes that aren't endings just haven't been continued yet.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Ending {
+    Good,
+    Bad,
+    Neutral,
+    True,
+}
+
+impl Ending {
+    pub const ALL: [Ending; 4] = [Ending::Good, Ending::Bad, Ending::Neutral, Ending::True];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Ending::Good => "good",
+            Ending::Bad => "bad",
+            Ending::Neutral => "neutral",
+            Ending::True => "true",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Ending::Good => "Good ending",
+            Ending::Bad => "Bad ending",
+            Ending::Neutral => "Neutral ending",
+            Ending::True => "True ending",
+        }
+    }
+}
+
+impl std::str::FromStr for Ending {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ending::ALL
+            .into_iter()
+            .find(|ending| ending.as_str() == s)
+            .ok_or_else(|| format!("Unknown ending {:?}", s))
+    }
+}
+
+/// A node that ends the story, with the story it belongs to.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NodeEnding {
+    pub node_id: String,
+    /// The root of the node's story, so readers can count the endings there are to find.
+    pub root_id: String,
+    pub ending: Ending,
+}
+
+/// A choice leading to a node written elsewhere, so paths can meet or loop back.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NodeLink {
+    pub id: String,
+    /// The node the choice is offered after.
+    pub parent_id: String,
+    /// The node it leads to.
+    pub target_id: String,
+    pub choice_text: String,
+    pub created_by: Option<String>,
+}
+
+/// How long whoever deleted a node has to undo it.
+pub const UNDO_WINDOW_SECS: u64 = 60;
+
+/// A node taken out of the story but not yet purged from the database.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DeletedNode {
+    pub node: AdventureNode,
+    /// Unix time it was deleted.
+    pub deleted_at: u64,
+    pub deleted_by: Option<String>,
+}
+
+/// A submission in the moderation queue.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PendingNode {
+    pub node: AdventureNode,
+    /// Unix time it was submitted.
+    pub submitted_at: u64,
+    /// Choice text of the node it continues, for context.
+    pub parent_choice_text: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ReviewDecision {
+    Approve,
+    /// `reason` is shown to the author.
+    Reject { reason: String },
+}
+
+/// A node its author submitted that isn't part of the story (yet).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Submission {
+    pub node: AdventureNode,
+    pub status: SubmissionStatus,
+    pub submitted_at: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SubmissionStatus {
+    Pending,
+    Rejected { reason: String },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportReason {
+    Spam,
+    Offensive,
+    OffTopic,
+    Other,
+}
+
+impl ReportReason {
+    pub const ALL: [ReportReason; 4] = [
+        ReportReason::Spam,
+        ReportReason::Offensive,
+        ReportReason::OffTopic,
+        ReportReason::Other,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReportReason::Spam => "spam",
+            ReportReason::Offensive => "offensive",
+            ReportReason::OffTopic => "off_topic",
+            ReportReason::Other => "other",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ReportReason::Spam => "Spam",
+            ReportReason::Offensive => "Offensive",
+            ReportReason::OffTopic => "Off topic",
+            ReportReason::Other => "Something else",
+        }
+    }
+}
+
+impl std::str::FromStr for ReportReason {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ReportReason::ALL
+            .into_iter()
+            .find(|reason| reason.as_str() == s)
+            .ok_or_else(|| format!("Unknown report reason {:?}", s))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Report {
+    pub reporter: String,
+    pub reason: ReportReason,
+    pub details: String,
+    /// Unix time of the report.
+    pub reported_at: u64,
+}
+
+/// A node with open reports, for the moderators' reports screen.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReportedNode {
+    pub node: AdventureNode,
+    /// The nodes leading to it, root first.
+    pub path: Vec<AdventureNode>,
+    pub reports: Vec<Report>,
+    /// Whether enough reports came in that it was taken out of the story.
+    pub hidden: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReportResolution {
+    /// The reports were acted on; the node stays as it is, hidden or not.
+    Resolve,
+    /// The reports were unfounded; a hidden node goes back into the story.
+    Dismiss,
+    /// The node is deleted. Like any delete, it mustn't have children.
+    Delete,
+}
+
+/// What happens to other authors' nodes continuing from a purged user's nodes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PurgeMode {
+    /// They move up to the nearest surviving node (becoming new stories if there's none).
+    Reparent,
+    /// They're deleted too.
+    Cascade,
+}
+
+/// What a purge deletes and moves.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PurgePlan {
+    pub deleted: Vec<String>,
+    /// Nodes by other authors and the parent they move to (`None` makes them a new story).
+    pub reparented: Vec<(String, Option<String>)>,
+    /// One entry per topmost node of the purged user.
+    pub subtrees: Vec<PurgedSubtree>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PurgedSubtree {
+    pub root: AdventureNode,
+    /// How many nodes in this subtree are deleted, `root` included.
+    pub deleted: u64,
+    /// Other authors whose nodes in this subtree are deleted or moved.
+    pub other_authors: Vec<String>,
+}
+
+/// What deleting a subtree removes.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SubtreeDeletion {
+    pub root: AdventureNode,
+    /// Every node that goes, `root` included, whatever its status.
+    pub deleted: Vec<String>,
+    /// Who wrote them and how many each, most first.
+    pub authors: Vec<(String, u64)>,
 }
+
+/// Actions the current identity may take, so clients don't have to repeat the server's rules.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Capabilities {
+    pub nodes: HashMap<String, NodeCapabilities>,
+    /// Whether a new story (a node with no parent) may be started.
+    pub new_story: bool,
+    /// Links offered after the requested nodes, by link ID.
+    pub links: HashMap<String, LinkCapabilities>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct NodeCapabilities {
+    pub delete: bool,
+    pub edit: bool,
+    /// Add an option continuing from this node.
+    pub branch: bool,
+    pub report: bool,
+    pub moderate: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LinkCapabilities {
+    pub remove: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct UserInfo {
+    pub username: String,
+    pub role: Role,
+}
+
+/// What a user is trusted to do, from least to most.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Banned,
+    Writer,
+    TrustedWriter,
+    Moderator,
+    Admin,
+}
+
+/// Something a user may or may not be allowed to do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Action {
+    Submit,
+    DeleteOwn,
+    DeleteAny,
+    Edit,
+    Report,
+    /// Publish without waiting in the moderation queue.
+    SkipReview,
+    Moderate,
+    ManageRoles,
+    /// Ban or shadowban users and purge what they wrote.
+    Ban,
+    /// Bring back deleted nodes, whoever deleted them and however long ago.
+    Restore,
+    /// Delete or move whole branches of the story at once.
+    Restructure,
+}
+
+impl Role {
+    /// Signed-in users nobody has assigned a role to.
+    pub const DEFAULT: Role = Role::Writer;
+
+    pub const ALL: [Role; 5] = [
+        Role::Banned,
+        Role::Writer,
+        Role::TrustedWriter,
+        Role::Moderator,
+        Role::Admin,
+    ];
+
+    /// The least role allowed to perform `action`.
+    fn minimum_for(action: Action) -> Role {
+        match action {
+            Action::Submit | Action::DeleteOwn | Action::Report => Role::Writer,
+            Action::Edit | Action::SkipReview => Role::TrustedWriter,
+            Action::DeleteAny | Action::Moderate => Role::Moderator,
+            Action::ManageRoles | Action::Ban | Action::Restore | Action::Restructure => Role::Admin,
+        }
+    }
+
+    pub fn allows(self, action: Action) -> bool {
+        self >= Self::minimum_for(action)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::Banned => "banned",
+            Role::Writer => "writer",
+            Role::TrustedWriter => "trusted_writer",
+            Role::Moderator => "moderator",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Role::ALL
+            .into_iter()
+            .find(|role| role.as_str() == s)
+            .ok_or_else(|| format!("Unknown role {:?}", s))
+    }
+}
+
+/// Signed token the backend issues at login.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SessionToken {
+    pub token: String,
+    pub username: String,
+    /// Unix time after which the token is rejected.
+    pub expires_at: u64,
+}
+
+/// Result of an incremental sync.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChangeSet {
+    pub added: Vec<AdventureNode>,
+    pub removed: Vec<String>,
+    /// Cursor to send with the next `RequestChangesSince`.
+    pub cursor: u64,
+}
+
+/// Links and rules of some nodes. Complete for each of `node_ids`, so whatever else a
+/// client holds for them is out of date.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct NodeDetails {
+    pub node_ids: Vec<String>,
+    /// Links offered after the nodes.
+    pub links: Vec<NodeLink>,
+    pub mechanics: Vec<NodeMechanics>,
+    pub chance: ChanceTable,
+    pub endings: Vec<NodeEnding>,
+}
+
+/// Change pushed to connected readers over the `/api/events` stream, JSON-encoded.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LiveEvent {
+    NodeAdded(AdventureNode),
+    NodeDeleted { node_id: String },
+    /// New descendant counts for the nodes whose counts changed.
+    CountsChanged(HashMap<String, u64>),
+    /// A submission entered the moderation queue. Its content stays private.
+    SubmissionQueued { node_id: String },
+    /// A queued submission was approved (and also sent as `NodeAdded`) or rejected.
+    SubmissionReviewed { node_id: String, approved: bool },
+    /// Someone reported a node. Who and why is only shown to moderators.
+    NodeReported { node_id: String },
+    LinkAdded(NodeLink),
+    LinkRemoved { link_id: String },
+    /// A node's mechanics were set or, when both are empty, cleared.
+    MechanicsChanged(NodeMechanics),
+    RandomBranchChanged { node_id: String, roll: Option<Roll> },
+    OutcomeValueChanged { node_id: String, value: Option<u32> },
+    EndingSet(NodeEnding),
+    EndingCleared { node_id: String },
+}
+
+/// Structured failure returned by the server, so clients can react to specific cases.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ApiError {
+    /// The action needs a verified identity and none was supplied.
+    Unauthenticated,
+    /// The identity is known but isn't allowed to perform the action.
+    Forbidden,
+    /// The referenced node doesn't exist.
+    NotFound,
+    /// The node can't be removed because other nodes continue from it.
+    HasChildren,
+    /// A submitted field was rejected.
+    Validation { field: String, reason: String },
+    /// Too many requests; try again after `retry_after` seconds.
+    RateLimited { retry_after: u64 },
+    /// The request couldn't be decoded or isn't something the server handles.
+    BadRequest(String),
+    /// The server failed while handling the request.
+    Internal,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unauthenticated => write!(f, "Authentication required"),
+            Self::Forbidden => write!(f, "You don't have permission to do that"),
+            Self::NotFound => write!(f, "Node not found"),
+            Self::HasChildren => write!(f, "Cannot delete a node that has children"),
+            Self::Validation { field, reason } => write!(f, "Invalid {}: {}", field, reason),
+            Self::RateLimited { retry_after } => {
+                write!(f, "Too many requests, try again in {}s", retry_after)
+            }
+            Self::BadRequest(reason) => write!(f, "Bad request: {}", reason),
+            Self::Internal => write!(f, "Internal server error"),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
@@ -0,0 +1,516 @@
+//! Gamebook mechanics: what a node requires of the reader before they can choose it, and
+//! what it changes once they have. Both are written as short text so writers can type
+//! them, and parsed here so the server and the frontend agree on what they mean.
+//!
+//! A condition is an expression over the reader's state, e.g. `has_key`, `gold >= 10`
+//! or `not met_wizard and (torches > 0 or lantern)`. A name stands for a counter's value,
+//! how many of that item the reader carries, or 1 for a set flag; anything never set is 0.
+//!
+//! Effects are statements separated by `;` or new lines: `set met_wizard`,
+//! `unset met_wizard`, `add key`, `remove key`, `gold = 5`, `gold += 10`, `gold -= 3`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// Longest condition or effects text accepted, in characters.
+pub const MAX_SOURCE_CHARS: usize = 200;
+
+/// Longest variable name accepted, in characters.
+pub const MAX_NAME_CHARS: usize = 32;
+
+/// A node's condition and effects, as written. Both empty means the node has none.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct NodeMechanics {
+    pub node_id: String,
+    /// Must hold for readers to choose the node, however they reach it. Empty always holds.
+    pub condition: String,
+    /// Applied when a reader enters the node.
+    pub effects: String,
+}
+
+impl NodeMechanics {
+    pub fn is_empty(&self) -> bool {
+        self.condition.trim().is_empty() && self.effects.trim().is_empty()
+    }
+}
+
+/// Why a condition or effects text couldn't be parsed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError(pub String);
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+fn error<T>(message: impl Into<String>) -> Result<T, ParseError> {
+    Err(ParseError(message.into()))
+}
+
+/// Everything the reader has picked up along their path.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct GameState {
+    pub flags: BTreeSet<String>,
+    pub items: BTreeMap<String, u32>,
+    pub counters: BTreeMap<String, i64>,
+}
+
+impl GameState {
+    /// What `name` stands for in a condition.
+    pub fn value(&self, name: &str) -> i64 {
+        if let Some(&n) = self.counters.get(name) {
+            n
+        } else if let Some(&n) = self.items.get(name) {
+            n.into()
+        } else {
+            self.flags.contains(name).into()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.flags.is_empty() && self.items.is_empty() && self.counters.is_empty()
+    }
+
+    pub fn apply(&mut self, effects: &[Effect]) {
+        for effect in effects {
+            match effect {
+                Effect::Set(name) => {
+                    self.flags.insert(name.clone());
+                }
+                Effect::Unset(name) => {
+                    self.flags.remove(name);
+                }
+                Effect::Add(name) => *self.items.entry(name.clone()).or_default() += 1,
+                Effect::Remove(name) => {
+                    if let Some(n) = self.items.get_mut(name) {
+                        *n -= 1;
+                        if *n == 0 {
+                            self.items.remove(name);
+                        }
+                    }
+                }
+                Effect::Assign(name, n) => {
+                    self.counters.insert(name.clone(), *n);
+                }
+                Effect::Change(name, n) => {
+                    let counter = self.counters.entry(name.clone()).or_default();
+                    *counter = counter.saturating_add(*n);
+                }
+            }
+        }
+    }
+}
+
+/// One change a node makes to the reader's state.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Effect {
+    Set(String),
+    Unset(String),
+    Add(String),
+    Remove(String),
+    Assign(String, i64),
+    /// Adds to a counter; negative for `-=`.
+    Change(String, i64),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// A parsed condition.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Condition {
+    Always,
+    Value(Operand),
+    Compare(Operand, Comparison, Operand),
+    Not(Box<Condition>),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operand {
+    Name(String),
+    Number(i64),
+}
+
+impl Operand {
+    fn value(&self, state: &GameState) -> i64 {
+        match self {
+            Operand::Name(name) => state.value(name),
+            Operand::Number(n) => *n,
+        }
+    }
+}
+
+impl Condition {
+    pub fn holds(&self, state: &GameState) -> bool {
+        match self {
+            Condition::Always => true,
+            Condition::Value(operand) => operand.value(state) != 0,
+            Condition::Compare(a, comparison, b) => {
+                let (a, b) = (a.value(state), b.value(state));
+                match comparison {
+                    Comparison::Eq => a == b,
+                    Comparison::Ne => a != b,
+                    Comparison::Lt => a < b,
+                    Comparison::Le => a <= b,
+                    Comparison::Gt => a > b,
+                    Comparison::Ge => a >= b,
+                }
+            }
+            Condition::Not(c) => !c.holds(state),
+            Condition::And(a, b) => a.holds(state) && b.holds(state),
+            Condition::Or(a, b) => a.holds(state) || b.holds(state),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Name(String),
+    Number(i64),
+    Op(&'static str),
+    Open,
+    Close,
+}
+
+const OPERATORS: [&str; 11] = ["==", "!=", "<=", ">=", "+=", "-=", "&&", "||", "<", ">", "!"];
+
+fn check_length(source: &str) -> Result<(), ParseError> {
+    if source.chars().count() > MAX_SOURCE_CHARS {
+        return error(format!("Must be at most {} characters", MAX_SOURCE_CHARS));
+    }
+    Ok(())
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = if c.is_ascii_alphabetic() || c == '_' {
+            let len = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len());
+            if len > MAX_NAME_CHARS {
+                return error(format!("Names must be at most {} characters", MAX_NAME_CHARS));
+            }
+            tokens.push(Token::Name(rest[..len].to_string()));
+            len
+        } else if c.is_ascii_digit() {
+            let len = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+            let Ok(n) = rest[..len].parse() else {
+                return error(format!("{} is too large", &rest[..len]));
+            };
+            tokens.push(Token::Number(n));
+            len
+        } else if c == '(' || c == ')' {
+            tokens.push(if c == '(' { Token::Open } else { Token::Close });
+            1
+        } else if let Some(op) = OPERATORS.into_iter().find(|op| rest.starts_with(op)) {
+            tokens.push(Token::Op(op));
+            op.len()
+        } else if c == '=' || c == '-' {
+            tokens.push(Token::Op(if c == '=' { "=" } else { "-" }));
+            1
+        } else {
+            return error(format!("Unexpected {:?}", c));
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    /// Consumes the next token if it's the keyword or operator `word`.
+    fn eat(&mut self, word: &str) -> bool {
+        let found = match self.peek() {
+            Some(Token::Name(name)) => name == word,
+            Some(Token::Op(op)) => *op == word,
+            _ => false,
+        };
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Condition, ParseError> {
+        let mut condition = self.and()?;
+        while self.eat("or") || self.eat("||") {
+            condition = Condition::Or(Box::new(condition), Box::new(self.and()?));
+        }
+        Ok(condition)
+    }
+
+    fn and(&mut self) -> Result<Condition, ParseError> {
+        let mut condition = self.not()?;
+        while self.eat("and") || self.eat("&&") {
+            condition = Condition::And(Box::new(condition), Box::new(self.not()?));
+        }
+        Ok(condition)
+    }
+
+    fn not(&mut self) -> Result<Condition, ParseError> {
+        if self.eat("not") || self.eat("!") {
+            return Ok(Condition::Not(Box::new(self.not()?)));
+        }
+        if self.peek() == Some(&Token::Open) {
+            self.pos += 1;
+            let condition = self.or()?;
+            if self.next() != Some(Token::Close) {
+                return error("Missing )");
+            }
+            return Ok(condition);
+        }
+        let a = self.operand()?;
+        let comparison = match self.peek() {
+            Some(Token::Op("==")) => Comparison::Eq,
+            Some(Token::Op("!=")) => Comparison::Ne,
+            Some(Token::Op("<")) => Comparison::Lt,
+            Some(Token::Op("<=")) => Comparison::Le,
+            Some(Token::Op(">")) => Comparison::Gt,
+            Some(Token::Op(">=")) => Comparison::Ge,
+            _ => return Ok(Condition::Value(a)),
+        };
+        self.pos += 1;
+        Ok(Condition::Compare(a, comparison, self.operand()?))
+    }
+
+    fn operand(&mut self) -> Result<Operand, ParseError> {
+        match self.next() {
+            Some(Token::Name(name)) if name == "true" => Ok(Operand::Number(1)),
+            Some(Token::Name(name)) if name == "false" => Ok(Operand::Number(0)),
+            Some(Token::Name(name)) if is_keyword(&name) => error(format!("Expected a name or number before {}", name)),
+            Some(Token::Name(name)) => Ok(Operand::Name(name)),
+            Some(Token::Number(n)) => Ok(Operand::Number(n)),
+            Some(Token::Op("-")) => match self.next() {
+                Some(Token::Number(n)) => Ok(Operand::Number(-n)),
+                _ => error("Expected a number after -"),
+            },
+            Some(_) => error("Expected a name or number"),
+            None => error("Unexpected end"),
+        }
+    }
+
+    fn name(&mut self) -> Result<String, ParseError> {
+        match self.next() {
+            Some(Token::Name(name)) if is_name(&name) => Ok(name),
+            _ => error("Expected a name"),
+        }
+    }
+
+    fn number(&mut self) -> Result<i64, ParseError> {
+        match self.operand()? {
+            Operand::Number(n) => Ok(n),
+            Operand::Name(_) => error("Expected a number"),
+        }
+    }
+
+    fn effect(&mut self) -> Result<Effect, ParseError> {
+        let effect = if self.eat("set") {
+            Effect::Set(self.name()?)
+        } else if self.eat("unset") {
+            Effect::Unset(self.name()?)
+        } else if self.eat("add") {
+            Effect::Add(self.name()?)
+        } else if self.eat("remove") {
+            Effect::Remove(self.name()?)
+        } else {
+            let name = self.name()?;
+            if self.eat("=") {
+                Effect::Assign(name, self.number()?)
+            } else if self.eat("+=") {
+                Effect::Change(name, self.number()?)
+            } else if self.eat("-=") {
+                Effect::Change(name, self.number()?.saturating_neg())
+            } else {
+                return error(format!("Expected =, += or -= after {}", name));
+            }
+        };
+        Ok(effect)
+    }
+
+    fn finish(&self) -> Result<(), ParseError> {
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => error("Unexpected text at the end"),
+        }
+    }
+}
+
+fn is_keyword(name: &str) -> bool {
+    matches!(name, "and" | "or" | "not" | "set" | "unset" | "add" | "remove")
+}
+
+/// Whether `name` can be used as a variable.
+pub fn is_name(name: &str) -> bool {
+    matches!(tokenize(name).as_deref(), Ok([Token::Name(n)]) if !is_keyword(n) && n != "true" && n != "false")
+}
+
+/// Parses a condition. Empty text always holds.
+pub fn parse_condition(source: &str) -> Result<Condition, ParseError> {
+    check_length(source)?;
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        pos: 0,
+    };
+    if parser.peek().is_none() {
+        return Ok(Condition::Always);
+    }
+    let condition = parser.or()?;
+    parser.finish()?;
+    Ok(condition)
+}
+
+/// Parses effects, one per `;` or line.
+pub fn parse_effects(source: &str) -> Result<Vec<Effect>, ParseError> {
+    check_length(source)?;
+    source
+        .split([';', '\n'])
+        .filter(|statement| !statement.trim().is_empty())
+        .map(|statement| {
+            let mut parser = Parser {
+                tokens: tokenize(statement)?,
+                pos: 0,
+            };
+            let effect = parser.effect()?;
+            parser.finish()?;
+            Ok(effect)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(counters: &[(&str, i64)]) -> GameState {
+        GameState {
+            counters: counters.iter().map(|(name, n)| (name.to_string(), *n)).collect(),
+            ..GameState::default()
+        }
+    }
+
+    fn holds(source: &str, state: &GameState) -> bool {
+        parse_condition(source).unwrap().holds(state)
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let name = |n: &str| Box::new(Condition::Value(Operand::Name(n.into())));
+        assert_eq!(
+            parse_condition("a or b and c").unwrap(),
+            Condition::Or(name("a"), Box::new(Condition::And(name("b"), name("c")))),
+        );
+        assert!(holds("a or b and c", &state(&[("a", 1)])));
+        assert!(!holds("a or b and c", &state(&[("b", 1)])));
+        assert!(!holds("(a or b) and c", &state(&[("a", 1)])));
+    }
+
+    #[test]
+    fn not_applies_to_the_nearest_term() {
+        assert!(holds("not a and b", &state(&[("b", 1)])));
+        assert!(!holds("not (a or b)", &state(&[("b", 1)])));
+        assert!(holds("!a && b || c", &state(&[("c", 1)])));
+    }
+
+    #[test]
+    fn compares_names_and_negative_numbers() {
+        let poor = state(&[("gold", -2)]);
+        assert!(holds("gold > -5", &poor));
+        assert!(holds("gold == -2", &poor));
+        assert!(!holds("gold >= 0", &poor));
+        assert!(holds("never_set == 0 and true and not false", &poor));
+    }
+
+    #[test]
+    fn applies_effects_in_order() {
+        let mut state = GameState::default();
+        state.apply(&parse_effects("set met_wizard; add key\nadd key; gold = 5; gold += 10; gold -= 3").unwrap());
+        assert!(state.flags.contains("met_wizard"));
+        assert_eq!(state.value("key"), 2);
+        assert_eq!(state.value("gold"), 12);
+
+        state.apply(&parse_effects("unset met_wizard; remove key; remove key; gold -= -8; debt = -4").unwrap());
+        assert!(!state.flags.contains("met_wizard"));
+        assert!(!state.items.contains_key("key"));
+        assert_eq!(state.value("gold"), 20);
+        assert_eq!(state.value("debt"), -4);
+    }
+
+    #[test]
+    fn changes_saturate_instead_of_overflowing() {
+        let mut state = state(&[("gold", i64::MAX)]);
+        state.apply(&[Effect::Change("gold".into(), 1)]);
+        assert_eq!(state.value("gold"), i64::MAX);
+    }
+
+    #[test]
+    fn rejects_keywords_as_names() {
+        assert!(parse_condition("and").is_err());
+        assert!(parse_condition("gold > or").is_err());
+        assert!(parse_effects("set and").is_err());
+        assert!(parse_effects("remove = 3").is_err());
+        assert!(parse_effects("true = 3").is_err());
+        assert!(!is_name("not"));
+        assert!(!is_name("false"));
+        assert!(is_name("lockpicking"));
+    }
+
+    #[test]
+    fn rejects_overlong_sources_and_names() {
+        let long = "a".repeat(MAX_SOURCE_CHARS + 1);
+        assert!(parse_condition(&long).is_err());
+        assert!(parse_effects(&format!("x = 1; {}", long)).is_err());
+        assert!(parse_condition(&"a".repeat(MAX_NAME_CHARS)).is_ok());
+        assert!(parse_condition(&"a".repeat(MAX_NAME_CHARS + 1)).is_err());
+    }
+
+    #[test]
+    fn rejects_numbers_too_large_for_a_counter() {
+        assert!(parse_condition("gold > 9223372036854775807").is_ok());
+        assert!(parse_condition("gold > 9223372036854775808").is_err());
+        assert!(parse_effects("gold = 99999999999999999999").is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_text() {
+        assert!(parse_condition("(gold > 1").is_err());
+        assert!(parse_condition("gold >").is_err());
+        assert!(parse_condition("gold $ 1").is_err());
+        assert!(parse_effects("gold").is_err());
+        assert!(parse_effects("gold += key").is_err());
+        assert!(parse_effects("set a b").is_err());
+    }
+
+    #[test]
+    fn empty_text_means_no_rules() {
+        assert_eq!(parse_condition("  ").unwrap(), Condition::Always);
+        assert!(parse_effects("; \n ;").unwrap().is_empty());
+    }
+}